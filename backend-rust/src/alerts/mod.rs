@@ -0,0 +1,289 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::sync::{mpsc, RwLock};
+use tracing::{debug, info, warn};
+
+use crate::{storage, tmux, types::ServerMessage};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AlertKind {
+    Bell,
+    Activity,
+    Silence,
+}
+
+/// Which alerts a window opted into.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WindowAlertSettings {
+    pub session_name: String,
+    pub window_index: u32,
+    #[serde(default)]
+    pub bell: bool,
+    #[serde(default)]
+    pub activity: bool,
+    /// Alert after this many seconds without output.
+    pub silence_seconds: Option<u32>,
+}
+
+impl WindowAlertSettings {
+    fn is_empty(&self) -> bool {
+        !self.bell && !self.activity && self.silence_seconds.unwrap_or(0) == 0
+    }
+
+    fn wants(&self, kind: AlertKind) -> bool {
+        match kind {
+            AlertKind::Bell => self.bell,
+            AlertKind::Activity => self.activity,
+            AlertKind::Silence => self.silence_seconds.unwrap_or(0) > 0,
+        }
+    }
+
+    fn target(&self) -> String {
+        format!("={}:{}", self.session_name, self.window_index)
+    }
+}
+
+/// Alert flags tmux has raised on a window.
+#[derive(Debug, Clone)]
+struct WindowFlags {
+    session_name: String,
+    window_index: u32,
+    window_id: String,
+    window_name: String,
+    raised: Vec<AlertKind>,
+}
+
+async fn list_window_flags() -> Result<Vec<WindowFlags>> {
+    let format = [
+        "#{session_name}",
+        "#{window_index}",
+        "#{window_id}",
+        "#{window_bell_flag}#{window_activity_flag}#{window_silence_flag}",
+        "#{window_name}",
+    ]
+    .join("\t");
+    let output = tmux::run_tmux(&["list-windows", "-a", "-F", &format]).await?;
+
+    Ok(output
+        .lines()
+        .filter_map(|line| {
+            let parts: Vec<&str> = line.splitn(5, '\t').collect();
+            if parts.len() < 5 {
+                return None;
+            }
+            let flags = parts[3].as_bytes();
+            let raised = [AlertKind::Bell, AlertKind::Activity, AlertKind::Silence]
+                .into_iter()
+                .enumerate()
+                .filter(|(i, _)| flags.get(*i) == Some(&b'1'))
+                .map(|(_, kind)| kind)
+                .collect();
+            Some(WindowFlags {
+                session_name: parts[0].to_string(),
+                window_index: parts[1].parse().ok()?,
+                window_id: parts[2].to_string(),
+                raised,
+                window_name: parts[4].to_string(),
+            })
+        })
+        .collect())
+}
+
+/// Alerts newly raised since the last poll, for windows that opted in.
+/// `seen` is updated to the flags currently raised.
+fn new_alerts(
+    settings: &[WindowAlertSettings],
+    windows: &[WindowFlags],
+    seen: &mut HashSet<(String, AlertKind)>,
+) -> Vec<ServerMessage> {
+    let mut alerts = Vec::new();
+    let mut raised_now = HashSet::new();
+    for window in windows {
+        let Some(setting) = settings
+            .iter()
+            .find(|s| s.session_name == window.session_name && s.window_index == window.window_index)
+        else {
+            continue;
+        };
+        for kind in &window.raised {
+            if !setting.wants(*kind) {
+                continue;
+            }
+            let key = (window.window_id.clone(), *kind);
+            if !seen.contains(&key) {
+                alerts.push(ServerMessage::WindowAlert {
+                    session_name: window.session_name.clone(),
+                    window_index: window.window_index,
+                    window_name: window.window_name.clone(),
+                    kind: *kind,
+                });
+            }
+            raised_now.insert(key);
+        }
+    }
+    *seen = raised_now;
+    alerts
+}
+
+/// Per-window alert settings, kept in `~/.webmux/alerts/settings.json`.
+pub struct AlertManager {
+    settings: RwLock<Vec<WindowAlertSettings>>,
+}
+
+impl AlertManager {
+    pub fn new() -> Self {
+        Self {
+            settings: RwLock::new(Vec::new()),
+        }
+    }
+
+    pub async fn initialize(&self) -> Result<()> {
+        let settings: Vec<WindowAlertSettings> = storage::load_json(&settings_path()?)?;
+        *self.settings.write().await = settings;
+        Ok(())
+    }
+
+    pub async fn get_settings(&self) -> Vec<WindowAlertSettings> {
+        self.settings.read().await.clone()
+    }
+
+    /// Replace one window's settings; turning everything off removes them.
+    pub async fn set_window(&self, window: WindowAlertSettings) -> Result<Vec<WindowAlertSettings>> {
+        apply(&window).await?;
+
+        let mut settings = self.settings.write().await;
+        settings.retain(|s| !(s.session_name == window.session_name && s.window_index == window.window_index));
+        if !window.is_empty() {
+            settings.push(window.clone());
+        }
+        storage::save_json(&settings_path()?, &*settings)?;
+        info!(
+            "Alert settings for {}:{}: {:?}",
+            window.session_name, window.window_index, window
+        );
+        Ok(settings.clone())
+    }
+
+    /// Poll tmux's alert flags and broadcast newly raised ones.
+    pub async fn run(&self, broadcast_tx: mpsc::UnboundedSender<ServerMessage>) {
+        let mut seen = HashSet::new();
+        // Window id each setting was last applied to, so recreated windows
+        // get their monitor options again
+        let mut applied: HashMap<(String, u32), String> = HashMap::new();
+        let mut ticker = tokio::time::interval(POLL_INTERVAL);
+
+        loop {
+            ticker.tick().await;
+            let settings = self.get_settings().await;
+            if settings.is_empty() {
+                seen.clear();
+                continue;
+            }
+            let windows = match list_window_flags().await {
+                Ok(windows) => windows,
+                Err(e) => {
+                    debug!("Failed to list windows for alerts: {}", e);
+                    continue;
+                }
+            };
+
+            for setting in &settings {
+                let key = (setting.session_name.clone(), setting.window_index);
+                let Some(window) = windows
+                    .iter()
+                    .find(|w| w.session_name == key.0 && w.window_index == key.1)
+                else {
+                    continue;
+                };
+                if applied.get(&key) != Some(&window.window_id) {
+                    match apply(setting).await {
+                        Ok(()) => {
+                            applied.insert(key, window.window_id.clone());
+                        }
+                        Err(e) => warn!("Failed to apply alert settings to {}: {}", setting.target(), e),
+                    }
+                }
+            }
+
+            for alert in new_alerts(&settings, &windows, &mut seen) {
+                if broadcast_tx.send(alert).is_err() {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// Turn on tmux's monitoring for the alerts a window wants. Options for the
+/// others are unset, falling back to the user's global configuration.
+async fn apply(settings: &WindowAlertSettings) -> Result<()> {
+    let target = settings.target();
+    let silence = settings.silence_seconds.filter(|s| *s > 0).map(|s| s.to_string());
+    for (option, value) in [
+        ("monitor-bell", settings.bell.then_some("on")),
+        ("monitor-activity", settings.activity.then_some("on")),
+        ("monitor-silence", silence.as_deref()),
+    ] {
+        match value {
+            Some(value) => tmux::run_tmux(&["set-option", "-w", "-t", &target, option, value]).await?,
+            None => tmux::run_tmux(&["set-option", "-w", "-u", "-t", &target, option]).await?,
+        };
+    }
+    Ok(())
+}
+
+fn settings_path() -> Result<PathBuf> {
+    Ok(storage::data_dir("alerts")?.join("settings.json"))
+}
+
+lazy_static::lazy_static! {
+    pub static ref ALERT_MANAGER: AlertManager = AlertManager::new();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn window(index: u32, id: &str, raised: Vec<AlertKind>) -> WindowFlags {
+        WindowFlags {
+            session_name: "work".into(),
+            window_index: index,
+            window_id: id.into(),
+            window_name: "build".into(),
+            raised,
+        }
+    }
+
+    #[test]
+    fn reports_each_raised_flag_once() {
+        let settings = vec![WindowAlertSettings {
+            session_name: "work".into(),
+            window_index: 1,
+            bell: true,
+            activity: false,
+            silence_seconds: None,
+        }];
+        let mut seen = HashSet::new();
+
+        let raised = [window(1, "@1", vec![AlertKind::Bell, AlertKind::Activity]), window(2, "@2", vec![AlertKind::Bell])];
+        let alerts = new_alerts(&settings, &raised, &mut seen);
+        assert_eq!(alerts.len(), 1);
+        assert!(matches!(
+            &alerts[0],
+            ServerMessage::WindowAlert { window_index: 1, kind: AlertKind::Bell, .. }
+        ));
+
+        // Still raised: nothing new
+        assert!(new_alerts(&settings, &raised, &mut seen).is_empty());
+        // Cleared, then raised again
+        assert!(new_alerts(&settings, &[window(1, "@1", vec![])], &mut seen).is_empty());
+        assert_eq!(new_alerts(&settings, &raised, &mut seen).len(), 1);
+    }
+}
@@ -0,0 +1,539 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+use tracing::{debug, info};
+
+use crate::{
+    control::{PaneEvent, Subscription, CONTROL_HUB},
+    export::strip_ansi,
+    terminal_buffer::Utf8StreamDecoder,
+    tmux,
+    types::PaneTarget,
+};
+
+const DEFAULT_TIMEOUT_SECS: u32 = 30;
+/// Output kept for matching while no expect consumes it.
+const MAX_BUFFER_BYTES: usize = 64 * 1024;
+/// Steps a run may execute, so a `goto` loop without an expect ends.
+const MAX_EXECUTED_STEPS: usize = 1000;
+/// Finished runs kept for listing.
+const MAX_FINISHED_RUNS: usize = 50;
+
+/// A pattern an expect step waits for, and where to continue once it
+/// matches. Without `goto` the run moves on to the next step.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExpectCase {
+    pub pattern: String,
+    pub goto: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "kebab-case")]
+pub enum StepAction {
+    /// Type text literally, followed by Enter unless `enter` is false.
+    Send {
+        text: String,
+        #[serde(default = "default_true")]
+        enter: bool,
+        /// Keep the text out of status messages, e.g. for passwords.
+        #[serde(default)]
+        secret: bool,
+    },
+    /// Press a key by its tmux name, e.g. `Enter`, `C-c` or `Escape`.
+    Key { key: String },
+    /// Wait for output matching `pattern` or one of `cases`; patterns are
+    /// tried in order against the output since the previous match.
+    #[serde(rename_all = "camelCase")]
+    Expect {
+        pattern: Option<String>,
+        #[serde(default)]
+        cases: Vec<ExpectCase>,
+        timeout_seconds: Option<u32>,
+        /// Label to continue at on timeout; the run fails without one.
+        on_timeout: Option<String>,
+    },
+    Sleep { ms: u64 },
+    /// Continue at the step with label `to`.
+    Goto { to: String },
+    /// End the run as failed.
+    Fail { message: Option<String> },
+    /// End the run as succeeded.
+    Done,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Step {
+    pub label: Option<String>,
+    #[serde(flatten)]
+    pub action: StepAction,
+}
+
+/// Steps run against one pane. Missing indexes fall back to the current
+/// window and active pane.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AutomationScript {
+    pub name: Option<String>,
+    #[serde(flatten)]
+    pub target: PaneTarget,
+    pub steps: Vec<Step>,
+    /// Default timeout for expect steps.
+    pub timeout_seconds: Option<u32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RunState {
+    Running,
+    Succeeded,
+    Failed,
+    Stopped,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RunStatus {
+    pub run_id: String,
+    pub name: Option<String>,
+    pub session_name: String,
+    pub state: RunState,
+    /// Index of the current (or last) step.
+    pub step: usize,
+    pub step_count: usize,
+    pub label: Option<String>,
+    /// What the step is doing, e.g. what was sent or matched.
+    pub detail: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl RunStatus {
+    fn new(run_id: String, script: &AutomationScript) -> Self {
+        Self {
+            run_id,
+            name: script.name.clone(),
+            session_name: script.target.session_name.clone(),
+            state: RunState::Running,
+            step: 0,
+            step_count: script.steps.len(),
+            label: script.steps.first().and_then(|s| s.label.clone()),
+            detail: None,
+            started_at: Utc::now(),
+            finished_at: None,
+            error: None,
+        }
+    }
+
+    fn finish(&mut self, state: RunState, error: Option<String>) {
+        self.state = state;
+        self.error = error;
+        self.finished_at = Some(Utc::now());
+    }
+
+    /// A run that couldn't start.
+    pub fn rejected(run_id: String, script: &AutomationScript, error: String) -> Self {
+        let mut status = Self::new(run_id, script);
+        status.finish(RunState::Failed, Some(error));
+        status
+    }
+}
+
+/// A script checked and ready to run: regexes compiled and labels resolved
+/// to step indexes.
+struct Program {
+    steps: Vec<Step>,
+    labels: HashMap<String, usize>,
+    patterns: Vec<Vec<(Regex, Option<usize>)>>,
+    timeout: Duration,
+}
+
+impl Program {
+    fn compile(script: &AutomationScript) -> Result<Self> {
+        if script.steps.is_empty() {
+            anyhow::bail!("Script has no steps");
+        }
+        let mut labels = HashMap::new();
+        for (index, step) in script.steps.iter().enumerate() {
+            if let Some(label) = &step.label {
+                if labels.insert(label.clone(), index).is_some() {
+                    anyhow::bail!("Duplicate label: {}", label);
+                }
+            }
+        }
+        let resolve = |label: &str| {
+            labels
+                .get(label)
+                .copied()
+                .ok_or_else(|| anyhow::anyhow!("Unknown label: {}", label))
+        };
+
+        let mut patterns = Vec::new();
+        for step in &script.steps {
+            let mut compiled = Vec::new();
+            match &step.action {
+                StepAction::Expect { pattern, cases, on_timeout, .. } => {
+                    let cases = pattern
+                        .iter()
+                        .map(|pattern| (pattern, None))
+                        .chain(cases.iter().map(|case| (&case.pattern, case.goto.as_ref())));
+                    for (pattern, goto) in cases {
+                        let regex = Regex::new(pattern)?;
+                        compiled.push((regex, goto.map(|label| resolve(label)).transpose()?));
+                    }
+                    if compiled.is_empty() {
+                        anyhow::bail!("Expect step without a pattern");
+                    }
+                    if let Some(label) = on_timeout {
+                        resolve(label)?;
+                    }
+                }
+                StepAction::Goto { to } => {
+                    resolve(to)?;
+                }
+                _ => {}
+            }
+            patterns.push(compiled);
+        }
+
+        Ok(Self {
+            steps: script.steps.clone(),
+            labels,
+            patterns,
+            timeout: Duration::from_secs(script.timeout_seconds.unwrap_or(DEFAULT_TIMEOUT_SECS) as u64),
+        })
+    }
+}
+
+/// Pane output not yet consumed by an expect, escape sequences removed
+/// when matching.
+#[derive(Default)]
+struct OutputBuffer {
+    raw: String,
+}
+
+impl OutputBuffer {
+    fn push(&mut self, text: &str) {
+        self.raw.push_str(text);
+        if self.raw.len() > MAX_BUFFER_BYTES {
+            let mut cut = self.raw.len() - MAX_BUFFER_BYTES;
+            while !self.raw.is_char_boundary(cut) {
+                cut += 1;
+            }
+            self.raw.drain(..cut);
+        }
+    }
+
+    /// The first case whose pattern matches, with the matched text. Output
+    /// up to the end of the match is consumed.
+    fn take_match(&mut self, cases: &[(Regex, Option<usize>)]) -> Option<(usize, String)> {
+        let text = strip_ansi(&self.raw).replace('\r', "");
+        let (index, found) = cases
+            .iter()
+            .enumerate()
+            .find_map(|(index, (regex, _))| regex.find(&text).map(|m| (index, m)))?;
+        let matched = found.as_str().to_string();
+        self.raw = text[found.end()..].to_string();
+        Some((index, matched))
+    }
+}
+
+enum Outcome {
+    Succeeded,
+    Failed(String),
+}
+
+type Reporter = Arc<dyn Fn(&RunStatus) + Send + Sync>;
+
+struct Run {
+    status: RunStatus,
+    report: Reporter,
+    handle: Option<JoinHandle<()>>,
+}
+
+/// Automation runs, in progress and recently finished.
+pub struct AutomationRunner {
+    runs: RwLock<HashMap<String, Run>>,
+}
+
+impl AutomationRunner {
+    pub fn new() -> Self {
+        Self {
+            runs: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Start running `script`. `report` receives the status whenever the
+    /// run moves to another step or finishes.
+    pub async fn start(
+        &'static self,
+        run_id: String,
+        script: AutomationScript,
+        report: impl Fn(&RunStatus) + Send + Sync + 'static,
+    ) -> Result<()> {
+        let program = Program::compile(&script)?;
+        if self.runs.read().await.contains_key(&run_id) {
+            anyhow::bail!("Run {} already exists", run_id);
+        }
+        let pane_id = tmux::run_tmux(&["display-message", "-p", "-t", &script.target.tmux_target(), "#{pane_id}"])
+            .await?
+            .trim()
+            .to_string();
+        if pane_id.is_empty() {
+            anyhow::bail!("Pane not found: {}", script.target.tmux_target());
+        }
+
+        // Subscribe before looking at the screen so nothing printed in
+        // between is lost. Whatever is on the cursor line already counts,
+        // so a prompt shown before the run started can still be expected.
+        let subscription = CONTROL_HUB
+            .subscribe(HashSet::from([script.target.session_name.clone()]))
+            .await;
+        let mut output = OutputBuffer::default();
+        let visible = tmux::capture_visible(&script.target, false).await?;
+        if let Some(line) = visible.lines().rev().find(|line| !line.trim().is_empty()) {
+            output.push(line);
+        }
+
+        let status = RunStatus::new(run_id.clone(), &script);
+        let report: Reporter = Arc::new(report);
+        info!("Starting automation {} on {}", run_id, script.target.tmux_target());
+
+        let mut runs = self.runs.write().await;
+        prune_finished(&mut runs);
+        let handle = tokio::spawn({
+            let run_id = run_id.clone();
+            async move {
+                let outcome = self.execute(&run_id, &program, &pane_id, output, subscription).await;
+                let (state, error) = match outcome {
+                    Outcome::Succeeded => (RunState::Succeeded, None),
+                    Outcome::Failed(error) => (RunState::Failed, Some(error)),
+                };
+                debug!("Automation {} finished: {:?} {:?}", run_id, state, error);
+                self.update(&run_id, |status| status.finish(state, error)).await;
+            }
+        });
+        runs.insert(
+            run_id,
+            Run {
+                status,
+                report,
+                handle: Some(handle),
+            },
+        );
+        Ok(())
+    }
+
+    pub async fn stop(&self, run_id: &str) -> Result<RunStatus> {
+        let mut runs = self.runs.write().await;
+        let run = runs
+            .get_mut(run_id)
+            .ok_or_else(|| anyhow::anyhow!("Run not found: {}", run_id))?;
+        if let Some(handle) = run.handle.take() {
+            handle.abort();
+        }
+        if run.status.state == RunState::Running {
+            run.status.finish(RunState::Stopped, None);
+            (run.report)(&run.status);
+        }
+        Ok(run.status.clone())
+    }
+
+    /// Every known run, newest first.
+    pub async fn list(&self) -> Vec<RunStatus> {
+        let mut runs: Vec<RunStatus> = self.runs.read().await.values().map(|run| run.status.clone()).collect();
+        runs.sort_by_key(|run| std::cmp::Reverse(run.started_at));
+        runs
+    }
+
+    async fn update(&self, run_id: &str, change: impl FnOnce(&mut RunStatus)) {
+        let mut runs = self.runs.write().await;
+        if let Some(run) = runs.get_mut(run_id) {
+            if run.status.state != RunState::Running {
+                return;
+            }
+            change(&mut run.status);
+            if run.status.state != RunState::Running {
+                run.handle = None;
+            }
+            (run.report)(&run.status);
+        }
+    }
+
+    async fn execute(
+        &self,
+        run_id: &str,
+        program: &Program,
+        pane_id: &str,
+        mut output: OutputBuffer,
+        mut subscription: Subscription,
+    ) -> Outcome {
+        let mut decoder = Utf8StreamDecoder::new();
+        let mut current = 0;
+        let mut detail = None;
+
+        for _ in 0..MAX_EXECUTED_STEPS {
+            let Some(step) = program.steps.get(current) else {
+                return Outcome::Succeeded;
+            };
+            self.update(run_id, |status| {
+                status.step = current;
+                status.label = step.label.clone();
+                status.detail = detail.take();
+            })
+            .await;
+
+            let mut next = current + 1;
+            let result: Result<Option<String>> = match &step.action {
+                StepAction::Send { text, enter, secret } => async {
+                    tmux::send_keys_to_session(pane_id, text).await?;
+                    if *enter {
+                        tmux::send_special_key(pane_id, "Enter").await?;
+                    }
+                    Ok(Some(if *secret { "Sent hidden text".to_string() } else { format!("Sent {:?}", text) }))
+                }
+                .await,
+                StepAction::Key { key } => tmux::send_special_key(pane_id, key)
+                    .await
+                    .map(|()| Some(format!("Pressed {}", key))),
+                StepAction::Expect { timeout_seconds, on_timeout, .. } => {
+                    let timeout = timeout_seconds
+                        .map(|s| Duration::from_secs(s as u64))
+                        .unwrap_or(program.timeout);
+                    let deadline = Instant::now() + timeout;
+                    let cases = &program.patterns[current];
+                    loop {
+                        if let Some((index, matched)) = output.take_match(cases) {
+                            if let Some(goto) = cases[index].1 {
+                                next = goto;
+                            }
+                            break Ok(Some(format!("Matched {:?}", matched)));
+                        }
+                        match tokio::time::timeout_at(deadline, subscription.events.recv()).await {
+                            Err(_) => match on_timeout {
+                                Some(label) => {
+                                    next = program.labels[label];
+                                    break Ok(Some(format!("Timed out after {}s", timeout.as_secs())));
+                                }
+                                None => break Err(anyhow::anyhow!("Timed out after {}s", timeout.as_secs())),
+                            },
+                            Ok(Some(PaneEvent::Output { pane_id: from, data, .. })) if from == pane_id => {
+                                output.push(&decoder.decode_chunk(&data).0);
+                            }
+                            Ok(Some(PaneEvent::PaneClosed(closed))) if closed == pane_id => {
+                                break Err(anyhow::anyhow!("Pane closed"));
+                            }
+                            Ok(None) | Ok(Some(PaneEvent::SessionClosed(_))) => {
+                                break Err(anyhow::anyhow!("Session closed"));
+                            }
+                            Ok(Some(_)) => {}
+                        }
+                    }
+                }
+                StepAction::Sleep { ms } => {
+                    tokio::time::sleep(Duration::from_millis(*ms)).await;
+                    Ok(None)
+                }
+                StepAction::Goto { to } => {
+                    next = program.labels[to];
+                    Ok(None)
+                }
+                StepAction::Fail { message } => {
+                    return Outcome::Failed(message.clone().unwrap_or_else(|| "Script failed".to_string()));
+                }
+                StepAction::Done => return Outcome::Succeeded,
+            };
+
+            match result {
+                Ok(d) => detail = d,
+                Err(e) => return Outcome::Failed(e.to_string()),
+            }
+            current = next;
+        }
+        Outcome::Failed(format!("Stopped after {} steps", MAX_EXECUTED_STEPS))
+    }
+}
+
+fn prune_finished(runs: &mut HashMap<String, Run>) {
+    let mut finished: Vec<(DateTime<Utc>, String)> = runs
+        .iter()
+        .filter(|(_, run)| run.status.state != RunState::Running)
+        .map(|(id, run)| (run.status.started_at, id.clone()))
+        .collect();
+    if finished.len() < MAX_FINISHED_RUNS {
+        return;
+    }
+    finished.sort();
+    for (_, id) in finished.iter().take(finished.len() + 1 - MAX_FINISHED_RUNS) {
+        runs.remove(id);
+    }
+}
+
+lazy_static::lazy_static! {
+    pub static ref AUTOMATIONS: AutomationRunner = AutomationRunner::new();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn script(json: serde_json::Value) -> AutomationScript {
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[test]
+    fn compiles_labels_and_cases() {
+        let program = Program::compile(&script(serde_json::json!({
+            "sessionName": "work",
+            "steps": [
+                { "action": "send", "text": "ssh host" },
+                { "label": "login", "action": "expect", "timeoutSeconds": 5, "cases": [
+                    { "pattern": "password:", "goto": "password" },
+                    { "pattern": "\\$ $" }
+                ] },
+                { "action": "done" },
+                { "label": "password", "action": "send", "text": "hunter2", "secret": true },
+                { "action": "goto", "to": "login" }
+            ]
+        })))
+        .unwrap();
+        assert_eq!(program.labels["password"], 3);
+        assert_eq!(program.patterns[1].len(), 2);
+        assert_eq!(program.patterns[1][0].1, Some(3));
+
+        let unknown = script(serde_json::json!({
+            "sessionName": "work",
+            "steps": [{ "action": "expect", "pattern": "x", "onTimeout": "missing" }]
+        }));
+        assert!(Program::compile(&unknown).is_err());
+    }
+
+    #[test]
+    fn consumes_output_up_to_each_match() {
+        let cases = |patterns: &[&str]| -> Vec<(Regex, Option<usize>)> {
+            patterns.iter().map(|p| (Regex::new(p).unwrap(), None)).collect()
+        };
+        let mut output = OutputBuffer::default();
+        output.push("Last login\r\n\x1b[1mhost\x1b[0m$ ");
+        assert_eq!(output.take_match(&cases(&["nope", r"\$ $"])), Some((1, "$ ".to_string())));
+        // The prompt was consumed, so it doesn't match again
+        assert_eq!(output.take_match(&cases(&[r"\$ $"])), None);
+
+        output.push("[sudo] pass");
+        assert_eq!(output.take_match(&cases(&["password"])), None);
+        output.push("word for me: ");
+        assert_eq!(output.take_match(&cases(&["password"])).unwrap().0, 0);
+    }
+}
@@ -1,15 +1,14 @@
 use std::fs::File;
 use std::io::{BufRead, BufReader, Seek, SeekFrom};
 use std::path::{Path, PathBuf};
-use std::process::Stdio;
 
 use anyhow::{bail, Context, Result};
 use notify::{RecursiveMode, Watcher};
-use tokio::process::Command;
 use tokio::sync::mpsc;
 use tracing::{debug, error, info, warn};
 
 use super::{claude_parser, codex_parser, AiTool, ChatLogEvent, ChatMessage};
+use crate::procinfo::{get_descendant_pids, get_pane_pid, get_process_cwd, process_name};
 
 // ---------------------------------------------------------------------------
 // Log file detection
@@ -205,103 +204,6 @@ fn parse_line(line: &str, tool: &AiTool) -> Option<ChatMessage> {
     }
 }
 
-// ---------------------------------------------------------------------------
-// Process-tree helpers
-// ---------------------------------------------------------------------------
-
-/// Ask tmux for the PID of the primary pane in the given target.
-async fn get_pane_pid(target: &str) -> Result<u32> {
-    let output = Command::new("tmux")
-        .args(["display-message", "-t", target, "-p", "#{pane_pid}"])
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .output()
-        .await
-        .context("failed to run tmux display-message")?;
-
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        bail!("tmux display-message failed: {stderr}");
-    }
-
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    let pid: u32 = stdout
-        .trim()
-        .parse()
-        .with_context(|| format!("invalid pane PID: {stdout}"))?;
-
-    Ok(pid)
-}
-
-/// Recursively collect all descendant PIDs of `parent_pid` via procfs.
-///
-/// This avoids shelling out to `ps` on every call and works on Linux by
-/// scanning `/proc/*/stat` for processes whose PPID matches.
-fn get_descendant_pids(parent_pid: u32) -> Result<Vec<u32>> {
-    let mut result = vec![parent_pid];
-    let mut queue = vec![parent_pid];
-
-    while let Some(ppid) = queue.pop() {
-        for child in direct_children(ppid) {
-            result.push(child);
-            queue.push(child);
-        }
-    }
-
-    Ok(result)
-}
-
-/// Return the immediate child PIDs of `ppid` by scanning `/proc`.
-fn direct_children(ppid: u32) -> Vec<u32> {
-    let mut children = Vec::new();
-    let Ok(entries) = std::fs::read_dir("/proc") else {
-        return children;
-    };
-
-    for entry in entries.flatten() {
-        let name = entry.file_name();
-        let Some(pid) = name.to_str().and_then(|s| s.parse::<u32>().ok()) else {
-            continue;
-        };
-
-        // /proc/<pid>/stat format: pid (comm) state ppid ...
-        let stat_path = format!("/proc/{pid}/stat");
-        let Ok(stat) = std::fs::read_to_string(&stat_path) else {
-            continue;
-        };
-
-        // The comm field can contain spaces and parentheses, so find the last
-        // ')' to locate the end of the comm field reliably.
-        let Some(after_comm) = stat.rfind(')') else {
-            continue;
-        };
-        let fields: Vec<&str> = stat[after_comm + 2..].split_whitespace().collect();
-        // fields[0] = state, fields[1] = ppid
-        if let Some(parent) = fields.get(1).and_then(|s| s.parse::<u32>().ok()) {
-            if parent == ppid {
-                children.push(pid);
-            }
-        }
-    }
-
-    children
-}
-
-/// Read the executable name for a given PID from `/proc/<pid>/comm`.
-fn process_name(pid: u32) -> Option<String> {
-    let path = format!("/proc/{pid}/comm");
-    std::fs::read_to_string(path)
-        .ok()
-        .map(|s| s.trim().to_string())
-}
-
-/// Read the current working directory of a process via its `/proc` symlink.
-fn get_process_cwd(pid: u32) -> Result<PathBuf> {
-    let link = format!("/proc/{pid}/cwd");
-    std::fs::read_link(&link)
-        .with_context(|| format!("failed to read {link}"))
-}
-
 // ---------------------------------------------------------------------------
 // Log file location helpers
 // ---------------------------------------------------------------------------
@@ -0,0 +1,115 @@
+use base64::Engine;
+use serde::Serialize;
+
+const OSC52: &str = "\x1b]52;";
+/// Longest unterminated sequence held across reads before giving up on it.
+const MAX_PENDING: usize = 4 * 1024 * 1024;
+
+/// Text an application put on the clipboard with OSC 52.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ClipboardEvent {
+    /// Selection parameter, e.g. `c` for the clipboard or `p` for primary.
+    pub selection: String,
+    pub text: String,
+}
+
+/// Picks OSC 52 clipboard sequences out of a terminal output stream,
+/// including ones split across reads.
+#[derive(Default)]
+pub struct Osc52Scanner {
+    pending: String,
+}
+
+impl Osc52Scanner {
+    pub fn scan(&mut self, chunk: &str) -> Vec<ClipboardEvent> {
+        let joined;
+        let data = if self.pending.is_empty() {
+            chunk
+        } else {
+            self.pending.push_str(chunk);
+            joined = std::mem::take(&mut self.pending);
+            joined.as_str()
+        };
+
+        let mut events = Vec::new();
+        let mut rest = data;
+        while let Some(start) = rest.find(OSC52) {
+            let body = &rest[start + OSC52.len()..];
+            match find_terminator(body) {
+                Some((end, terminator_len)) => {
+                    events.extend(parse(&body[..end]));
+                    rest = &body[end + terminator_len..];
+                }
+                None => {
+                    if body.len() <= MAX_PENDING {
+                        self.pending = rest[start..].to_string();
+                    }
+                    return events;
+                }
+            }
+        }
+
+        // Keep a trailing partial introducer for the next read
+        if let Some(len) = (1..OSC52.len()).rev().find(|len| rest.ends_with(&OSC52[..*len])) {
+            self.pending = rest[rest.len() - len..].to_string();
+        }
+        events
+    }
+}
+
+/// Position and length of the BEL or ST ending an OSC body.
+pub fn find_terminator(body: &str) -> Option<(usize, usize)> {
+    let bel = body.find('\x07').map(|i| (i, 1));
+    let st = body.find("\x1b\\").map(|i| (i, 2));
+    match (bel, st) {
+        (Some(a), Some(b)) => Some(if a.0 < b.0 { a } else { b }),
+        (a, b) => a.or(b),
+    }
+}
+
+fn parse(body: &str) -> Option<ClipboardEvent> {
+    let (selection, data) = body.split_once(';')?;
+    // `?` asks the terminal for the clipboard; there's nothing to forward
+    if data == "?" {
+        return None;
+    }
+    let decoded = base64::engine::general_purpose::STANDARD.decode(data).ok()?;
+    Some(ClipboardEvent {
+        selection: if selection.is_empty() { "s0".to_string() } else { selection.to_string() },
+        text: String::from_utf8_lossy(&decoded).into_owned(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_sequences_with_either_terminator() {
+        let mut scanner = Osc52Scanner::default();
+        let events = scanner.scan("before\x1b]52;c;aGVsbG8=\x07middle\x1b]52;;d29ybGQ=\x1b\\after");
+
+        assert_eq!(
+            events,
+            vec![
+                ClipboardEvent { selection: "c".into(), text: "hello".into() },
+                ClipboardEvent { selection: "s0".into(), text: "world".into() },
+            ]
+        );
+    }
+
+    #[test]
+    fn joins_sequences_split_across_reads() {
+        let mut scanner = Osc52Scanner::default();
+        assert!(scanner.scan("text\x1b]5").is_empty());
+        assert!(scanner.scan("2;c;aGVs").is_empty());
+        assert_eq!(scanner.scan("bG8=\x07more")[0].text, "hello");
+        assert!(scanner.pending.is_empty());
+    }
+
+    #[test]
+    fn ignores_queries_and_bad_data() {
+        let mut scanner = Osc52Scanner::default();
+        assert!(scanner.scan("\x1b]52;c;?\x07\x1b]52;c;!!!\x07\x1b]0;title\x07").is_empty());
+    }
+}
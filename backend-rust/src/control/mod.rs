@@ -0,0 +1,396 @@
+use anyhow::Result;
+use std::collections::{HashMap, HashSet};
+use std::process::Stdio;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::process::{Child, Command};
+use tokio::sync::{mpsc, oneshot, Mutex, Notify};
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+use crate::tmux;
+
+/// How often the hub attaches to sessions that appeared since, reattaches
+/// lost clients and looks for closed panes.
+const RECONCILE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How long to wait for a new control client to finish attaching.
+const ATTACH_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// A notification from a tmux control-mode client.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ControlEvent {
+    /// Bytes a pane wrote, e.g. `%output %3 ...`.
+    Output { pane_id: String, data: Vec<u8> },
+    /// Windows or panes were added, closed or rearranged, so pane indexes
+    /// may have changed.
+    LayoutChanged,
+    /// The control client went away.
+    Exit(Option<String>),
+}
+
+/// Undo control mode's escaping: bytes below 0x20 and backslashes arrive
+/// as `\ooo` octal escapes.
+pub fn unescape(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len());
+    let mut i = 0;
+    while i < data.len() {
+        if data[i] == b'\\' && i + 4 <= data.len() && data[i + 1..i + 4].iter().all(|b| (b'0'..=b'7').contains(b)) {
+            let value = data[i + 1..i + 4].iter().fold(0u32, |acc, b| acc * 8 + (b - b'0') as u32);
+            out.push(value as u8);
+            i += 4;
+        } else {
+            out.push(data[i]);
+            i += 1;
+        }
+    }
+    out
+}
+
+/// Parse one line of control-mode output. Command replies and
+/// notifications nobody uses yet are skipped.
+pub fn parse_line(line: &[u8]) -> Option<ControlEvent> {
+    let line = line.strip_suffix(b"\n").unwrap_or(line);
+    let line = line.strip_suffix(b"\r").unwrap_or(line);
+    let (name, rest) = match line.iter().position(|b| *b == b' ') {
+        Some(space) => (&line[..space], &line[space + 1..]),
+        None => (line, &[][..]),
+    };
+
+    match name {
+        b"%output" => {
+            let space = rest.iter().position(|b| *b == b' ')?;
+            Some(ControlEvent::Output {
+                pane_id: String::from_utf8_lossy(&rest[..space]).into_owned(),
+                data: unescape(&rest[space + 1..]),
+            })
+        }
+        b"%layout-change" | b"%window-add" | b"%window-close" | b"%unlinked-window-close"
+        | b"%session-window-changed" | b"%window-pane-changed" => Some(ControlEvent::LayoutChanged),
+        b"%exit" => Some(ControlEvent::Exit(
+            (!rest.is_empty()).then(|| String::from_utf8_lossy(rest).into_owned()),
+        )),
+        _ => None,
+    }
+}
+
+/// Where a pane sits, for panes known by id from `%output`.
+#[derive(Debug, Clone)]
+pub struct PaneLocation {
+    pub session_name: String,
+    pub window_index: u32,
+    pub pane_index: u32,
+}
+
+/// Window and pane index of a pane, as seen from `session_name`.
+pub async fn locate_pane(session_name: &str, pane_id: &str) -> Result<PaneLocation> {
+    let output = tmux::run_tmux(&["display-message", "-p", "-t", pane_id, "#{window_index} #{pane_index}"]).await?;
+    let (window, pane) = output
+        .trim()
+        .split_once(' ')
+        .ok_or_else(|| anyhow::anyhow!("Unexpected pane location: {}", output.trim()))?;
+    Ok(PaneLocation {
+        session_name: session_name.to_string(),
+        window_index: window.parse()?,
+        pane_index: pane.parse()?,
+    })
+}
+
+/// A read-only control-mode client attached to one session. It doesn't
+/// affect the session's size.
+pub struct ControlClient {
+    child: Child,
+    reader: JoinHandle<()>,
+    /// Fires once tmux has answered the attach, so output from then on is
+    /// delivered.
+    attached: Option<oneshot::Receiver<()>>,
+}
+
+impl ControlClient {
+    /// Attach to `session_name` and forward its events, tagged with the
+    /// session name, until the client exits or is closed.
+    pub fn attach(session_name: &str, events: mpsc::UnboundedSender<(String, ControlEvent)>) -> Result<Self> {
+        let target = format!("={}", session_name);
+        let mut child = Command::new("tmux")
+            .args(["-C", "attach-session", "-r", "-f", "ignore-size", "-t", &target])
+            // Control mode ends when stdin closes, so keep it open
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()?;
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| anyhow::anyhow!("tmux stdout unavailable"))?;
+
+        let session_name = session_name.to_string();
+        let (attached_tx, attached) = oneshot::channel();
+        let reader = tokio::spawn(async move {
+            let mut reader = BufReader::new(stdout);
+            let mut line = Vec::new();
+            let mut attached_tx = Some(attached_tx);
+            loop {
+                line.clear();
+                match reader.read_until(b'\n', &mut line).await {
+                    Ok(0) | Err(_) => break,
+                    Ok(_) => {
+                        // The reply to the attach itself comes first
+                        if line.starts_with(b"%end") || line.starts_with(b"%error") {
+                            if let Some(tx) = attached_tx.take() {
+                                let _ = tx.send(());
+                            }
+                        }
+                        if let Some(event) = parse_line(&line) {
+                            let exit = matches!(event, ControlEvent::Exit(_));
+                            if events.send((session_name.clone(), event)).is_err() || exit {
+                                return;
+                            }
+                        }
+                    }
+                }
+            }
+            debug!("Control client for {} closed", session_name);
+            let _ = events.send((session_name, ControlEvent::Exit(None)));
+        });
+
+        Ok(Self {
+            child,
+            reader,
+            attached: Some(attached),
+        })
+    }
+
+    /// Wait until tmux has attached the client, or it gave up.
+    pub async fn wait_attached(&mut self) {
+        if let Some(attached) = self.attached.take() {
+            let _ = tokio::time::timeout(ATTACH_TIMEOUT, attached).await;
+        }
+    }
+
+    pub fn close(mut self) {
+        self.reader.abort();
+        let _ = self.child.start_kill();
+    }
+}
+
+/// Output of a watched session, as delivered to hub subscribers.
+#[derive(Debug, Clone)]
+pub enum PaneEvent {
+    Output {
+        pane_id: String,
+        location: PaneLocation,
+        data: Vec<u8>,
+    },
+    /// The pane no longer exists; state kept for it can go.
+    PaneClosed(String),
+    /// The session's control client went away, usually because the session
+    /// ended. It is reattached if the session comes back.
+    SessionClosed(String),
+}
+
+struct Subscriber {
+    sessions: HashSet<String>,
+    events: mpsc::UnboundedSender<PaneEvent>,
+}
+
+/// Shares one control-mode client per session among everything that
+/// follows pane output, so a session is attached once however many
+/// features watch it.
+pub struct ControlHub {
+    subscribers: std::sync::Mutex<HashMap<u64, Subscriber>>,
+    clients: Mutex<HashMap<String, ControlClient>>,
+    next_id: AtomicU64,
+    changed: Notify,
+    events_tx: mpsc::UnboundedSender<(String, ControlEvent)>,
+    events_rx: std::sync::Mutex<Option<mpsc::UnboundedReceiver<(String, ControlEvent)>>>,
+}
+
+/// A hub subscriber's view of pane output. Dropping it unsubscribes.
+pub struct Subscription {
+    id: u64,
+    pub events: mpsc::UnboundedReceiver<PaneEvent>,
+}
+
+impl Subscription {
+    /// Follow these sessions from now on. Clients for sessions that exist
+    /// are attached before this returns.
+    pub async fn set_sessions(&self, sessions: HashSet<String>) {
+        if let Some(subscriber) = CONTROL_HUB.subscribers.lock().unwrap().get_mut(&self.id) {
+            subscriber.sessions = sessions;
+        }
+        CONTROL_HUB.reconcile().await;
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        CONTROL_HUB.subscribers.lock().unwrap().remove(&self.id);
+        CONTROL_HUB.changed.notify_one();
+    }
+}
+
+impl ControlHub {
+    pub fn new() -> Self {
+        let (events_tx, events_rx) = mpsc::unbounded_channel();
+        Self {
+            subscribers: std::sync::Mutex::new(HashMap::new()),
+            clients: Mutex::new(HashMap::new()),
+            next_id: AtomicU64::new(0),
+            changed: Notify::new(),
+            events_tx,
+            events_rx: std::sync::Mutex::new(Some(events_rx)),
+        }
+    }
+
+    /// Follow pane output of `sessions`. Clients for sessions that exist
+    /// are attached before this returns, so no output after it is missed.
+    pub async fn subscribe(&self, sessions: HashSet<String>) -> Subscription {
+        let (events, rx) = mpsc::unbounded_channel();
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.subscribers.lock().unwrap().insert(id, Subscriber { sessions, events });
+        self.reconcile().await;
+        Subscription { id, events: rx }
+    }
+
+    /// Attach to every wanted session that exists and close clients nobody
+    /// wants any more.
+    async fn reconcile(&self) {
+        let wanted: HashSet<String> = self
+            .subscribers
+            .lock()
+            .unwrap()
+            .values()
+            .flat_map(|subscriber| subscriber.sessions.iter().cloned())
+            .collect();
+
+        let mut clients = self.clients.lock().await;
+        let unwanted: Vec<String> = clients.keys().filter(|s| !wanted.contains(*s)).cloned().collect();
+        for session_name in unwanted {
+            if let Some(client) = clients.remove(&session_name) {
+                debug!("Detaching control client from {}", session_name);
+                client.close();
+            }
+        }
+        for session_name in wanted {
+            if clients.contains_key(&session_name) || !tmux::has_session(&session_name).await {
+                continue;
+            }
+            match ControlClient::attach(&session_name, self.events_tx.clone()) {
+                Ok(mut client) => {
+                    client.wait_attached().await;
+                    debug!("Following output of session {}", session_name);
+                    clients.insert(session_name, client);
+                }
+                Err(e) => warn!("Failed to attach control client to {}: {}", session_name, e),
+            }
+        }
+    }
+
+    fn send(&self, event: PaneEvent, session_name: Option<&str>) {
+        for subscriber in self.subscribers.lock().unwrap().values() {
+            if session_name.is_none_or(|s| subscriber.sessions.contains(s)) {
+                let _ = subscriber.events.send(event.clone());
+            }
+        }
+    }
+
+    /// Route control-mode events to subscribers, keeping clients in line
+    /// with what they want.
+    pub async fn run(&self) {
+        let Some(mut events) = self.events_rx.lock().unwrap().take() else {
+            return;
+        };
+        let mut panes: HashMap<String, PaneLocation> = HashMap::new();
+        let mut seen: HashSet<String> = HashSet::new();
+        let mut ticker = tokio::time::interval(RECONCILE_INTERVAL);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+        loop {
+            let (session_name, event) = tokio::select! {
+                _ = ticker.tick() => {
+                    self.reconcile().await;
+                    if !seen.is_empty() {
+                        if let Ok(live) = tmux::list_pane_ids().await {
+                            let closed: Vec<String> = seen.iter().filter(|id| !live.contains(*id)).cloned().collect();
+                            for pane_id in closed {
+                                seen.remove(&pane_id);
+                                panes.remove(&pane_id);
+                                self.send(PaneEvent::PaneClosed(pane_id), None);
+                            }
+                        }
+                    }
+                    continue;
+                }
+                _ = self.changed.notified() => {
+                    self.reconcile().await;
+                    continue;
+                }
+                Some(event) = events.recv() => event,
+            };
+
+            match event {
+                ControlEvent::Output { pane_id, data } => {
+                    let location = match panes.get(&pane_id) {
+                        Some(location) => location.clone(),
+                        None => match locate_pane(&session_name, &pane_id).await {
+                            Ok(location) => {
+                                panes.insert(pane_id.clone(), location.clone());
+                                location
+                            }
+                            Err(e) => {
+                                debug!("Failed to locate pane {}: {}", pane_id, e);
+                                continue;
+                            }
+                        },
+                    };
+                    seen.insert(pane_id.clone());
+                    self.send(PaneEvent::Output { pane_id, location, data }, Some(&session_name));
+                }
+                ControlEvent::LayoutChanged => {
+                    panes.retain(|_, location| location.session_name != session_name);
+                }
+                ControlEvent::Exit(reason) => {
+                    debug!("Control client for {} exited: {:?}", session_name, reason);
+                    self.clients.lock().await.remove(&session_name);
+                    panes.retain(|_, location| location.session_name != session_name);
+                    self.send(PaneEvent::SessionClosed(session_name.clone()), Some(&session_name));
+                }
+            }
+        }
+    }
+}
+
+lazy_static::lazy_static! {
+    pub static ref CONTROL_HUB: ControlHub = ControlHub::new();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_output_with_escapes() {
+        let event = parse_line(b"%output %12 ok\\015\\012back\\134slash \\033[1m\n").unwrap();
+        assert_eq!(
+            event,
+            ControlEvent::Output {
+                pane_id: "%12".into(),
+                data: b"ok\r\nback\\slash \x1b[1m".to_vec(),
+            }
+        );
+    }
+
+    #[test]
+    fn parses_notifications_and_skips_replies() {
+        assert_eq!(parse_line(b"%window-add @3"), Some(ControlEvent::LayoutChanged));
+        assert_eq!(parse_line(b"%exit"), Some(ControlEvent::Exit(None)));
+        assert_eq!(
+            parse_line(b"%exit detached"),
+            Some(ControlEvent::Exit(Some("detached".into())))
+        );
+        assert_eq!(parse_line(b"%begin 1 2 0"), None);
+        assert_eq!(parse_line(b"some reply"), None);
+    }
+}
@@ -0,0 +1,480 @@
+use anyhow::Result;
+use axum::{
+    extract::Query,
+    http::{header, StatusCode},
+    response::IntoResponse,
+};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+
+use crate::scrollback;
+use crate::tmux;
+use crate::types::PaneTarget;
+
+/// Longest export we render; older lines of a larger range are dropped.
+const MAX_EXPORT_LINES: usize = 5000;
+
+const FONT_SIZE: f32 = 14.0;
+const CHAR_WIDTH: f32 = 8.4;
+const LINE_HEIGHT: f32 = 17.0;
+const PADDING: f32 = 16.0;
+const DEFAULT_FG: (u8, u8, u8) = (0xd4, 0xd4, 0xd4);
+const DEFAULT_BG: (u8, u8, u8) = (0x1e, 0x1e, 0x1e);
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Html,
+    Svg,
+    Text,
+}
+
+impl ExportFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Html => "text/html; charset=utf-8",
+            ExportFormat::Svg => "image/svg+xml",
+            ExportFormat::Text => "text/plain; charset=utf-8",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Html => "html",
+            ExportFormat::Svg => "svg",
+            ExportFormat::Text => "txt",
+        }
+    }
+}
+
+/// What to export. Without a line range the pane's visible screen is used;
+/// with one, lines of its history as numbered by scrollback retrieval.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportRequest {
+    pub session_name: String,
+    pub window_index: Option<u32>,
+    pub pane_index: Option<u32>,
+    pub format: ExportFormat,
+    pub start: Option<usize>,
+    pub end: Option<usize>,
+}
+
+impl ExportRequest {
+    fn target(&self) -> PaneTarget {
+        PaneTarget {
+            session_name: self.session_name.clone(),
+            window_index: self.window_index,
+            pane_index: self.pane_index,
+        }
+    }
+
+    /// Human-readable pane name, e.g. `work:1.2`.
+    fn label(&self) -> String {
+        let mut label = self.session_name.clone();
+        if let Some(window) = self.window_index {
+            label.push_str(&format!(":{}", window));
+        }
+        if let Some(pane) = self.pane_index {
+            label.push_str(&format!(".{}", pane));
+        }
+        label
+    }
+
+    pub fn filename(&self) -> String {
+        let mut name = self.label().replace(':', "-");
+        name.retain(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | '.'));
+        format!("{}.{}", name, self.format.extension())
+    }
+}
+
+/// Capture and render a pane as requested.
+pub async fn export_pane(request: &ExportRequest) -> Result<String> {
+    let target = request.target();
+    let content = if request.start.is_none() && request.end.is_none() {
+        tmux::capture_visible(&target, true).await?
+    } else {
+        scrollback::get_range(&target, request.start, request.end, true)
+            .await?
+            .lines
+            .join("\n")
+    };
+    let pane_width: u16 = tmux::pane_format(&target, "#{pane_width}")
+        .await?
+        .parse()
+        .unwrap_or(80);
+
+    let title = format!("{} - webmux", request.label());
+    Ok(render(&content, pane_width, request.format, &title))
+}
+
+/// `GET /api/export?sessionName=..&windowIndex=..&paneIndex=..&format=html`
+pub async fn export_handler(Query(request): Query<ExportRequest>) -> impl IntoResponse {
+    match export_pane(&request).await {
+        Ok(content) => (
+            StatusCode::OK,
+            [
+                (header::CONTENT_TYPE, request.format.content_type().to_string()),
+                (
+                    header::CONTENT_DISPOSITION,
+                    format!("attachment; filename=\"{}\"", request.filename()),
+                ),
+            ],
+            content,
+        )
+            .into_response(),
+        Err(e) => (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref ANSI_ESCAPE: Regex =
+        Regex::new(r"\x1b(\[[0-?]*[ -/]*[@-~]|\][^\x07\x1b]*(\x07|\x1b\\)|[()][0-9A-Za-z]|[=>78cDEHM])").unwrap();
+}
+
+/// Remove escape sequences (CSI, OSC, charset and simple ESC codes).
+pub fn strip_ansi(text: &str) -> String {
+    ANSI_ESCAPE.replace_all(text, "").into_owned()
+}
+
+/// Render text captured with escapes to the chosen format.
+pub fn render(content: &str, min_cols: u16, format: ExportFormat, title: &str) -> String {
+    let mut lines: Vec<&str> = content.trim_end_matches('\n').split('\n').collect();
+    if lines.len() > MAX_EXPORT_LINES {
+        lines.drain(..lines.len() - MAX_EXPORT_LINES);
+    }
+
+    if format == ExportFormat::Text {
+        let text: Vec<String> = lines
+            .iter()
+            .map(|line| strip_ansi(line).trim_end().to_string())
+            .collect();
+        return format!("{}\n", text.join("\n"));
+    }
+
+    // Size the model so joined lines never wrap and nothing scrolls away
+    let widest = lines
+        .iter()
+        .map(|line| strip_ansi(line).chars().count())
+        .max()
+        .unwrap_or(0);
+    let cols = (widest.min(u16::MAX as usize) as u16).max(min_cols).max(1);
+    let rows = lines.len().max(1) as u16;
+    let mut parser = vt100::Parser::new(rows, cols, 0);
+    parser.process(lines.join("\r\n").as_bytes());
+
+    let runs: Vec<Vec<Run>> = (0..rows).map(|row| row_runs(parser.screen(), row, cols)).collect();
+    let used_cols = runs
+        .iter()
+        .filter_map(|row| row.last().map(|run| run.col + run.width))
+        .max()
+        .unwrap_or(0)
+        .max(1);
+
+    match format {
+        ExportFormat::Html => render_html(&runs, title),
+        _ => render_svg(&runs, used_cols, title),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Style {
+    fg: (u8, u8, u8),
+    bg: Option<(u8, u8, u8)>,
+    bold: bool,
+    italic: bool,
+    underline: bool,
+}
+
+#[derive(Debug)]
+struct Run {
+    col: u16,
+    width: u16,
+    style: Style,
+    text: String,
+}
+
+fn cell_style(cell: &vt100::Cell) -> Style {
+    let mut fg = resolve_color(cell.fgcolor());
+    let mut bg = resolve_color(cell.bgcolor());
+    if cell.inverse() {
+        std::mem::swap(&mut fg, &mut bg);
+        fg = fg.or(Some(DEFAULT_BG));
+        bg = bg.or(Some(DEFAULT_FG));
+    }
+    Style {
+        fg: fg.unwrap_or(DEFAULT_FG),
+        bg,
+        bold: cell.bold(),
+        italic: cell.italic(),
+        underline: cell.underline(),
+    }
+}
+
+/// Split a row into runs of identically styled cells, dropping trailing
+/// unstyled blanks.
+fn row_runs(screen: &vt100::Screen, row: u16, cols: u16) -> Vec<Run> {
+    let mut runs: Vec<Run> = Vec::new();
+    for col in 0..cols {
+        let Some(cell) = screen.cell(row, col) else {
+            continue;
+        };
+        if cell.is_wide_continuation() {
+            if let Some(run) = runs.last_mut() {
+                run.width += 1;
+            }
+            continue;
+        }
+
+        let style = cell_style(cell);
+        let contents = cell.contents();
+        let text = if contents.is_empty() { " ".to_string() } else { contents };
+        match runs.last_mut() {
+            Some(run) if run.style == style => {
+                run.text.push_str(&text);
+                run.width += 1;
+            }
+            _ => runs.push(Run {
+                col,
+                width: 1,
+                style,
+                text,
+            }),
+        }
+    }
+
+    // Trailing blanks only matter if they paint a background
+    while let Some(run) = runs.last_mut() {
+        if run.style.bg.is_some() {
+            break;
+        }
+        let trimmed = run.text.trim_end_matches(' ');
+        let removed = (run.text.len() - trimmed.len()) as u16;
+        if trimmed.is_empty() {
+            runs.pop();
+        } else {
+            run.text.truncate(trimmed.len());
+            run.width -= removed;
+            break;
+        }
+    }
+    runs
+}
+
+fn resolve_color(color: vt100::Color) -> Option<(u8, u8, u8)> {
+    match color {
+        vt100::Color::Default => None,
+        vt100::Color::Idx(index) => Some(palette(index)),
+        vt100::Color::Rgb(r, g, b) => Some((r, g, b)),
+    }
+}
+
+/// The xterm 256-color palette.
+fn palette(index: u8) -> (u8, u8, u8) {
+    const BASE: [(u8, u8, u8); 16] = [
+        (0x00, 0x00, 0x00),
+        (0xcd, 0x31, 0x31),
+        (0x0d, 0xbc, 0x79),
+        (0xe5, 0xe5, 0x10),
+        (0x24, 0x72, 0xc8),
+        (0xbc, 0x3f, 0xbc),
+        (0x11, 0xa8, 0xcd),
+        (0xe5, 0xe5, 0xe5),
+        (0x66, 0x66, 0x66),
+        (0xf1, 0x4c, 0x4c),
+        (0x23, 0xd1, 0x8b),
+        (0xf5, 0xf5, 0x43),
+        (0x3b, 0x8e, 0xea),
+        (0xd6, 0x70, 0xd6),
+        (0x29, 0xb8, 0xdb),
+        (0xff, 0xff, 0xff),
+    ];
+    match index {
+        0..=15 => BASE[index as usize],
+        16..=231 => {
+            let index = index - 16;
+            let level = |v: u8| if v == 0 { 0 } else { 55 + v * 40 };
+            (level(index / 36), level((index / 6) % 6), level(index % 6))
+        }
+        _ => {
+            let gray = 8 + (index - 232) * 10;
+            (gray, gray, gray)
+        }
+    }
+}
+
+fn hex((r, g, b): (u8, u8, u8)) -> String {
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            // Control characters aren't allowed in XML
+            c if c.is_control() => escaped.push(' '),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn render_html(rows: &[Vec<Run>], title: &str) -> String {
+    let mut body = String::new();
+    for (index, row) in rows.iter().enumerate() {
+        if index > 0 {
+            body.push('\n');
+        }
+        for run in row {
+            let mut css = Vec::new();
+            if run.style.fg != DEFAULT_FG {
+                css.push(format!("color:{}", hex(run.style.fg)));
+            }
+            if let Some(bg) = run.style.bg {
+                css.push(format!("background:{}", hex(bg)));
+            }
+            if run.style.bold {
+                css.push("font-weight:bold".to_string());
+            }
+            if run.style.italic {
+                css.push("font-style:italic".to_string());
+            }
+            if run.style.underline {
+                css.push("text-decoration:underline".to_string());
+            }
+
+            if css.is_empty() {
+                body.push_str(&escape_xml(&run.text));
+            } else {
+                let _ = write!(body, "<span style=\"{}\">{}</span>", css.join(";"), escape_xml(&run.text));
+            }
+        }
+    }
+
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n<style>\n\
+         body {{ margin: 0; background: {bg}; }}\n\
+         pre {{ margin: 0; padding: {pad}px; color: {fg}; font-family: Menlo, Consolas, 'DejaVu Sans Mono', monospace; \
+         font-size: {size}px; line-height: {line}px; }}\n\
+         </style>\n</head>\n<body>\n<pre>{body}</pre>\n</body>\n</html>\n",
+        title = escape_xml(title),
+        bg = hex(DEFAULT_BG),
+        fg = hex(DEFAULT_FG),
+        pad = PADDING,
+        size = FONT_SIZE,
+        line = LINE_HEIGHT,
+        body = body,
+    )
+}
+
+fn render_svg(rows: &[Vec<Run>], cols: u16, title: &str) -> String {
+    let width = cols as f32 * CHAR_WIDTH + PADDING * 2.0;
+    let height = rows.len() as f32 * LINE_HEIGHT + PADDING * 2.0;
+
+    let mut backgrounds = String::new();
+    let mut text = String::new();
+    for (index, row) in rows.iter().enumerate() {
+        let y = PADDING + index as f32 * LINE_HEIGHT;
+        for run in row {
+            let x = PADDING + run.col as f32 * CHAR_WIDTH;
+            if let Some(bg) = run.style.bg {
+                let _ = writeln!(
+                    backgrounds,
+                    "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" fill=\"{}\"/>",
+                    x,
+                    y,
+                    run.width as f32 * CHAR_WIDTH,
+                    LINE_HEIGHT,
+                    hex(bg)
+                );
+            }
+            if run.text.trim().is_empty() {
+                continue;
+            }
+
+            let mut attrs = format!(
+                "x=\"{:.1}\" y=\"{:.1}\" textLength=\"{:.1}\" fill=\"{}\"",
+                x,
+                y + LINE_HEIGHT * 0.8,
+                run.width as f32 * CHAR_WIDTH,
+                hex(run.style.fg)
+            );
+            if run.style.bold {
+                attrs.push_str(" font-weight=\"bold\"");
+            }
+            if run.style.italic {
+                attrs.push_str(" font-style=\"italic\"");
+            }
+            if run.style.underline {
+                attrs.push_str(" text-decoration=\"underline\"");
+            }
+            let _ = writeln!(text, "<text {}>{}</text>", attrs, escape_xml(&run.text));
+        }
+    }
+
+    format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w:.0}\" height=\"{h:.0}\" viewBox=\"0 0 {w:.1} {h:.1}\">\n\
+         <title>{title}</title>\n\
+         <rect width=\"100%\" height=\"100%\" fill=\"{bg}\"/>\n\
+         {backgrounds}\
+         <g font-family=\"Menlo, Consolas, 'DejaVu Sans Mono', monospace\" font-size=\"{size}\" xml:space=\"preserve\">\n\
+         {text}</g>\n</svg>\n",
+        w = width,
+        h = height,
+        title = escape_xml(title),
+        bg = hex(DEFAULT_BG),
+        backgrounds = backgrounds,
+        size = FONT_SIZE,
+        text = text,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "\x1b[1;31merror\x1b[0m: <bad> & worse   \n\x1b[42m ok \x1b[0m\n";
+
+    #[test]
+    fn strips_escape_sequences() {
+        assert_eq!(strip_ansi("\x1b[1;31mred\x1b[0m \x1b]0;title\x07x\x1b(B"), "red x");
+    }
+
+    #[test]
+    fn renders_plain_text() {
+        let text = render(SAMPLE, 80, ExportFormat::Text, "t");
+        assert_eq!(text, "error: <bad> & worse\n ok\n");
+    }
+
+    #[test]
+    fn renders_html_with_styles() {
+        let html = render(SAMPLE, 80, ExportFormat::Html, "my <pane>");
+
+        assert!(html.contains("<title>my &lt;pane&gt;</title>"));
+        assert!(html.contains("<span style=\"color:#cd3131;font-weight:bold\">error</span>: &lt;bad&gt; &amp; worse\n"));
+        assert!(html.contains("<span style=\"background:#0dbc79\"> ok </span></pre>"));
+    }
+
+    #[test]
+    fn renders_svg_rows() {
+        let svg = render(SAMPLE, 80, ExportFormat::Svg, "t");
+
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\""));
+        assert!(svg.contains(">error</text>"));
+        assert!(svg.contains("fill=\"#0dbc79\"/>"));
+        assert_eq!(svg.matches("<text ").count(), 3);
+    }
+
+    #[test]
+    fn palette_covers_cube_and_grays() {
+        assert_eq!(palette(16), (0, 0, 0));
+        assert_eq!(palette(196), (255, 0, 0));
+        assert_eq!(palette(232), (8, 8, 8));
+        assert_eq!(palette(255), (238, 238, 238));
+    }
+}
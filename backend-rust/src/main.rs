@@ -18,14 +18,32 @@ use tower_http::{
 use tracing::{error, info};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
+mod alerts;
 mod audio;
+mod automation;
 mod chat_log;
+mod clipboard;
+mod control;
 mod cron;
 mod dotfiles;
+mod export;
 mod monitor;
+mod pane_log;
+mod procinfo;
+mod recording;
+mod resize_policy;
+mod scheduled_input;
+mod screen;
+mod scrollback;
+mod session_templates;
+mod shell_integration;
+mod snapshots;
+mod storage;
 mod terminal_buffer;
+mod thumbnails;
 mod tmux;
 mod types;
+mod watches;
 mod websocket;
 
 // Global flag for audio logging
@@ -94,6 +112,65 @@ async fn main() -> Result<()> {
         error!("Failed to initialize CRON manager: {}", e);
     }
     
+    // Initialize snapshot scheduler
+    if let Err(e) = crate::snapshots::SNAPSHOT_MANAGER.initialize().await {
+        error!("Failed to initialize snapshot manager: {}", e);
+    }
+    tokio::spawn(async move {
+        crate::snapshots::SNAPSHOT_MANAGER.run_scheduler().await;
+    });
+
+    // Watch windows for bell, activity and silence
+    if let Err(e) = crate::alerts::ALERT_MANAGER.initialize().await {
+        error!("Failed to load alert settings: {}", e);
+    }
+    let alerts_tx = broadcast_tx.clone();
+    tokio::spawn(async move {
+        crate::alerts::ALERT_MANAGER.run(alerts_tx).await;
+    });
+
+    // Shared control-mode clients for features that follow pane output
+    tokio::spawn(async move {
+        crate::control::CONTROL_HUB.run().await;
+    });
+
+    // Match watch rules against pane output
+    if let Err(e) = crate::watches::WATCH_MANAGER.initialize().await {
+        error!("Failed to load watch rules: {}", e);
+    }
+    let watches_tx = broadcast_tx.clone();
+    tokio::spawn(async move {
+        crate::watches::WATCH_MANAGER.run(watches_tx).await;
+    });
+
+    // Record commands in sessions with shell integration
+    if let Err(e) = crate::shell_integration::SHELL_INTEGRATION.initialize().await {
+        error!("Failed to load shell integration settings: {}", e);
+    }
+    let shell_tx = broadcast_tx.clone();
+    tokio::spawn(async move {
+        crate::shell_integration::SHELL_INTEGRATION.run(shell_tx).await;
+    });
+
+    // Resume pane output logs
+    if let Err(e) = crate::pane_log::PANE_LOGS.initialize().await {
+        error!("Failed to resume pane logs: {}", e);
+    }
+
+    // Send scheduled one-off input as it comes due
+    if let Err(e) = crate::scheduled_input::SCHEDULED_INPUTS.initialize().await {
+        error!("Failed to load scheduled input: {}", e);
+    }
+    let scheduled_tx = broadcast_tx.clone();
+    tokio::spawn(async move {
+        crate::scheduled_input::SCHEDULED_INPUTS.run(scheduled_tx).await;
+    });
+
+    // Apply saved resize policies
+    if let Err(e) = crate::resize_policy::RESIZE_POLICIES.initialize().await {
+        error!("Failed to load resize policies: {}", e);
+    }
+
     // Start tmux monitor
     let monitor = monitor::TmuxMonitor::new(broadcast_tx);
     tokio::spawn(async move {
@@ -108,6 +185,10 @@ async fn main() -> Result<()> {
     let app = Router::new()
         // WebSocket endpoint
         .route("/ws", get(websocket::ws_handler))
+        // Recording downloads
+        .route("/api/recordings/:id", get(recording::download_handler))
+        // Pane exports (HTML, SVG, text)
+        .route("/api/export", get(export::export_handler))
         // Serve static files (Vue app)
         .fallback_service(serve_dir)
         // Add CORS
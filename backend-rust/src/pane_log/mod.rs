@@ -0,0 +1,422 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use crate::{export::strip_ansi, storage, terminal_buffer::Utf8StreamDecoder, tmux, types::PaneTarget};
+
+const DEFAULT_MAX_BYTES: u64 = 10 * 1024 * 1024;
+const DEFAULT_KEEP_FILES: u32 = 5;
+const DEFAULT_TAIL_LINES: usize = 100;
+/// How far from the end of a log `tail` reads.
+const MAX_TAIL_BYTES: u64 = 1024 * 1024;
+/// Longest escape sequence held back while waiting for the rest of it.
+const MAX_HELD_ESCAPE: usize = 256;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogOptions {
+    /// Write plain text: escape sequences and carriage returns removed.
+    #[serde(default)]
+    pub strip_ansi: bool,
+    /// Rotate the log once it would grow past this size.
+    pub max_bytes: Option<u64>,
+    /// Rotated files kept, as `<name>.1` (newest) to `<name>.<n>`.
+    pub keep_files: Option<u32>,
+}
+
+/// A pane whose output is being written to a log file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PaneLog {
+    pub id: String,
+    pub pane_id: String,
+    pub session_name: String,
+    pub window_index: u32,
+    pub pane_index: u32,
+    /// File name within the session's log directory.
+    pub file: String,
+    pub strip_ansi: bool,
+    pub max_bytes: u64,
+    pub keep_files: u32,
+    pub started_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogFile {
+    pub session_name: String,
+    pub name: String,
+    pub size: u64,
+    pub modified: DateTime<Utc>,
+    /// Currently being written to.
+    pub active: bool,
+}
+
+/// Removes escape sequences from a stream, holding back a sequence split
+/// across chunks until the rest of it arrives.
+#[derive(Default)]
+struct AnsiStripper {
+    pending: String,
+}
+
+impl AnsiStripper {
+    fn strip(&mut self, text: &str) -> String {
+        let mut data = std::mem::take(&mut self.pending);
+        data.push_str(text);
+        if let Some(esc) = data.rfind('\x1b') {
+            let tail = &data[esc..];
+            if tail.len() < MAX_HELD_ESCAPE && strip_ansi(tail) == tail {
+                self.pending = tail.to_string();
+                data.truncate(esc);
+            }
+        }
+        strip_ansi(&data).replace('\r', "")
+    }
+}
+
+/// Appends to a log file, rotating it by size.
+struct LogWriter {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    max_bytes: u64,
+    keep_files: u32,
+    text: Option<(Utf8StreamDecoder, AnsiStripper)>,
+}
+
+impl LogWriter {
+    fn open(path: PathBuf, log: &PaneLog) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+        Ok(Self {
+            path,
+            file,
+            size,
+            max_bytes: log.max_bytes.max(1),
+            keep_files: log.keep_files,
+            text: log
+                .strip_ansi
+                .then(|| (Utf8StreamDecoder::new(), AnsiStripper::default())),
+        })
+    }
+
+    fn write(&mut self, data: &[u8]) -> io::Result<()> {
+        let stripped;
+        let data = match &mut self.text {
+            Some((decoder, stripper)) => {
+                stripped = stripper.strip(&decoder.decode_chunk(data).0);
+                stripped.as_bytes()
+            }
+            None => data,
+        };
+        if data.is_empty() {
+            return Ok(());
+        }
+        if self.size > 0 && self.size + data.len() as u64 > self.max_bytes {
+            self.rotate()?;
+        }
+        self.file.write_all(data)?;
+        self.size += data.len() as u64;
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        let rotated = |n: u32| PathBuf::from(format!("{}.{}", self.path.display(), n));
+        if self.keep_files == 0 {
+            fs::remove_file(&self.path)?;
+        } else {
+            let _ = fs::remove_file(rotated(self.keep_files));
+            for n in (1..self.keep_files).rev() {
+                if rotated(n).exists() {
+                    fs::rename(rotated(n), rotated(n + 1))?;
+                }
+            }
+            fs::rename(&self.path, rotated(1))?;
+        }
+        self.file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+/// Pane output logs under `~/.webmux/logs/<session>/`. Active logs are
+/// kept in `~/.webmux/logs/active.json` and resumed on startup.
+pub struct PaneLogManager {
+    active: Mutex<HashMap<String, PaneLog>>,
+}
+
+impl PaneLogManager {
+    pub fn new() -> Self {
+        Self {
+            active: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub async fn initialize(&'static self) -> Result<()> {
+        let logs: Vec<PaneLog> = storage::load_json(&active_path()?)?;
+        for log in logs {
+            // tmux hands out pane ids again after a server restart, so only
+            // resume if the pane is still where the log says it is
+            let format = "#{session_name}\t#{window_index}\t#{pane_index}";
+            let location = tmux::run_tmux(&["display-message", "-p", "-t", &log.pane_id, format]).await;
+            let expected = format!("{}\t{}\t{}", log.session_name, log.window_index, log.pane_index);
+            if location.map(|l| l.trim_end() != expected).unwrap_or(true) {
+                debug!("Not resuming log of closed or moved pane {}", log.pane_id);
+                continue;
+            }
+            // The old pipe ends at its next write now that nothing reads
+            // it; close it so a new one can be opened
+            if tmux::run_tmux(&["pipe-pane", "-t", &log.pane_id]).await.is_err() {
+                debug!("Not resuming log of closed pane {}", log.pane_id);
+                continue;
+            }
+            if let Err(e) = self.open_pipe(log.clone()).await {
+                warn!("Failed to resume log of pane {}: {}", log.pane_id, e);
+            }
+        }
+        self.save()
+    }
+
+    pub fn list_active(&self) -> Vec<PaneLog> {
+        let mut logs: Vec<PaneLog> = self.active.lock().unwrap().values().cloned().collect();
+        logs.sort_by_key(|log| log.started_at);
+        logs
+    }
+
+    pub async fn start(&'static self, target: &PaneTarget, options: LogOptions) -> Result<PaneLog> {
+        let format = "#{pane_id}\t#{session_name}\t#{window_index}\t#{pane_index}\t#{pane_pipe}";
+        let output = tmux::run_tmux(&["display-message", "-p", "-t", &target.tmux_target(), format]).await?;
+        let parts: Vec<&str> = output.trim_end().split('\t').collect();
+        if parts.len() < 5 {
+            anyhow::bail!("Pane not found: {}", target.tmux_target());
+        }
+        if self.active.lock().unwrap().contains_key(parts[0]) {
+            anyhow::bail!("Pane is already being logged");
+        }
+        if parts[4] == "1" {
+            anyhow::bail!("Pane output is already piped elsewhere");
+        }
+
+        let started_at = Utc::now();
+        let log = PaneLog {
+            id: Uuid::new_v4().to_string(),
+            pane_id: parts[0].to_string(),
+            session_name: parts[1].to_string(),
+            window_index: parts[2].parse()?,
+            pane_index: parts[3].parse()?,
+            // Indexes get reused by later panes, so name the file after this
+            // pane and when its log started
+            file: log_file_name(parts[0], started_at),
+            strip_ansi: options.strip_ansi,
+            max_bytes: options.max_bytes.unwrap_or(DEFAULT_MAX_BYTES),
+            keep_files: options.keep_files.unwrap_or(DEFAULT_KEEP_FILES),
+            started_at,
+        };
+        self.open_pipe(log.clone()).await?;
+        self.save()?;
+        info!("Logging pane {} to {}", log.pane_id, log.file);
+        Ok(log)
+    }
+
+    pub async fn stop(&self, target: &PaneTarget) -> Result<PaneLog> {
+        let pane_id = tmux::run_tmux(&["display-message", "-p", "-t", &target.tmux_target(), "#{pane_id}"]).await?;
+        let log = self
+            .active
+            .lock()
+            .unwrap()
+            .remove(pane_id.trim())
+            .ok_or_else(|| anyhow::anyhow!("Pane is not being logged"))?;
+        self.save()?;
+        // Closing the pipe ends the reader
+        tmux::run_tmux(&["pipe-pane", "-t", &log.pane_id]).await?;
+        info!("Stopped logging pane {}", log.pane_id);
+        Ok(log)
+    }
+
+    /// Pipe the pane's output through a FIFO into a reader that writes the
+    /// log, and record it as active.
+    async fn open_pipe(&'static self, log: PaneLog) -> Result<()> {
+        let dir = session_dir(&log.session_name)?;
+        let fifo = dir.join(format!(".{}.fifo", log.pane_id.trim_start_matches('%')));
+        let _ = fs::remove_file(&fifo);
+        let status = tokio::process::Command::new("mkfifo").arg(&fifo).status().await?;
+        if !status.success() {
+            anyhow::bail!("Failed to create {}", fifo.display());
+        }
+        let mut writer = LogWriter::open(dir.join(&log.file), &log)?;
+
+        // `cat` blocks opening the FIFO until the reader below opens it
+        let command = format!("exec cat > '{}'", fifo.display());
+        if let Err(e) = tmux::run_tmux(&["pipe-pane", "-O", "-t", &log.pane_id, &command]).await {
+            let _ = fs::remove_file(&fifo);
+            return Err(e);
+        }
+        self.active.lock().unwrap().insert(log.pane_id.clone(), log.clone());
+
+        tokio::task::spawn_blocking(move || {
+            let result = File::open(&fifo).and_then(|mut pipe| {
+                let _ = fs::remove_file(&fifo);
+                let mut buf = [0u8; 16 * 1024];
+                loop {
+                    match pipe.read(&mut buf)? {
+                        0 => return Ok(()),
+                        n => writer.write(&buf[..n])?,
+                    }
+                }
+            });
+            if let Err(e) = result {
+                warn!("Log of pane {} failed: {}", log.pane_id, e);
+            }
+            // The pane closed, or logging stopped or failed
+            let mut active = self.active.lock().unwrap();
+            if active.get(&log.pane_id).is_some_and(|current| current.id == log.id) {
+                active.remove(&log.pane_id);
+                drop(active);
+                if let Err(e) = self.save() {
+                    warn!("Failed to save active logs: {}", e);
+                }
+            }
+            debug!("Log of pane {} closed", log.pane_id);
+        });
+        Ok(())
+    }
+
+    fn save(&self) -> Result<()> {
+        storage::save_json(&active_path()?, &self.list_active())
+    }
+
+    /// Log files of every session, current and rotated.
+    pub fn list_files(&self) -> Result<Vec<LogFile>> {
+        let active = self.list_active();
+        let mut files = Vec::new();
+        for dir in fs::read_dir(storage::data_dir("logs")?)? {
+            let dir = dir?;
+            if !dir.file_type()?.is_dir() {
+                continue;
+            }
+            let session_name = dir.file_name().to_string_lossy().into_owned();
+            for entry in fs::read_dir(dir.path())? {
+                let entry = entry?;
+                let name = entry.file_name().to_string_lossy().into_owned();
+                if name.starts_with('.') || !name.contains(".log") {
+                    continue;
+                }
+                let metadata = entry.metadata()?;
+                files.push(LogFile {
+                    active: active
+                        .iter()
+                        .any(|log| log.file == name && dir_name(&log.session_name) == session_name),
+                    session_name: session_name.clone(),
+                    name,
+                    size: metadata.len(),
+                    modified: metadata.modified()?.into(),
+                });
+            }
+        }
+        files.sort_by(|a, b| (&a.session_name, &a.name).cmp(&(&b.session_name, &b.name)));
+        Ok(files)
+    }
+}
+
+/// The last `lines` lines of a session's log file.
+pub fn tail(session_name: &str, file: &str, lines: Option<usize>) -> Result<String> {
+    if file.is_empty() || file.starts_with('.') || file.contains('/') {
+        anyhow::bail!("Invalid log file name: {}", file);
+    }
+    let path = storage::data_dir("logs")?.join(dir_name(session_name)).join(file);
+    let mut log = File::open(&path).with_context(|| format!("Log not found: {}", file))?;
+    let len = log.metadata()?.len();
+    log.seek(SeekFrom::Start(len.saturating_sub(MAX_TAIL_BYTES)))?;
+    let mut data = Vec::new();
+    log.read_to_end(&mut data)?;
+
+    let text = String::from_utf8_lossy(&data);
+    let all: Vec<&str> = text.lines().collect();
+    let count = lines.unwrap_or(DEFAULT_TAIL_LINES);
+    Ok(all[all.len().saturating_sub(count)..].join("\n"))
+}
+
+/// Log file name for pane `pane_id`, e.g. `pane12-20240501T093000.log`.
+fn log_file_name(pane_id: &str, started_at: DateTime<Utc>) -> String {
+    format!("pane{}-{}.log", pane_id.trim_start_matches('%'), started_at.format("%Y%m%dT%H%M%S"))
+}
+
+/// A session's log directory name, safe to use as a path component.
+fn dir_name(session_name: &str) -> String {
+    let mut name: String = session_name
+        .chars()
+        .filter(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        .collect();
+    if name.is_empty() || name.starts_with('.') {
+        name.insert(0, '_');
+    }
+    name
+}
+
+fn session_dir(session_name: &str) -> Result<PathBuf> {
+    storage::data_dir(&Path::new("logs").join(dir_name(session_name)).to_string_lossy())
+}
+
+fn active_path() -> Result<PathBuf> {
+    Ok(storage::data_dir("logs")?.join("active.json"))
+}
+
+lazy_static::lazy_static! {
+    pub static ref PANE_LOGS: PaneLogManager = PaneLogManager::new();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_escapes_split_across_chunks() {
+        let mut stripper = AnsiStripper::default();
+        assert_eq!(stripper.strip("ok \x1b[3"), "ok ");
+        assert_eq!(stripper.strip("1mred\x1b[0m\r\n"), "red\n");
+        assert!(stripper.pending.is_empty());
+    }
+
+    #[test]
+    fn names_files_by_pane_id_and_start() {
+        let started_at = DateTime::parse_from_rfc3339("2024-05-01T09:30:00Z").unwrap().with_timezone(&Utc);
+        assert_eq!(log_file_name("%12", started_at), "pane12-20240501T093000.log");
+    }
+
+    #[test]
+    fn rotates_by_size() {
+        let dir = std::env::temp_dir().join(format!("webmux-log-{}", Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let log = PaneLog {
+            id: "log".into(),
+            pane_id: "%1".into(),
+            session_name: "work".into(),
+            window_index: 0,
+            pane_index: 0,
+            file: "0.0.log".into(),
+            strip_ansi: false,
+            max_bytes: 10,
+            keep_files: 2,
+            started_at: Utc::now(),
+        };
+        let path = dir.join(&log.file);
+        let mut writer = LogWriter::open(path.clone(), &log).unwrap();
+        for chunk in ["first\n", "second\n", "third\n", "fourth\n"] {
+            writer.write(chunk.as_bytes()).unwrap();
+        }
+
+        let read = |suffix: &str| fs::read_to_string(format!("{}{}", path.display(), suffix)).unwrap();
+        assert_eq!(read(""), "fourth\n");
+        assert_eq!(read(".1"), "third\n");
+        assert_eq!(read(".2"), "second\n");
+        assert!(!Path::new(&format!("{}.3", path.display())).exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,351 @@
+use anyhow::{Context, Result};
+use axum::{
+    extract::Path,
+    http::{header, StatusCode},
+    response::IntoResponse,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::storage;
+
+pub mod player;
+
+/// Metadata stored next to each recording as `<id>.json`; the events live
+/// in `<id>.cast`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordingInfo {
+    pub id: String,
+    pub session_name: String,
+    pub title: Option<String>,
+    pub started_at: DateTime<Utc>,
+    /// Seconds of recorded time; `None` while still recording, or if the
+    /// server stopped before the recording was finished.
+    pub duration: Option<f64>,
+    pub width: u16,
+    pub height: u16,
+    pub input_recorded: bool,
+    #[serde(default)]
+    pub size: u64,
+    #[serde(default)]
+    pub active: bool,
+}
+
+type CastWriter = Arc<Mutex<BufWriter<File>>>;
+
+lazy_static::lazy_static! {
+    /// Recordings currently being written, so readers can flush them first.
+    static ref ACTIVE_RECORDINGS: Mutex<HashMap<String, CastWriter>> = Mutex::new(HashMap::new());
+}
+
+/// Writes one attached session to an asciicast v2 file.
+pub struct Recorder {
+    info: RecordingInfo,
+    dir: PathBuf,
+    writer: CastWriter,
+    started: Instant,
+}
+
+impl Recorder {
+    pub fn start(
+        session_name: &str,
+        title: Option<String>,
+        width: u16,
+        height: u16,
+        record_input: bool,
+    ) -> Result<Self> {
+        Self::start_in(recordings_dir()?, session_name, title, width, height, record_input)
+    }
+
+    fn start_in(
+        dir: PathBuf,
+        session_name: &str,
+        title: Option<String>,
+        width: u16,
+        height: u16,
+        record_input: bool,
+    ) -> Result<Self> {
+        let info = RecordingInfo {
+            id: Uuid::new_v4().to_string(),
+            session_name: session_name.to_string(),
+            title,
+            started_at: Utc::now(),
+            duration: None,
+            width,
+            height,
+            input_recorded: record_input,
+            size: 0,
+            active: true,
+        };
+
+        let file = File::create(dir.join(format!("{}.cast", info.id)))?;
+        let mut writer = BufWriter::new(file);
+        let header = serde_json::json!({
+            "version": 2,
+            "width": width,
+            "height": height,
+            "timestamp": info.started_at.timestamp(),
+            "title": info.title.clone().unwrap_or_else(|| session_name.to_string()),
+            "env": {
+                "TERM": "xterm-256color",
+                "SHELL": std::env::var("SHELL").unwrap_or_default(),
+            },
+        });
+        writeln!(writer, "{}", header)?;
+        writer.flush()?;
+        storage::save_json(&dir.join(format!("{}.json", info.id)), &info)?;
+
+        let writer = Arc::new(Mutex::new(writer));
+        if let Ok(mut active) = ACTIVE_RECORDINGS.lock() {
+            active.insert(info.id.clone(), writer.clone());
+        }
+        info!("Started recording {} for session {}", info.id, session_name);
+
+        Ok(Self {
+            info,
+            dir,
+            writer,
+            started: Instant::now(),
+        })
+    }
+
+    pub fn info(&self) -> &RecordingInfo {
+        &self.info
+    }
+
+    pub fn output(&mut self, data: &str) {
+        self.event("o", data);
+    }
+
+    /// Record client keystrokes, if this recording asked for them.
+    pub fn input(&mut self, data: &str) {
+        if self.info.input_recorded {
+            self.event("i", data);
+        }
+    }
+
+    pub fn resize(&mut self, cols: u16, rows: u16) {
+        self.event("r", &format!("{}x{}", cols, rows));
+    }
+
+    fn event(&mut self, kind: &str, data: &str) {
+        let time = self.started.elapsed().as_secs_f64();
+        let line = serde_json::json!([(time * 1_000_000.0).round() / 1_000_000.0, kind, data]);
+        let result = match self.writer.lock() {
+            Ok(mut writer) => writeln!(writer, "{}", line),
+            Err(_) => Ok(()),
+        };
+        if let Err(e) = result {
+            warn!("Failed to write to recording {}: {}", self.info.id, e);
+        }
+    }
+
+    /// Flush the file and store the final duration and size.
+    pub fn finish(mut self) -> Result<RecordingInfo> {
+        if let Ok(mut active) = ACTIVE_RECORDINGS.lock() {
+            active.remove(&self.info.id);
+        }
+        if let Ok(mut writer) = self.writer.lock() {
+            writer.flush()?;
+        }
+
+        self.info.duration = Some(self.started.elapsed().as_secs_f64());
+        self.info.size = fs::metadata(self.dir.join(format!("{}.cast", self.info.id)))?.len();
+        self.info.active = false;
+        storage::save_json(&self.dir.join(format!("{}.json", self.info.id)), &self.info)?;
+        info!("Finished recording {}", self.info.id);
+        Ok(self.info)
+    }
+}
+
+fn recordings_dir() -> Result<PathBuf> {
+    storage::data_dir("recordings")
+}
+
+fn validate_id(id: &str) -> Result<()> {
+    if id.is_empty() || !id.chars().all(|c| c.is_ascii_hexdigit() || c == '-') {
+        anyhow::bail!("Invalid recording id: {}", id);
+    }
+    Ok(())
+}
+
+pub fn cast_path(id: &str) -> Result<PathBuf> {
+    validate_id(id)?;
+    Ok(recordings_dir()?.join(format!("{}.cast", id)))
+}
+
+fn info_path(id: &str) -> Result<PathBuf> {
+    validate_id(id)?;
+    Ok(recordings_dir()?.join(format!("{}.json", id)))
+}
+
+fn is_active(id: &str) -> bool {
+    ACTIVE_RECORDINGS
+        .lock()
+        .map(|active| active.contains_key(id))
+        .unwrap_or(false)
+}
+
+/// Write out buffered events of a recording still in progress.
+fn flush_active(id: &str) {
+    let writer = ACTIVE_RECORDINGS.lock().ok().and_then(|active| active.get(id).cloned());
+    let Some(writer) = writer else {
+        return;
+    };
+    if let Ok(mut writer) = writer.lock() {
+        if let Err(e) = writer.flush() {
+            warn!("Failed to flush recording {}: {}", id, e);
+        }
+    };
+}
+
+/// List recordings, newest first.
+pub fn list_recordings() -> Result<Vec<RecordingInfo>> {
+    let mut recordings = Vec::new();
+    for entry in fs::read_dir(recordings_dir()?)?.flatten() {
+        let path = entry.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("json") {
+            continue;
+        }
+        match fs::read_to_string(&path)
+            .map_err(anyhow::Error::from)
+            .and_then(|c| serde_json::from_str::<RecordingInfo>(&c).map_err(Into::into))
+        {
+            Ok(mut recording) => {
+                recording.active = is_active(&recording.id);
+                if recording.active {
+                    flush_active(&recording.id);
+                }
+                if recording.active || recording.duration.is_none() {
+                    // Still growing, or never finished; report what's on disk
+                    recording.size = fs::metadata(cast_path(&recording.id)?)
+                        .map(|m| m.len())
+                        .unwrap_or(0);
+                }
+                recordings.push(recording);
+            }
+            Err(e) => warn!("Skipping unreadable recording {}: {}", path.display(), e),
+        }
+    }
+    recordings.sort_by_key(|r| std::cmp::Reverse(r.started_at));
+    Ok(recordings)
+}
+
+/// Read the raw asciicast file.
+pub fn read_recording(id: &str) -> Result<String> {
+    let path = cast_path(id)?;
+    flush_active(id);
+    fs::read_to_string(&path).with_context(|| format!("Recording not found: {}", id))
+}
+
+/// `GET /api/recordings/:id` - download a recording as a `.cast` file.
+pub async fn download_handler(Path(id): Path<String>) -> impl IntoResponse {
+    match read_recording(&id) {
+        Ok(content) => (
+            StatusCode::OK,
+            [
+                (header::CONTENT_TYPE, "application/x-asciicast".to_string()),
+                (
+                    header::CONTENT_DISPOSITION,
+                    format!("attachment; filename=\"{}.cast\"", id),
+                ),
+            ],
+            content,
+        )
+            .into_response(),
+        Err(e) => (StatusCode::NOT_FOUND, e.to_string()).into_response(),
+    }
+}
+
+pub fn delete_recording(id: &str) -> Result<()> {
+    if is_active(id) {
+        anyhow::bail!("Recording {} is still in progress", id);
+    }
+    let cast = cast_path(id)?;
+    let info = info_path(id)?;
+    if !cast.exists() && !info.exists() {
+        anyhow::bail!("Recording not found: {}", id);
+    }
+    let _ = fs::remove_file(cast);
+    let _ = fs::remove_file(info);
+    info!("Deleted recording {}", id);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(dir: &std::path::Path, id: &str) -> Vec<serde_json::Value> {
+        fs::read_to_string(dir.join(format!("{}.cast", id)))
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn writes_header_events_and_final_info() {
+        let dir = std::env::temp_dir().join(format!("webmux-rec-{}", Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut recorder = Recorder::start_in(dir.clone(), "work", None, 80, 24, true).unwrap();
+        let id = recorder.info().id.clone();
+        assert!(is_active(&id));
+
+        recorder.output("hello\r\n");
+        recorder.input("ls\r");
+        recorder.resize(100, 30);
+        let info = recorder.finish().unwrap();
+        assert!(!is_active(&id));
+
+        let lines = lines(&dir, &id);
+        assert_eq!(lines[0]["version"], 2);
+        assert_eq!(lines[0]["width"], 80);
+        assert_eq!(lines[0]["height"], 24);
+        assert_eq!(lines[0]["title"], "work");
+        let events: Vec<_> = lines[1..].iter().map(|e| (e[1].clone(), e[2].clone())).collect();
+        assert_eq!(
+            events,
+            vec![
+                ("o".into(), "hello\r\n".into()),
+                ("i".into(), "ls\r".into()),
+                ("r".into(), "100x30".into()),
+            ]
+        );
+        assert!(lines[1][0].as_f64().unwrap() <= lines[3][0].as_f64().unwrap());
+
+        let stored: RecordingInfo =
+            serde_json::from_str(&fs::read_to_string(dir.join(format!("{}.json", id))).unwrap()).unwrap();
+        assert!(!stored.active);
+        assert!(stored.duration.is_some());
+        assert_eq!(stored.size, info.size);
+        assert_eq!(info.size, fs::metadata(dir.join(format!("{}.cast", id))).unwrap().len());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn skips_input_unless_requested() {
+        let dir = std::env::temp_dir().join(format!("webmux-rec-{}", Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut recorder = Recorder::start_in(dir.clone(), "work", Some("demo".into()), 80, 24, false).unwrap();
+        let id = recorder.info().id.clone();
+        recorder.input("secret\r");
+        recorder.output("ok");
+        recorder.finish().unwrap();
+
+        let lines = lines(&dir, &id);
+        assert_eq!(lines[0]["title"], "demo");
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[1][1], "o");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
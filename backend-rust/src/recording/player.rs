@@ -0,0 +1,335 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+
+/// Clears the screen and scrollback before a seek redraws from the start.
+const TERMINAL_RESET: &str = "\x1bc";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CastEvent {
+    Output(String),
+    Resize(u16, u16),
+}
+
+/// A parsed asciicast v2 file with timestamps already idle-capped.
+#[derive(Debug, Clone)]
+pub struct Cast {
+    pub width: u16,
+    pub height: u16,
+    pub events: Vec<(f64, CastEvent)>,
+}
+
+impl Cast {
+    pub fn duration(&self) -> f64 {
+        self.events.last().map(|(time, _)| *time).unwrap_or(0.0)
+    }
+}
+
+/// Parse an asciicast v2 file. Pauses longer than `idle_time_limit` (or the
+/// file's own `idle_time_limit`) are shortened to that limit. Input and
+/// marker events are dropped.
+pub fn parse_cast(content: &str, idle_time_limit: Option<f64>) -> Result<Cast> {
+    let mut lines = content.lines().filter(|l| !l.trim().is_empty());
+    let header: serde_json::Value = serde_json::from_str(
+        lines
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("Recording is empty"))?,
+    )?;
+    if header["version"].as_u64() != Some(2) {
+        anyhow::bail!("Unsupported asciicast version");
+    }
+    let width = header["width"].as_u64().unwrap_or(80) as u16;
+    let height = header["height"].as_u64().unwrap_or(24) as u16;
+    let idle_time_limit = idle_time_limit
+        .or_else(|| header["idle_time_limit"].as_f64())
+        .filter(|limit| *limit > 0.0);
+
+    let mut events = Vec::new();
+    let mut last_time = 0.0;
+    let mut adjusted = 0.0;
+    for line in lines {
+        let (time, code, data): (f64, String, String) = serde_json::from_str(line)?;
+        let delay = (time - last_time).max(0.0);
+        last_time = time;
+        adjusted += idle_time_limit.map_or(delay, |limit| delay.min(limit));
+
+        let event = match code.as_str() {
+            "o" => CastEvent::Output(data),
+            "r" => match data.split_once('x') {
+                Some((cols, rows)) => match (cols.parse(), rows.parse()) {
+                    (Ok(cols), Ok(rows)) => CastEvent::Resize(cols, rows),
+                    _ => continue,
+                },
+                None => continue,
+            },
+            _ => continue,
+        };
+        events.push((adjusted, event));
+    }
+
+    Ok(Cast {
+        width,
+        height,
+        events,
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PlaybackState {
+    Playing,
+    Paused,
+    Finished,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlaybackStatus {
+    pub id: String,
+    pub state: PlaybackState,
+    /// Seconds into the (idle-capped) recording.
+    pub position: f64,
+    pub duration: f64,
+    pub speed: f64,
+    pub width: u16,
+    pub height: u16,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PlaybackAction {
+    Pause,
+    Resume,
+    Seek,
+    SetSpeed,
+    Stop,
+}
+
+#[derive(Debug)]
+pub enum PlayerCommand {
+    Pause,
+    Resume,
+    Seek(f64),
+    SetSpeed(f64),
+}
+
+#[derive(Debug)]
+pub enum PlayerEvent {
+    Output(String),
+    Status(PlaybackStatus),
+}
+
+/// Position within a cast: which events have been played and the terminal
+/// size they left behind.
+struct Cursor<'a> {
+    cast: &'a Cast,
+    next: usize,
+    width: u16,
+    height: u16,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(cast: &'a Cast) -> Self {
+        Self {
+            cast,
+            next: 0,
+            width: cast.width,
+            height: cast.height,
+        }
+    }
+
+    /// Rewind and replay everything up to `position` as one redraw.
+    fn seek(&mut self, position: f64) -> String {
+        self.next = 0;
+        self.width = self.cast.width;
+        self.height = self.cast.height;
+        let mut output = TERMINAL_RESET.to_string();
+        output.push_str(&self.advance(position));
+        output
+    }
+
+    /// Collect output for every event at or before `position`.
+    fn advance(&mut self, position: f64) -> String {
+        let mut output = String::new();
+        while let Some((time, event)) = self.cast.events.get(self.next) {
+            if *time > position {
+                break;
+            }
+            match event {
+                CastEvent::Output(data) => output.push_str(data),
+                CastEvent::Resize(cols, rows) => {
+                    self.width = *cols;
+                    self.height = *rows;
+                }
+            }
+            self.next += 1;
+        }
+        output
+    }
+
+    fn next_time(&self) -> Option<f64> {
+        self.cast.events.get(self.next).map(|(time, _)| *time)
+    }
+}
+
+/// Play `cast` with its original timing until the command channel closes.
+pub async fn run(
+    id: String,
+    cast: Cast,
+    start: f64,
+    speed: f64,
+    mut commands: mpsc::UnboundedReceiver<PlayerCommand>,
+    events: mpsc::UnboundedSender<PlayerEvent>,
+) {
+    let duration = cast.duration();
+    let mut cursor = Cursor::new(&cast);
+    let mut speed = speed;
+    let mut state = PlaybackState::Playing;
+    // Playback position is `base_position` plus wall time since `base_at`,
+    // scaled by speed, while playing
+    let mut base_position = start.clamp(0.0, duration);
+    let mut base_at = Instant::now();
+
+    let status = |state, position: f64, speed, cursor: &Cursor| {
+        PlayerEvent::Status(PlaybackStatus {
+            id: id.clone(),
+            state,
+            position: position.min(duration),
+            duration,
+            speed,
+            width: cursor.width,
+            height: cursor.height,
+        })
+    };
+
+    let _ = events.send(PlayerEvent::Output(cursor.seek(base_position)));
+    let _ = events.send(status(state, base_position, speed, &cursor));
+
+    loop {
+        let position = match state {
+            PlaybackState::Playing => base_position + base_at.elapsed().as_secs_f64() * speed,
+            _ => base_position,
+        };
+
+        if state == PlaybackState::Playing && cursor.next_time().is_none() {
+            state = PlaybackState::Finished;
+            base_position = duration;
+            if events.send(status(state, duration, speed, &cursor)).is_err() {
+                break;
+            }
+            continue;
+        }
+
+        let wait = match (state, cursor.next_time()) {
+            (PlaybackState::Playing, Some(time)) => {
+                Some(Duration::from_secs_f64(((time - position) / speed).max(0.0)))
+            }
+            _ => None,
+        };
+
+        tokio::select! {
+            _ = tokio::time::sleep(wait.unwrap_or_default()), if wait.is_some() => {
+                let position = base_position + base_at.elapsed().as_secs_f64() * speed;
+                let (width, height) = (cursor.width, cursor.height);
+                let output = cursor.advance(position);
+                if !output.is_empty() && events.send(PlayerEvent::Output(output)).is_err() {
+                    break;
+                }
+                if (width, height) != (cursor.width, cursor.height) {
+                    let _ = events.send(status(state, position, speed, &cursor));
+                }
+            }
+            command = commands.recv() => {
+                let Some(command) = command else {
+                    break;
+                };
+                match command {
+                    PlayerCommand::Pause => {
+                        if state == PlaybackState::Playing {
+                            base_position = position;
+                            state = PlaybackState::Paused;
+                        }
+                    }
+                    PlayerCommand::Resume => {
+                        if state == PlaybackState::Finished {
+                            // Resuming a finished recording starts it over
+                            let _ = events.send(PlayerEvent::Output(cursor.seek(0.0)));
+                            base_position = 0.0;
+                        }
+                        base_at = Instant::now();
+                        state = PlaybackState::Playing;
+                    }
+                    PlayerCommand::Seek(target) => {
+                        base_position = target.clamp(0.0, duration);
+                        base_at = Instant::now();
+                        let _ = events.send(PlayerEvent::Output(cursor.seek(base_position)));
+                        if state == PlaybackState::Finished {
+                            state = PlaybackState::Paused;
+                        }
+                    }
+                    PlayerCommand::SetSpeed(new_speed) => {
+                        base_position = position;
+                        base_at = Instant::now();
+                        speed = new_speed;
+                    }
+                }
+                let _ = events.send(status(state, base_position, speed, &cursor));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CAST: &str = r#"{"version": 2, "width": 80, "height": 24}
+[0.5, "o", "hello "]
+[0.75, "i", "x"]
+[10.75, "o", "world"]
+[11.0, "r", "100x30"]
+[11.5, "o", "!"]
+"#;
+
+    #[test]
+    fn parses_events_and_caps_idle_time() {
+        let cast = parse_cast(CAST, Some(2.0)).unwrap();
+
+        assert_eq!(cast.width, 80);
+        assert_eq!(cast.events.len(), 4);
+        assert_eq!(cast.events[0], (0.5, CastEvent::Output("hello ".into())));
+        // The 10s pause after the input event is cut to 2s
+        assert_eq!(cast.events[1].0, 2.75);
+        assert_eq!(cast.events[2], (3.0, CastEvent::Resize(100, 30)));
+        assert_eq!(cast.duration(), 3.5);
+    }
+
+    #[test]
+    fn keeps_original_timing_without_limit() {
+        let cast = parse_cast(CAST, None).unwrap();
+        assert_eq!(cast.duration(), 11.5);
+    }
+
+    #[test]
+    fn rejects_other_versions() {
+        assert!(parse_cast(r#"{"version": 1}"#, None).is_err());
+        assert!(parse_cast("", None).is_err());
+    }
+
+    #[test]
+    fn seek_replays_output_and_size() {
+        let cast = parse_cast(CAST, None).unwrap();
+        let mut cursor = Cursor::new(&cast);
+
+        assert_eq!(cursor.seek(11.2), "\x1bchello world");
+        assert_eq!((cursor.width, cursor.height), (100, 30));
+        assert_eq!(cursor.next_time(), Some(11.5));
+
+        assert_eq!(cursor.seek(1.0), "\x1bchello ");
+        assert_eq!((cursor.width, cursor.height), (80, 24));
+        assert_eq!(cursor.advance(20.0), "world!");
+        assert_eq!(cursor.next_time(), None);
+    }
+}
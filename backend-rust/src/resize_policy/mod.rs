@@ -0,0 +1,208 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use tokio::sync::RwLock;
+use tracing::{info, warn};
+
+use crate::{storage, tmux};
+
+/// How tmux sizes a session's windows when several clients of different
+/// sizes are attached.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "lowercase")]
+pub enum ResizePolicy {
+    Largest,
+    Smallest,
+    /// The size of the client that most recently had input.
+    Latest,
+    Fixed { cols: u16, rows: u16 },
+}
+
+impl ResizePolicy {
+    /// Value of the `window-size` window option.
+    fn window_size(&self) -> &'static str {
+        match self {
+            ResizePolicy::Largest => "largest",
+            ResizePolicy::Smallest => "smallest",
+            ResizePolicy::Latest => "latest",
+            ResizePolicy::Fixed { .. } => "manual",
+        }
+    }
+
+    /// Command that applies the policy to the current window, for use in a
+    /// hook and as the per-window command.
+    fn window_command(&self) -> Vec<String> {
+        match self {
+            ResizePolicy::Fixed { cols, rows } => vec![
+                "resize-window".to_string(),
+                "-x".to_string(),
+                cols.to_string(),
+                "-y".to_string(),
+                rows.to_string(),
+            ],
+            _ => vec![
+                "set-option".to_string(),
+                "-w".to_string(),
+                "window-size".to_string(),
+                self.window_size().to_string(),
+            ],
+        }
+    }
+
+    fn validate(&self) -> Result<()> {
+        if let ResizePolicy::Fixed { cols, rows } = self {
+            if *cols < 10 || *rows < 5 {
+                anyhow::bail!("Fixed size must be at least 10x5");
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The size tmux actually gave a session's current window.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct SessionSize {
+    pub cols: u16,
+    pub rows: u16,
+}
+
+pub async fn session_size(session_name: &str) -> Result<SessionSize> {
+    let target = format!("={}:", session_name);
+    let output = tmux::run_tmux(&["display-message", "-p", "-t", &target, "#{window_width} #{window_height}"]).await?;
+    let (cols, rows) = output
+        .trim()
+        .split_once(' ')
+        .ok_or_else(|| anyhow::anyhow!("Unexpected window size: {}", output.trim()))?;
+    Ok(SessionSize {
+        cols: cols.parse()?,
+        rows: rows.parse()?,
+    })
+}
+
+/// Per-session resize policies, kept in `~/.webmux/resize/policies.json`
+/// and applied through tmux's `window-size` option.
+pub struct ResizePolicyManager {
+    policies: RwLock<HashMap<String, ResizePolicy>>,
+}
+
+impl ResizePolicyManager {
+    pub fn new() -> Self {
+        Self {
+            policies: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Load saved policies and apply them to sessions that already exist.
+    pub async fn initialize(&self) -> Result<()> {
+        let policies: HashMap<String, ResizePolicy> = storage::load_json(&policies_path()?)?;
+        for (session_name, policy) in &policies {
+            if tmux::has_session(session_name).await {
+                if let Err(e) = apply(session_name, Some(policy)).await {
+                    warn!("Failed to apply resize policy to {}: {}", session_name, e);
+                }
+            }
+        }
+        *self.policies.write().await = policies;
+        Ok(())
+    }
+
+    pub async fn get(&self, session_name: &str) -> Option<ResizePolicy> {
+        self.policies.read().await.get(session_name).copied()
+    }
+
+    /// Set or clear (`None`) a session's policy. Clearing hands sizing back
+    /// to the global `window-size` option.
+    pub async fn set(&self, session_name: &str, policy: Option<ResizePolicy>) -> Result<()> {
+        if let Some(policy) = &policy {
+            policy.validate()?;
+        }
+        if !tmux::has_session(session_name).await {
+            anyhow::bail!("Session not found: {}", session_name);
+        }
+        apply(session_name, policy.as_ref()).await?;
+
+        let mut policies = self.policies.write().await;
+        match policy {
+            Some(policy) => policies.insert(session_name.to_string(), policy),
+            None => policies.remove(session_name),
+        };
+        storage::save_json(&policies_path()?, &*policies)?;
+        info!("Resize policy for {}: {:?}", session_name, policy);
+        Ok(())
+    }
+
+    /// Re-apply a saved policy, e.g. after the session was recreated.
+    pub async fn reapply(&self, session_name: &str) {
+        if let Some(policy) = self.get(session_name).await {
+            if let Err(e) = apply(session_name, Some(&policy)).await {
+                warn!("Failed to apply resize policy to {}: {}", session_name, e);
+            }
+        }
+    }
+}
+
+/// Apply a policy to every window of a session, and install a hook so
+/// windows created later follow it too.
+async fn apply(session_name: &str, policy: Option<&ResizePolicy>) -> Result<()> {
+    let session_target = format!("={}:", session_name);
+    let windows = tmux::list_windows(session_name).await?;
+
+    match policy {
+        Some(policy) => {
+            let command = policy.window_command();
+            for window in &windows {
+                let target = format!("={}:{}", session_name, window.index);
+                let mut args: Vec<&str> = command.iter().map(String::as_str).collect();
+                args.splice(1..1, ["-t", target.as_str()]);
+                tmux::run_tmux(&args).await?;
+            }
+            let hook = command.join(" ");
+            tmux::run_tmux(&["set-hook", "-t", &session_target, "after-new-window", &hook]).await?;
+        }
+        None => {
+            for window in &windows {
+                let target = format!("={}:{}", session_name, window.index);
+                tmux::run_tmux(&["set-option", "-w", "-u", "-t", &target, "window-size"]).await?;
+            }
+            tmux::run_tmux(&["set-hook", "-u", "-t", &session_target, "after-new-window"]).await?;
+        }
+    }
+    Ok(())
+}
+
+fn policies_path() -> Result<PathBuf> {
+    Ok(storage::data_dir("resize")?.join("policies.json"))
+}
+
+lazy_static::lazy_static! {
+    pub static ref RESIZE_POLICIES: ResizePolicyManager = ResizePolicyManager::new();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn policies_are_tagged_by_mode() {
+        let fixed: ResizePolicy = serde_json::from_str(r#"{"mode":"fixed","cols":120,"rows":40}"#).unwrap();
+        assert_eq!(fixed, ResizePolicy::Fixed { cols: 120, rows: 40 });
+        assert_eq!(
+            serde_json::to_string(&ResizePolicy::Largest).unwrap(),
+            r#"{"mode":"largest"}"#
+        );
+    }
+
+    #[test]
+    fn builds_window_commands() {
+        assert_eq!(
+            ResizePolicy::Smallest.window_command().join(" "),
+            "set-option -w window-size smallest"
+        );
+        assert_eq!(
+            ResizePolicy::Fixed { cols: 100, rows: 30 }.window_command().join(" "),
+            "resize-window -x 100 -y 30"
+        );
+        assert!(ResizePolicy::Fixed { cols: 2, rows: 30 }.validate().is_err());
+    }
+}
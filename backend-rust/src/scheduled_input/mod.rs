@@ -0,0 +1,253 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::sync::{mpsc, Notify, RwLock};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::{
+    storage, tmux,
+    types::{PaneTarget, ServerMessage},
+};
+
+/// Longest the scheduler sleeps without looking at the clock again.
+const MAX_SLEEP: Duration = Duration::from_secs(60);
+/// Input overdue by more than this, e.g. after the server was down, is
+/// reported as missed instead of sent.
+const MAX_LATENESS_MINUTES: i64 = 10;
+/// Furthest ahead input can be scheduled.
+const MAX_DELAY_DAYS: i64 = 365;
+
+/// Text to type into a pane once, at `run_at`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduledInput {
+    pub id: String,
+    pub name: Option<String>,
+    #[serde(flatten)]
+    pub target: PaneTarget,
+    pub text: String,
+    /// Press Enter after the text.
+    pub enter: bool,
+    pub run_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// When input delayed by `delay_seconds` from now is due.
+pub fn run_at_after(delay_seconds: u64) -> Result<DateTime<Utc>> {
+    i64::try_from(delay_seconds)
+        .ok()
+        .and_then(chrono::TimeDelta::try_seconds)
+        .filter(|delay| *delay <= chrono::TimeDelta::days(MAX_DELAY_DAYS))
+        .and_then(|delay| Utc::now().checked_add_signed(delay))
+        .ok_or_else(|| anyhow::anyhow!("Delay is longer than {} days", MAX_DELAY_DAYS))
+}
+
+/// Split `pending` into input still waiting, input due now, and input
+/// missed by more than the allowed lateness.
+fn take_due(
+    pending: Vec<ScheduledInput>,
+    now: DateTime<Utc>,
+) -> (Vec<ScheduledInput>, Vec<ScheduledInput>, Vec<ScheduledInput>) {
+    let (due, waiting): (Vec<_>, Vec<_>) = pending.into_iter().partition(|input| input.run_at <= now);
+    let (missed, due) = due
+        .into_iter()
+        .partition(|input| now - input.run_at > chrono::Duration::minutes(MAX_LATENESS_MINUTES));
+    (waiting, due, missed)
+}
+
+/// One-off pane input waiting to be sent, kept in
+/// `~/.webmux/scheduled/inputs.json` so it survives restarts.
+pub struct ScheduledInputManager {
+    pending: RwLock<Vec<ScheduledInput>>,
+    changed: Notify,
+}
+
+impl ScheduledInputManager {
+    pub fn new() -> Self {
+        Self {
+            pending: RwLock::new(Vec::new()),
+            changed: Notify::new(),
+        }
+    }
+
+    pub async fn initialize(&self) -> Result<()> {
+        let pending: Vec<ScheduledInput> = storage::load_json(&inputs_path()?)?;
+        *self.pending.write().await = pending;
+        Ok(())
+    }
+
+    /// Pending input, soonest first.
+    pub async fn list(&self) -> Vec<ScheduledInput> {
+        let mut pending = self.pending.read().await.clone();
+        pending.sort_by_key(|input| input.run_at);
+        pending
+    }
+
+    pub async fn schedule(
+        &self,
+        name: Option<String>,
+        target: PaneTarget,
+        text: String,
+        enter: bool,
+        run_at: DateTime<Utc>,
+    ) -> Result<ScheduledInput> {
+        if text.is_empty() && !enter {
+            anyhow::bail!("Nothing to send");
+        }
+        if run_at < Utc::now() - chrono::Duration::minutes(1) {
+            anyhow::bail!("Scheduled time is in the past");
+        }
+        if run_at > Utc::now() + chrono::TimeDelta::days(MAX_DELAY_DAYS) {
+            anyhow::bail!("Scheduled time is more than {} days away", MAX_DELAY_DAYS);
+        }
+        if !tmux::has_session(&target.session_name).await {
+            anyhow::bail!("Session not found: {}", target.session_name);
+        }
+
+        let input = ScheduledInput {
+            id: Uuid::new_v4().to_string(),
+            name,
+            target,
+            text,
+            enter,
+            run_at,
+            created_at: Utc::now(),
+        };
+        let mut pending = self.pending.write().await;
+        pending.push(input.clone());
+        storage::save_json(&inputs_path()?, &*pending)?;
+        drop(pending);
+        self.changed.notify_one();
+        info!("Scheduled input {} for {} at {}", input.id, input.target.tmux_target(), input.run_at);
+        Ok(input)
+    }
+
+    pub async fn cancel(&self, id: &str) -> Result<()> {
+        let mut pending = self.pending.write().await;
+        let before = pending.len();
+        pending.retain(|input| input.id != id);
+        if pending.len() == before {
+            anyhow::bail!("Scheduled input not found: {}", id);
+        }
+        storage::save_json(&inputs_path()?, &*pending)?;
+        drop(pending);
+        self.changed.notify_one();
+        Ok(())
+    }
+
+    /// Send input as it comes due and broadcast the outcome.
+    pub async fn run(&self, broadcast_tx: mpsc::UnboundedSender<ServerMessage>) {
+        loop {
+            let next = self.pending.read().await.iter().map(|input| input.run_at).min();
+            let sleep = next
+                .map(|at| (at - Utc::now()).to_std().unwrap_or(Duration::ZERO))
+                .unwrap_or(MAX_SLEEP)
+                .min(MAX_SLEEP);
+            tokio::select! {
+                _ = tokio::time::sleep(sleep) => {}
+                _ = self.changed.notified() => continue,
+            }
+
+            let (due, missed) = {
+                let mut pending = self.pending.write().await;
+                let (waiting, due, missed) = take_due(std::mem::take(&mut *pending), Utc::now());
+                *pending = waiting;
+                if !due.is_empty() || !missed.is_empty() {
+                    if let Err(e) = inputs_path().and_then(|path| storage::save_json(&path, &*pending)) {
+                        warn!("Failed to save scheduled input: {}", e);
+                    }
+                }
+                (due, missed)
+            };
+
+            for input in missed {
+                let error = format!("Missed by {} minutes", (Utc::now() - input.run_at).num_minutes());
+                warn!("Scheduled input {} not sent: {}", input.id, error);
+                let _ = broadcast_tx.send(ServerMessage::ScheduledInputSent {
+                    input,
+                    success: false,
+                    error: Some(error),
+                });
+            }
+            for input in due {
+                let result = send(&input).await;
+                if let Err(e) = &result {
+                    warn!("Scheduled input {} failed: {}", input.id, e);
+                }
+                let _ = broadcast_tx.send(ServerMessage::ScheduledInputSent {
+                    input,
+                    success: result.is_ok(),
+                    error: result.err().map(|e| e.to_string()),
+                });
+            }
+        }
+    }
+}
+
+async fn send(input: &ScheduledInput) -> Result<()> {
+    let target = input.target.tmux_target();
+    if !input.text.is_empty() {
+        tmux::send_keys_to_session(&target, &input.text).await?;
+    }
+    if input.enter {
+        tmux::send_special_key(&target, "Enter").await?;
+    }
+    Ok(())
+}
+
+fn inputs_path() -> Result<PathBuf> {
+    Ok(storage::data_dir("scheduled")?.join("inputs.json"))
+}
+
+lazy_static::lazy_static! {
+    pub static ref SCHEDULED_INPUTS: ScheduledInputManager = ScheduledInputManager::new();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input(id: &str, run_at: DateTime<Utc>) -> ScheduledInput {
+        ScheduledInput {
+            id: id.into(),
+            name: None,
+            target: PaneTarget {
+                session_name: "work".into(),
+                window_index: Some(2),
+                pane_index: None,
+            },
+            text: "git push".into(),
+            enter: true,
+            run_at,
+            created_at: run_at,
+        }
+    }
+
+    #[test]
+    fn splits_waiting_due_and_missed_input() {
+        let now = Utc::now();
+        let pending = vec![
+            input("later", now + chrono::Duration::minutes(5)),
+            input("now", now),
+            input("late", now - chrono::Duration::minutes(3)),
+            input("missed", now - chrono::Duration::hours(2)),
+        ];
+        let ids = |inputs: &[ScheduledInput]| inputs.iter().map(|i| i.id.clone()).collect::<Vec<_>>();
+
+        let (waiting, due, missed) = take_due(pending, now);
+        assert_eq!(ids(&waiting), vec!["later"]);
+        assert_eq!(ids(&due), vec!["now", "late"]);
+        assert_eq!(ids(&missed), vec!["missed"]);
+    }
+
+    #[test]
+    fn rejects_delays_beyond_a_year() {
+        assert!(run_at_after(60).is_ok());
+        assert!(run_at_after(366 * 24 * 60 * 60).is_err());
+        assert!(run_at_after(i64::MAX as u64 / 1000 + 1).is_err());
+        assert!(run_at_after(u64::MAX).is_err());
+    }
+}
@@ -0,0 +1,219 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::{Mutex, RwLock};
+
+/// In-memory model of what an attached tmux client is displaying, fed from
+/// the same PTY output that goes to the browser.
+pub struct TerminalScreen {
+    parser: vt100::Parser,
+}
+
+pub type SharedScreen = Arc<Mutex<TerminalScreen>>;
+
+/// What a terminal currently shows.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScreenSnapshot {
+    pub cols: u16,
+    pub rows: u16,
+    pub cursor_row: u16,
+    pub cursor_col: u16,
+    pub cursor_visible: bool,
+    pub alternate_screen: bool,
+    pub title: String,
+    /// Plain text, one line per row with trailing blanks trimmed.
+    pub text: String,
+    /// Escape sequences that redraw the screen contents and cursor from scratch
+    /// when written to a terminal of the same size.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub formatted: Option<String>,
+}
+
+impl TerminalScreen {
+    pub fn new(rows: u16, cols: u16) -> Self {
+        Self {
+            // tmux keeps the history; the model only needs the visible screen
+            parser: vt100::Parser::new(rows, cols, 0),
+        }
+    }
+
+    pub fn process(&mut self, data: &[u8]) {
+        self.parser.process(data);
+    }
+
+    pub fn resize(&mut self, rows: u16, cols: u16) {
+        self.parser.set_size(rows, cols);
+    }
+
+    pub fn screen(&self) -> &vt100::Screen {
+        self.parser.screen()
+    }
+
+    pub fn snapshot(&self, formatted: bool) -> ScreenSnapshot {
+        let screen = self.parser.screen();
+        let (rows, cols) = screen.size();
+        let (cursor_row, cursor_col) = screen.cursor_position();
+        ScreenSnapshot {
+            cols,
+            rows,
+            cursor_row,
+            cursor_col,
+            cursor_visible: !screen.hide_cursor(),
+            alternate_screen: screen.alternate_screen(),
+            title: screen.title().to_string(),
+            text: screen.contents(),
+            formatted: formatted.then(|| String::from_utf8_lossy(&screen.state_formatted()).into_owned()),
+        }
+    }
+}
+
+/// Turns successive states of a screen into the escape sequences that bring
+/// a client from the previous frame to the current one.
+#[derive(Default)]
+pub struct ScreenDiffer {
+    previous: Option<vt100::Screen>,
+}
+
+impl ScreenDiffer {
+    /// Escape sequences for the changes since the last frame, or `None` if
+    /// nothing changed. The first frame, and any frame after a resize or
+    /// `reset`, is a full redraw.
+    pub fn frame(&mut self, screen: &vt100::Screen) -> Option<Vec<u8>> {
+        let update = match &self.previous {
+            Some(previous) if previous.size() == screen.size() => screen.state_diff(previous),
+            _ => screen.state_formatted(),
+        };
+        self.previous = Some(screen.clone());
+        (!update.is_empty()).then_some(update)
+    }
+
+    pub fn reset(&mut self) {
+        self.previous = None;
+    }
+}
+
+struct Attachment {
+    session_name: String,
+    screen: SharedScreen,
+}
+
+/// Screen models of every live attachment, keyed by client id.
+pub struct ScreenRegistry {
+    attachments: RwLock<HashMap<String, Attachment>>,
+}
+
+impl ScreenRegistry {
+    fn new() -> Self {
+        Self {
+            attachments: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Record a client's attachment, replacing any previous one.
+    pub async fn register(&self, client_id: &str, session_name: &str, screen: SharedScreen) {
+        self.attachments.write().await.insert(
+            client_id.to_string(),
+            Attachment {
+                session_name: session_name.to_string(),
+                screen,
+            },
+        );
+    }
+
+    pub async fn unregister(&self, client_id: &str) {
+        self.attachments.write().await.remove(client_id);
+    }
+
+    /// The screen and session of a client's current attachment.
+    pub async fn for_client(&self, client_id: &str) -> Option<(String, SharedScreen)> {
+        self.attachments
+            .read()
+            .await
+            .get(client_id)
+            .map(|a| (a.session_name.clone(), a.screen.clone()))
+    }
+
+    /// Any attachment showing `session_name`, other than `exclude_client`'s.
+    pub async fn for_session(&self, session_name: &str, exclude_client: Option<&str>) -> Option<SharedScreen> {
+        self.attachments
+            .read()
+            .await
+            .iter()
+            .find(|(client_id, a)| a.session_name == session_name && Some(client_id.as_str()) != exclude_client)
+            .map(|(_, a)| a.screen.clone())
+    }
+}
+
+lazy_static::lazy_static! {
+    pub static ref SCREEN_REGISTRY: ScreenRegistry = ScreenRegistry::new();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tracks_text_and_cursor() {
+        let mut screen = TerminalScreen::new(5, 20);
+        screen.process(b"\x1b]0;build\x07hello\r\nworld\x1b[?25l");
+
+        let snapshot = screen.snapshot(false);
+
+        assert_eq!(snapshot.text, "hello\nworld");
+        assert_eq!((snapshot.cursor_row, snapshot.cursor_col), (1, 5));
+        assert!(!snapshot.cursor_visible);
+        assert_eq!(snapshot.title, "build");
+        assert!(snapshot.formatted.is_none());
+    }
+
+    #[test]
+    fn formatted_state_reproduces_screen() {
+        let mut screen = TerminalScreen::new(5, 20);
+        screen.process(b"\x1b[31mred\x1b[m plain\r\n  indented\x1b[2;4H");
+
+        let original = screen.snapshot(true);
+        let mut copy = TerminalScreen::new(5, 20);
+        copy.process(original.formatted.unwrap().as_bytes());
+        let copied = copy.snapshot(false);
+
+        assert_eq!(copied.text, original.text);
+        assert_eq!((copied.cursor_row, copied.cursor_col), (1, 3));
+    }
+
+    #[test]
+    fn differ_sends_only_changes() {
+        let mut screen = TerminalScreen::new(5, 20);
+        let mut differ = ScreenDiffer::default();
+        screen.process(b"hello");
+
+        let full = differ.frame(screen.screen()).unwrap();
+        assert!(String::from_utf8_lossy(&full).contains("hello"));
+        assert!(differ.frame(screen.screen()).is_none());
+
+        screen.process(b"\r\nworld");
+        let update = differ.frame(screen.screen()).unwrap();
+        let mut client = TerminalScreen::new(5, 20);
+        client.process(&full);
+        client.process(&update);
+        assert_eq!(client.snapshot(false).text, "hello\nworld");
+        assert!(!String::from_utf8_lossy(&update).contains("hello"));
+
+        differ.reset();
+        assert!(String::from_utf8_lossy(&differ.frame(screen.screen()).unwrap()).contains("hello"));
+    }
+
+    #[tokio::test]
+    async fn finds_other_clients_on_a_session() {
+        let registry = ScreenRegistry::new();
+        let screen: SharedScreen = Arc::new(Mutex::new(TerminalScreen::new(5, 20)));
+        registry.register("a", "work", screen).await;
+
+        assert!(registry.for_session("work", Some("b")).await.is_some());
+        assert!(registry.for_session("work", Some("a")).await.is_none());
+        assert!(registry.for_session("other", None).await.is_none());
+
+        registry.unregister("a").await;
+        assert!(registry.for_client("a").await.is_none());
+    }
+}
@@ -0,0 +1,233 @@
+use anyhow::Result;
+use regex::RegexBuilder;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::tmux;
+use crate::types::PaneTarget;
+
+/// Upper bound on matches returned by one search, whatever the client asks for.
+const MAX_SEARCH_RESULTS: usize = 1000;
+const MAX_CONTEXT_LINES: usize = 20;
+
+/// A slice of a pane's history. Line numbers count screen rows from the
+/// oldest row still in tmux's history, starting at 0, so a long line wrapped
+/// over several rows takes several numbers.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScrollbackRange {
+    pub start: usize,
+    pub lines: Vec<String>,
+    pub total_lines: usize,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchOptions {
+    #[serde(default)]
+    pub case_sensitive: bool,
+    /// Lines of context before and after each match.
+    #[serde(default)]
+    pub context: usize,
+    pub max_results: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LineMatch {
+    pub line: usize,
+    pub text: String,
+    /// Byte offsets of each match within `text`.
+    pub ranges: Vec<(usize, usize)>,
+    pub before: Vec<String>,
+    pub after: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PaneMatches {
+    pub target: PaneTarget,
+    pub matches: Vec<LineMatch>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchResults {
+    pub panes: Vec<PaneMatches>,
+    pub total_matches: usize,
+    /// True when the result limit cut the search short.
+    pub truncated: bool,
+}
+
+/// Fetch lines `start..=end` of a pane's history. Missing bounds mean the
+/// oldest and newest line; out-of-range bounds are clamped.
+pub async fn get_range(
+    target: &PaneTarget,
+    start: Option<usize>,
+    end: Option<usize>,
+    escapes: bool,
+) -> Result<ScrollbackRange> {
+    let (history, height) = tmux::pane_line_counts(target).await?;
+    let total_lines = history + height;
+
+    let start = start.unwrap_or(0).min(total_lines);
+    let end = end.map_or(total_lines, |end| end.saturating_add(1).min(total_lines));
+    let lines = if start < end {
+        // tmux numbers the top visible row 0 and history rows below it
+        let row = |line: usize| line as i64 - history as i64;
+        tmux::capture_rows(target, row(start), row(end - 1), escapes).await?
+    } else {
+        Vec::new()
+    };
+
+    Ok(ScrollbackRange {
+        start,
+        lines,
+        total_lines,
+    })
+}
+
+/// Search the history of one pane, or of every pane when no target is given.
+pub async fn search(
+    pattern: &str,
+    target: Option<&PaneTarget>,
+    options: &SearchOptions,
+) -> Result<SearchResults> {
+    let regex = RegexBuilder::new(pattern)
+        .case_insensitive(!options.case_sensitive)
+        .build()
+        .map_err(|e| anyhow::anyhow!("Invalid pattern: {}", e))?;
+
+    let all_panes = target.is_none();
+    let targets = match target {
+        Some(target) => vec![target.clone()],
+        None => tmux::list_all_panes().await?,
+    };
+    let limit = options
+        .max_results
+        .unwrap_or(MAX_SEARCH_RESULTS)
+        .min(MAX_SEARCH_RESULTS);
+    let context = options.context.min(MAX_CONTEXT_LINES);
+
+    let mut panes = Vec::new();
+    let mut total_matches = 0;
+    let mut truncated = false;
+    for target in targets {
+        let rows = match tmux::pane_line_counts(&target).await {
+            Ok((history, height)) => tmux::capture_rows(&target, -(history as i64), height as i64 - 1, false).await,
+            Err(e) => Err(e),
+        };
+        let rows = match rows {
+            Ok(rows) => rows,
+            // A single pane closing mid-search shouldn't fail the whole thing
+            Err(e) if all_panes => {
+                warn!("Skipping pane {} in search: {}", target.tmux_target(), e);
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+
+        // Once the limit is reached, further panes are only searched to
+        // tell whether anything was left out
+        let lines: Vec<&str> = rows.iter().map(String::as_str).collect();
+        let (matches, hit_limit) = search_lines(&lines, &regex, context, limit - total_matches);
+        if !matches.is_empty() {
+            total_matches += matches.len();
+            panes.push(PaneMatches { target, matches });
+        }
+        if hit_limit {
+            truncated = true;
+            break;
+        }
+    }
+
+    Ok(SearchResults {
+        panes,
+        total_matches,
+        truncated,
+    })
+}
+
+/// Find lines matching `regex`, returning at most `limit` matches and whether
+/// more were left unreported.
+pub fn search_lines(
+    lines: &[&str],
+    regex: &regex::Regex,
+    context: usize,
+    limit: usize,
+) -> (Vec<LineMatch>, bool) {
+    let mut matches = Vec::new();
+    for (index, line) in lines.iter().enumerate() {
+        let ranges: Vec<(usize, usize)> = regex
+            .find_iter(line)
+            .filter(|m| !m.is_empty())
+            .map(|m| (m.start(), m.end()))
+            .collect();
+        if ranges.is_empty() {
+            continue;
+        }
+        if matches.len() >= limit {
+            return (matches, true);
+        }
+
+        let before_start = index.saturating_sub(context);
+        let after_end = (index + 1 + context).min(lines.len());
+        matches.push(LineMatch {
+            line: index,
+            text: line.to_string(),
+            ranges,
+            before: lines[before_start..index].iter().map(|l| l.to_string()).collect(),
+            after: lines[index + 1..after_end].iter().map(|l| l.to_string()).collect(),
+        });
+    }
+    (matches, false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use regex::Regex;
+
+    #[test]
+    fn finds_matches_with_context() {
+        let lines = ["one", "two error", "three", "four", "five error error"];
+        let regex = Regex::new("error").unwrap();
+
+        let (matches, truncated) = search_lines(&lines, &regex, 1, 10);
+
+        assert!(!truncated);
+        assert_eq!(matches.len(), 2);
+        assert_eq!(matches[0].line, 1);
+        assert_eq!(matches[0].ranges, vec![(4, 9)]);
+        assert_eq!(matches[0].before, vec!["one"]);
+        assert_eq!(matches[0].after, vec!["three"]);
+        assert_eq!(matches[1].line, 4);
+        assert_eq!(matches[1].ranges, vec![(5, 10), (11, 16)]);
+        assert!(matches[1].after.is_empty());
+    }
+
+    #[test]
+    fn stops_at_limit() {
+        let lines = ["a", "a", "a"];
+        let regex = Regex::new("a").unwrap();
+
+        let (matches, truncated) = search_lines(&lines, &regex, 0, 2);
+
+        assert_eq!(matches.len(), 2);
+        assert!(truncated);
+
+        let (matches, truncated) = search_lines(&lines, &regex, 0, 3);
+        assert_eq!(matches.len(), 3);
+        assert!(!truncated);
+    }
+
+    #[test]
+    fn ignores_empty_matches() {
+        let lines = ["abc", ""];
+        let regex = Regex::new("x*").unwrap();
+
+        let (matches, _) = search_lines(&lines, &regex, 0, 10);
+
+        assert!(matches.is_empty());
+    }
+}
@@ -0,0 +1,525 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use tracing::{error, info, warn};
+
+use crate::{storage, tmux};
+
+/// A declarative session layout, stored as YAML or TOML under
+/// `~/.webmux/templates`.
+///
+/// String fields may reference variables as `${name}`; they are substituted
+/// when the template is instantiated.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionTemplate {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Session name to create; defaults to the template name.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_name: Option<String>,
+    /// Starting directory for every window unless overridden.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub root: Option<String>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub env: HashMap<String, String>,
+    /// Default values for variables, overridable at launch.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub variables: HashMap<String, String>,
+    pub windows: Vec<TemplateWindow>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TemplateWindow {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub root: Option<String>,
+    /// Any tmux layout name (`tiled`, `main-vertical`, ...) or layout string.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub layout: Option<String>,
+    /// Startup command for a single-pane window.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub env: HashMap<String, String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub panes: Vec<TemplatePane>,
+}
+
+/// A pane is either just a startup command or a command plus its own root.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(untagged)]
+pub enum TemplatePane {
+    Command(String),
+    Detailed {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        command: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        root: Option<String>,
+    },
+}
+
+impl TemplatePane {
+    fn command(&self) -> Option<&str> {
+        match self {
+            TemplatePane::Command(cmd) => Some(cmd.as_str()),
+            TemplatePane::Detailed { command, .. } => command.as_deref(),
+        }
+    }
+
+    fn root(&self) -> Option<&str> {
+        match self {
+            TemplatePane::Command(_) => None,
+            TemplatePane::Detailed { root, .. } => root.as_deref(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TemplateFormat {
+    Yaml,
+    Toml,
+}
+
+impl TemplateFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            TemplateFormat::Yaml => "yaml",
+            TemplateFormat::Toml => "toml",
+        }
+    }
+
+    fn from_path(path: &std::path::Path) -> Option<Self> {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("yaml") | Some("yml") => Some(TemplateFormat::Yaml),
+            Some("toml") => Some(TemplateFormat::Toml),
+            _ => None,
+        }
+    }
+}
+
+/// Parse template source in the given format and validate it.
+pub fn parse_template(content: &str, format: TemplateFormat) -> Result<SessionTemplate> {
+    let template: SessionTemplate = match format {
+        TemplateFormat::Yaml => serde_yaml::from_str(content).context("Invalid YAML template")?,
+        TemplateFormat::Toml => toml::from_str(content).context("Invalid TOML template")?,
+    };
+    validate_template(&template)?;
+    Ok(template)
+}
+
+fn validate_template(template: &SessionTemplate) -> Result<()> {
+    validate_name(&template.name)?;
+    if template.windows.is_empty() {
+        anyhow::bail!("Template must define at least one window");
+    }
+    for window in &template.windows {
+        if window.command.is_some() && !window.panes.is_empty() {
+            anyhow::bail!("A window cannot define both `command` and `panes`");
+        }
+    }
+    Ok(())
+}
+
+/// Template names double as file names, so keep them to a safe character set.
+fn validate_name(name: &str) -> Result<()> {
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+        || name.starts_with('.')
+    {
+        anyhow::bail!(
+            "Invalid template name '{}': use letters, digits, '-', '_' or '.'",
+            name
+        );
+    }
+    Ok(())
+}
+
+/// Replace `${var}` references using `vars`. Unknown variables are an error
+/// rather than silently expanding to nothing.
+pub fn substitute(input: &str, vars: &HashMap<String, String>) -> Result<String> {
+    let mut result = String::with_capacity(input.len());
+    let mut rest = input;
+
+    while let Some(start) = rest.find("${") {
+        result.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let end = after
+            .find('}')
+            .ok_or_else(|| anyhow::anyhow!("Unterminated variable reference in '{}'", input))?;
+        let key = &after[..end];
+        let value = vars
+            .get(key)
+            .ok_or_else(|| anyhow::anyhow!("Undefined template variable '{}'", key))?;
+        result.push_str(value);
+        rest = &after[end + 1..];
+    }
+    result.push_str(rest);
+
+    Ok(result)
+}
+
+// ---------------------------------------------------------------------------
+// Storage
+// ---------------------------------------------------------------------------
+
+fn templates_dir() -> Result<PathBuf> {
+    storage::data_dir("templates")
+}
+
+/// Find the file backing a template, whichever format it was saved in.
+fn find_template_file(name: &str) -> Result<Option<(PathBuf, TemplateFormat)>> {
+    validate_name(name)?;
+    let dir = templates_dir()?;
+    for ext in ["yaml", "yml", "toml"] {
+        let path = dir.join(format!("{}.{}", name, ext));
+        if path.exists() {
+            let format = TemplateFormat::from_path(&path).unwrap_or(TemplateFormat::Yaml);
+            return Ok(Some((path, format)));
+        }
+    }
+    Ok(None)
+}
+
+pub fn list_templates() -> Result<Vec<SessionTemplate>> {
+    let dir = templates_dir()?;
+    let mut templates = Vec::new();
+
+    for entry in fs::read_dir(&dir)?.flatten() {
+        let path = entry.path();
+        let Some(format) = TemplateFormat::from_path(&path) else {
+            continue;
+        };
+        let parsed = fs::read_to_string(&path)
+            .map_err(anyhow::Error::from)
+            .and_then(|content| parse_template(&content, format));
+        match parsed {
+            Ok(template) => templates.push(template),
+            Err(e) => warn!("Skipping invalid template {}: {:#}", path.display(), e),
+        }
+    }
+
+    templates.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(templates)
+}
+
+/// Read a template's raw source so it can be edited as text.
+pub fn read_template(name: &str) -> Result<(String, TemplateFormat)> {
+    let (path, format) = find_template_file(name)?
+        .ok_or_else(|| anyhow::anyhow!("Template not found: {}", name))?;
+    let content = fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    Ok((content, format))
+}
+
+/// Validate and store template source. The file is named after the
+/// template's `name` field; when `original_name` differs (a rename), the old
+/// file is removed. Saving under a name another template already uses is
+/// refused.
+pub fn save_template(
+    content: &str,
+    format: TemplateFormat,
+    original_name: Option<&str>,
+) -> Result<SessionTemplate> {
+    let template = parse_template(content, format)?;
+
+    let existing = find_template_file(&template.name)?;
+    if existing.is_some() && original_name != Some(template.name.as_str()) {
+        anyhow::bail!("Template '{}' already exists", template.name);
+    }
+
+    let path = templates_dir()?.join(format!("{}.{}", template.name, format.extension()));
+    fs::write(&path, content).with_context(|| format!("Failed to write {}", path.display()))?;
+
+    // Only now drop the replaced files: a copy in another format, so a name
+    // maps to exactly one file, and the old file of a rename
+    if let Some((old_path, _)) = existing.filter(|(old_path, _)| *old_path != path) {
+        fs::remove_file(old_path)?;
+    }
+    if let Some(original) = original_name.filter(|n| *n != template.name) {
+        delete_template(original)?;
+    }
+
+    info!("Saved session template: {}", template.name);
+    Ok(template)
+}
+
+pub fn delete_template(name: &str) -> Result<()> {
+    let (path, _) = find_template_file(name)?
+        .ok_or_else(|| anyhow::anyhow!("Template not found: {}", name))?;
+    fs::remove_file(&path)?;
+    info!("Deleted session template: {}", name);
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------
+// Instantiation
+// ---------------------------------------------------------------------------
+
+/// Create a tmux session from a stored template. Returns the session name.
+pub async fn instantiate(
+    name: &str,
+    session_name: Option<String>,
+    overrides: HashMap<String, String>,
+) -> Result<String> {
+    let (content, format) = read_template(name)?;
+    let template = parse_template(&content, format)?;
+
+    let mut vars = template.variables.clone();
+    vars.extend(overrides);
+    vars.entry("template".to_string())
+        .or_insert_with(|| template.name.clone());
+
+    let session_name = match session_name.filter(|s| !s.trim().is_empty()) {
+        Some(s) => s,
+        None => match &template.session_name {
+            Some(s) => substitute(s, &vars)?,
+            None => template.name.clone(),
+        },
+    };
+    vars.insert("session".to_string(), session_name.clone());
+
+    if tmux::has_session(&session_name).await {
+        anyhow::bail!("Session '{}' already exists", session_name);
+    }
+
+    tmux::ensure_tmux_server().await?;
+
+    info!("Instantiating template {} as session {}", template.name, session_name);
+    if let Err(e) = build_session(&template, &session_name, &vars).await {
+        error!("Template {} failed, removing partial session: {:#}", template.name, e);
+        let _ = tmux::kill_session(&session_name).await;
+        return Err(e);
+    }
+
+    Ok(session_name)
+}
+
+async fn build_session(
+    template: &SessionTemplate,
+    session_name: &str,
+    vars: &HashMap<String, String>,
+) -> Result<()> {
+    let session_root = match &template.root {
+        Some(root) => resolve_dir(&substitute(root, vars)?, None)?,
+        None => std::env::var("HOME").unwrap_or_else(|_| "/".to_string()),
+    };
+
+    let mut session_env = Vec::new();
+    for (key, value) in &template.env {
+        session_env.push(format!("{}={}", key, substitute(value, vars)?));
+    }
+
+    let mut first_window_id: Option<String> = None;
+
+    for (i, window) in template.windows.iter().enumerate() {
+        let window_root = match &window.root {
+            Some(root) => resolve_dir(&substitute(root, vars)?, Some(&session_root))?,
+            None => session_root.clone(),
+        };
+
+        let mut env = session_env.clone();
+        for (key, value) in &window.env {
+            env.push(format!("{}={}", key, substitute(value, vars)?));
+        }
+
+        let window_name = window
+            .name
+            .as_deref()
+            .map(|n| substitute(n, vars))
+            .transpose()?;
+
+        // The first window comes with the session itself
+        let mut args: Vec<String> = if i == 0 {
+            vec!["new-session".into(), "-d".into(), "-s".into(), session_name.into()]
+        } else {
+            vec!["new-window".into(), "-d".into(), "-t".into(), format!("={}:", session_name)]
+        };
+        args.extend(["-P".into(), "-F".into(), "#{window_id}".into()]);
+        args.extend(["-c".into(), window_root.clone()]);
+        if let Some(name) = &window_name {
+            args.extend(["-n".into(), name.clone()]);
+        }
+        for var in &env {
+            args.extend(["-e".into(), var.clone()]);
+        }
+
+        let window_id = run(&args).await?.trim().to_string();
+        if first_window_id.is_none() {
+            first_window_id = Some(window_id.clone());
+        }
+
+        let panes: Vec<TemplatePane> = if window.panes.is_empty() {
+            vec![TemplatePane::Detailed {
+                command: window.command.clone(),
+                root: None,
+            }]
+        } else {
+            window.panes.clone()
+        };
+
+        for (j, pane) in panes.iter().enumerate() {
+            // Splits are detached, so the window target keeps resolving to
+            // its first pane regardless of pane-base-index
+            let pane_target = if j == 0 {
+                window_id.clone()
+            } else {
+                let pane_root = match pane.root() {
+                    Some(root) => resolve_dir(&substitute(root, vars)?, Some(&window_root))?,
+                    None => window_root.clone(),
+                };
+                let mut args: Vec<String> = vec![
+                    "split-window".into(),
+                    "-d".into(),
+                    "-t".into(),
+                    window_id.clone(),
+                    "-P".into(),
+                    "-F".into(),
+                    "#{pane_id}".into(),
+                    "-c".into(),
+                    pane_root,
+                ];
+                for var in &env {
+                    args.extend(["-e".into(), var.clone()]);
+                }
+                let pane_id = run(&args).await?.trim().to_string();
+                // Re-tile after every split so tmux never runs out of room
+                run(&["select-layout".into(), "-t".into(), window_id.clone(), "tiled".into()])
+                    .await?;
+                pane_id
+            };
+
+            if j == 0 {
+                if let Some(root) = pane.root() {
+                    // The first pane already exists, so change directory instead
+                    let dir = resolve_dir(&substitute(root, vars)?, Some(&window_root))?;
+                    send_command(&pane_target, &format!("cd '{}'", dir.replace('\'', "'\\''")))
+                        .await?;
+                }
+            }
+
+            if let Some(command) = pane.command() {
+                send_command(&pane_target, &substitute(command, vars)?).await?;
+            }
+        }
+
+        if let Some(layout) = &window.layout {
+            run(&["select-layout".into(), "-t".into(), window_id.clone(), layout.clone()]).await?;
+        }
+    }
+
+    if let Some(window_id) = first_window_id {
+        run(&["select-window".into(), "-t".into(), window_id]).await?;
+    }
+
+    Ok(())
+}
+
+async fn run(args: &[String]) -> Result<String> {
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    tmux::run_tmux(&args).await
+}
+
+async fn send_command(target: &str, command: &str) -> Result<()> {
+    tmux::run_tmux(&["send-keys", "-t", target, "-l", command]).await?;
+    tmux::run_tmux(&["send-keys", "-t", target, "Enter"]).await?;
+    Ok(())
+}
+
+/// Resolve a template directory (expanding `~` and relative paths against
+/// `base`) and make sure it exists.
+fn resolve_dir(dir: &str, base: Option<&str>) -> Result<String> {
+    let mut path = storage::expand_home(dir);
+    if path.is_relative() {
+        if let Some(base) = base {
+            path = PathBuf::from(base).join(path);
+        }
+    }
+    if !path.is_dir() {
+        anyhow::bail!("Directory does not exist: {}", path.display());
+    }
+    Ok(path.to_string_lossy().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const YAML: &str = r#"
+name: dev
+root: ~/code/${project}
+variables:
+  project: webmux
+env:
+  RUST_LOG: debug
+windows:
+  - name: editor
+    command: nvim .
+  - name: server
+    layout: main-vertical
+    panes:
+      - cargo run
+      - command: cargo test
+        root: backend
+"#;
+
+    #[test]
+    fn parses_yaml_template() {
+        let template = parse_template(YAML, TemplateFormat::Yaml).unwrap();
+        assert_eq!(template.name, "dev");
+        assert_eq!(template.windows.len(), 2);
+        assert_eq!(template.windows[0].command.as_deref(), Some("nvim ."));
+        assert_eq!(template.windows[1].panes.len(), 2);
+        assert_eq!(template.windows[1].panes[0].command(), Some("cargo run"));
+        assert_eq!(template.windows[1].panes[1].root(), Some("backend"));
+    }
+
+    #[test]
+    fn parses_toml_template() {
+        let toml = r#"
+name = "logs"
+root = "/var/log"
+
+[[windows]]
+name = "tail"
+command = "tail -f syslog"
+"#;
+        let template = parse_template(toml, TemplateFormat::Toml).unwrap();
+        assert_eq!(template.name, "logs");
+        assert_eq!(template.windows[0].command.as_deref(), Some("tail -f syslog"));
+    }
+
+    #[test]
+    fn rejects_invalid_templates() {
+        assert!(parse_template("name: ../evil\nwindows: [{}]", TemplateFormat::Yaml).is_err());
+        assert!(parse_template("name: empty\nwindows: []", TemplateFormat::Yaml).is_err());
+        assert!(parse_template(
+            "name: both\nwindows: [{command: ls, panes: [top]}]",
+            TemplateFormat::Yaml
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn substitutes_variables() {
+        let mut vars = HashMap::new();
+        vars.insert("project".to_string(), "webmux".to_string());
+        vars.insert("session".to_string(), "dev".to_string());
+
+        assert_eq!(
+            substitute("~/code/${project}/${session}", &vars).unwrap(),
+            "~/code/webmux/dev"
+        );
+        assert_eq!(substitute("no vars", &vars).unwrap(), "no vars");
+        assert!(substitute("${missing}", &vars).is_err());
+        assert!(substitute("${project", &vars).is_err());
+    }
+}
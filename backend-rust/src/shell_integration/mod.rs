@@ -0,0 +1,458 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::PathBuf;
+use tokio::sync::{mpsc, Notify, RwLock};
+use tracing::{debug, info};
+use uuid::Uuid;
+
+use crate::{
+    clipboard::find_terminator,
+    control::{PaneEvent, PaneLocation, CONTROL_HUB},
+    export::strip_ansi,
+    storage,
+    terminal_buffer::Utf8StreamDecoder,
+    types::ServerMessage,
+};
+
+const MAX_HISTORY_PER_PANE: usize = 200;
+const DEFAULT_HISTORY_LIMIT: usize = 100;
+/// Longest unterminated OSC held across reads before giving up on it.
+const MAX_PENDING: usize = 8 * 1024;
+/// Longest command line kept when it's taken from the echoed input.
+const MAX_INPUT_CHARS: usize = 4096;
+
+/// A prompt or command marker from OSC 133, OSC 633 or OSC 7.
+#[derive(Debug, Clone, PartialEq)]
+enum Mark {
+    PromptStart,
+    /// The prompt ended and the user is typing the command.
+    CommandStart,
+    /// The command was submitted and its output follows.
+    Executed,
+    Finished(Option<i32>),
+    /// The command line as the shell reports it (633;E).
+    CommandLine(String),
+    Cwd(String),
+}
+
+#[derive(Debug, PartialEq)]
+enum Segment {
+    Text(String),
+    Mark(Mark),
+}
+
+fn parse_mark(body: &str) -> Option<Mark> {
+    let (code, rest) = body.split_once(';').unwrap_or((body, ""));
+    match code {
+        "133" | "633" => {
+            let mut params = rest.split(';');
+            match params.next()? {
+                "A" => Some(Mark::PromptStart),
+                "B" => Some(Mark::CommandStart),
+                "C" => Some(Mark::Executed),
+                "D" => Some(Mark::Finished(params.next().and_then(|code| code.parse().ok()))),
+                "E" if code == "633" => Some(Mark::CommandLine(unescape_633(params.next().unwrap_or("")))),
+                "P" if code == "633" => rest
+                    .strip_prefix("P;Cwd=")
+                    .map(|cwd| Mark::Cwd(unescape_633(cwd))),
+                _ => None,
+            }
+        }
+        // file://host/path, percent-encoded
+        "7" => {
+            let path = rest.strip_prefix("file://")?;
+            let path = &path[path.find('/')?..];
+            Some(Mark::Cwd(percent_decode(path)))
+        }
+        _ => None,
+    }
+}
+
+/// Undo 633's escaping of `\` as `\\` and other bytes as `\xNN`.
+fn unescape_633(text: &str) -> String {
+    let mut out = Vec::with_capacity(text.len());
+    let bytes = text.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'\\' && bytes.get(i + 1) == Some(&b'\\') {
+            out.push(b'\\');
+            i += 2;
+        } else if bytes[i] == b'\\' && bytes.get(i + 1) == Some(&b'x') {
+            match text.get(i + 2..i + 4).and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+                Some(byte) => {
+                    out.push(byte);
+                    i += 4;
+                }
+                None => {
+                    out.push(bytes[i]);
+                    i += 1;
+                }
+            }
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+fn percent_decode(text: &str) -> String {
+    let mut out = Vec::with_capacity(text.len());
+    let bytes = text.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        let decoded = (bytes[i] == b'%')
+            .then(|| text.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match decoded {
+            Some(byte) => {
+                out.push(byte);
+                i += 3;
+            }
+            None => {
+                out.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Splits a pane's output into text and markers, including markers split
+/// across reads. Other OSC sequences are dropped.
+#[derive(Default)]
+struct MarkScanner {
+    pending: String,
+}
+
+impl MarkScanner {
+    fn scan(&mut self, chunk: &str) -> Vec<Segment> {
+        let mut data = std::mem::take(&mut self.pending);
+        data.push_str(chunk);
+
+        let mut segments = Vec::new();
+        let push_text = |segments: &mut Vec<Segment>, text: &str| {
+            if !text.is_empty() {
+                segments.push(Segment::Text(text.to_string()));
+            }
+        };
+        let mut rest = data.as_str();
+        while let Some(start) = rest.find("\x1b]") {
+            push_text(&mut segments, &rest[..start]);
+            let body = &rest[start + 2..];
+            match find_terminator(body) {
+                Some((end, terminator_len)) => {
+                    if let Some(mark) = parse_mark(&body[..end]) {
+                        segments.push(Segment::Mark(mark));
+                    }
+                    rest = &body[end + terminator_len..];
+                }
+                None => {
+                    if body.len() <= MAX_PENDING {
+                        self.pending = rest[start..].to_string();
+                    }
+                    return segments;
+                }
+            }
+        }
+
+        // Keep a trailing ESC that may start an OSC
+        match rest.strip_suffix('\x1b') {
+            Some(text) => {
+                push_text(&mut segments, text);
+                self.pending = "\x1b".to_string();
+            }
+            None => push_text(&mut segments, rest),
+        }
+        segments
+    }
+}
+
+/// A command run at a prompt, as reported by the shell's markers.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CommandBlock {
+    pub id: String,
+    pub pane_id: String,
+    pub session_name: String,
+    pub window_index: u32,
+    pub pane_index: u32,
+    pub command: String,
+    pub cwd: Option<String>,
+    pub started_at: DateTime<Utc>,
+    /// Unset while the command runs.
+    pub finished_at: Option<DateTime<Utc>>,
+    /// Unset while running, or if the shell didn't report it.
+    pub exit_code: Option<i32>,
+}
+
+/// Follows one pane's prompt and command markers.
+#[derive(Default)]
+struct PaneTracker {
+    /// Session the pane was last seen in.
+    session_name: Option<String>,
+    scanner: MarkScanner,
+    /// Typed text since the prompt ended, while no command runs.
+    input: Option<String>,
+    command_line: Option<String>,
+    cwd: Option<String>,
+    running: Option<CommandBlock>,
+}
+
+impl PaneTracker {
+    /// Feed pane output, returning commands that started or finished.
+    fn feed(&mut self, text: &str, pane_id: &str, location: &PaneLocation) -> Vec<CommandBlock> {
+        self.session_name = Some(location.session_name.clone());
+        let mut changed = Vec::new();
+        for segment in self.scanner.scan(text) {
+            match segment {
+                Segment::Text(text) => {
+                    if let Some(input) = &mut self.input {
+                        if input.len() < MAX_INPUT_CHARS {
+                            input.push_str(&text);
+                        }
+                    }
+                }
+                Segment::Mark(Mark::PromptStart) => {
+                    // A new prompt without a finish marker: the command ended
+                    // without reporting a status
+                    changed.extend(self.finish(None));
+                    self.input = None;
+                }
+                Segment::Mark(Mark::CommandStart) => self.input = Some(String::new()),
+                Segment::Mark(Mark::CommandLine(command)) => self.command_line = Some(command),
+                Segment::Mark(Mark::Cwd(cwd)) => self.cwd = Some(cwd),
+                Segment::Mark(Mark::Executed) => {
+                    changed.extend(self.finish(None));
+                    let typed = self.input.take().unwrap_or_default();
+                    let command = self.command_line.take().unwrap_or_else(|| {
+                        let typed = strip_ansi(&typed).replace('\r', "");
+                        typed.lines().rev().find(|l| !l.trim().is_empty()).unwrap_or("").trim().to_string()
+                    });
+                    let block = CommandBlock {
+                        id: Uuid::new_v4().to_string(),
+                        pane_id: pane_id.to_string(),
+                        session_name: location.session_name.clone(),
+                        window_index: location.window_index,
+                        pane_index: location.pane_index,
+                        command,
+                        cwd: self.cwd.clone(),
+                        started_at: Utc::now(),
+                        finished_at: None,
+                        exit_code: None,
+                    };
+                    changed.push(block.clone());
+                    self.running = Some(block);
+                }
+                Segment::Mark(Mark::Finished(exit_code)) => changed.extend(self.finish(exit_code)),
+            }
+        }
+        changed
+    }
+
+    fn finish(&mut self, exit_code: Option<i32>) -> Option<CommandBlock> {
+        let mut block = self.running.take()?;
+        block.finished_at = Some(Utc::now());
+        block.exit_code = exit_code;
+        Some(block)
+    }
+}
+
+/// Command history from shell integration, for sessions that opted in.
+/// Shells must emit OSC 133 (or VS Code's OSC 633) markers around prompts
+/// and commands, and OSC 7 or 633;P for the working directory. tmux doesn't
+/// pass these on to attached clients, so each pane's raw output is read
+/// through a control-mode client. Opted-in sessions are kept in
+/// `~/.webmux/shell/sessions.json`.
+pub struct ShellIntegration {
+    sessions: RwLock<Vec<String>>,
+    history: RwLock<HashMap<String, VecDeque<CommandBlock>>>,
+    sessions_changed: Notify,
+}
+
+impl ShellIntegration {
+    pub fn new() -> Self {
+        Self {
+            sessions: RwLock::new(Vec::new()),
+            history: RwLock::new(HashMap::new()),
+            sessions_changed: Notify::new(),
+        }
+    }
+
+    pub async fn initialize(&self) -> Result<()> {
+        let sessions: Vec<String> = storage::load_json(&sessions_path()?)?;
+        *self.sessions.write().await = sessions;
+        Ok(())
+    }
+
+    pub async fn sessions(&self) -> Vec<String> {
+        self.sessions.read().await.clone()
+    }
+
+    pub async fn set_enabled(&self, session_name: &str, enabled: bool) -> Result<Vec<String>> {
+        let mut sessions = self.sessions.write().await;
+        sessions.retain(|s| s != session_name);
+        if enabled {
+            sessions.push(session_name.to_string());
+        }
+        storage::save_json(&sessions_path()?, &*sessions)?;
+        info!("Shell integration for {}: {}", session_name, enabled);
+        let result = sessions.clone();
+        drop(sessions);
+        self.sessions_changed.notify_one();
+        Ok(result)
+    }
+
+    /// Recorded commands in a session, optionally narrowed to a window or
+    /// pane, newest first.
+    pub async fn history(
+        &self,
+        session_name: &str,
+        window_index: Option<u32>,
+        pane_index: Option<u32>,
+        limit: Option<usize>,
+    ) -> Vec<CommandBlock> {
+        let history = self.history.read().await;
+        let mut commands: Vec<CommandBlock> = history
+            .values()
+            .flatten()
+            .filter(|c| {
+                c.session_name == session_name
+                    && window_index.is_none_or(|w| w == c.window_index)
+                    && pane_index.is_none_or(|p| p == c.pane_index)
+            })
+            .cloned()
+            .collect();
+        commands.sort_by_key(|c| std::cmp::Reverse(c.started_at));
+        commands.truncate(limit.unwrap_or(DEFAULT_HISTORY_LIMIT));
+        commands
+    }
+
+    async fn record(&self, block: &CommandBlock) {
+        let mut history = self.history.write().await;
+        let pane = history.entry(block.pane_id.clone()).or_default();
+        match pane.iter_mut().rev().find(|c| c.id == block.id) {
+            Some(existing) => *existing = block.clone(),
+            None => {
+                pane.push_back(block.clone());
+                if pane.len() > MAX_HISTORY_PER_PANE {
+                    pane.pop_front();
+                }
+            }
+        }
+    }
+
+    /// Track commands in opted-in sessions and broadcast each as it starts
+    /// and finishes.
+    pub async fn run(&self, broadcast_tx: mpsc::UnboundedSender<ServerMessage>) {
+        let mut subscription = CONTROL_HUB.subscribe(HashSet::new()).await;
+        let mut trackers: HashMap<String, (Utf8StreamDecoder, PaneTracker)> = HashMap::new();
+        let mut reload = true;
+
+        loop {
+            if reload {
+                let wanted: HashSet<String> = self.sessions().await.into_iter().collect();
+                trackers.retain(|_, (_, tracker)| {
+                    tracker.session_name.as_ref().is_none_or(|s| wanted.contains(s))
+                });
+                subscription.set_sessions(wanted).await;
+                reload = false;
+            }
+
+            let event = tokio::select! {
+                _ = self.sessions_changed.notified() => {
+                    reload = true;
+                    continue;
+                }
+                Some(event) = subscription.events.recv() => event,
+            };
+
+            match event {
+                PaneEvent::Output { pane_id, location, data } => {
+                    let (decoder, tracker) = trackers
+                        .entry(pane_id.clone())
+                        .or_insert_with(|| (Utf8StreamDecoder::new(), PaneTracker::default()));
+                    let (text, _) = decoder.decode_chunk(&data);
+                    for command in tracker.feed(&text, &pane_id, &location) {
+                        self.record(&command).await;
+                        let _ = broadcast_tx.send(ServerMessage::ShellCommand { command });
+                    }
+                }
+                PaneEvent::PaneClosed(pane_id) => {
+                    trackers.remove(&pane_id);
+                }
+                PaneEvent::SessionClosed(session_name) => {
+                    debug!("Stopped tracking commands in {}", session_name);
+                }
+            }
+        }
+    }
+}
+
+fn sessions_path() -> Result<PathBuf> {
+    Ok(storage::data_dir("shell")?.join("sessions.json"))
+}
+
+lazy_static::lazy_static! {
+    pub static ref SHELL_INTEGRATION: ShellIntegration = ShellIntegration::new();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn location() -> PaneLocation {
+        PaneLocation {
+            session_name: "work".into(),
+            window_index: 1,
+            pane_index: 0,
+        }
+    }
+
+    #[test]
+    fn scans_markers_split_across_reads() {
+        let mut scanner = MarkScanner::default();
+        let mut segments = scanner.scan("out\x1b]133;D;2\x07\x1b]7;file://host/home/a%20b\x1b\\\x1b]13");
+        segments.extend(scanner.scan("3;A\x07$ \x1b]0;title\x07"));
+        assert_eq!(
+            segments,
+            vec![
+                Segment::Text("out".into()),
+                Segment::Mark(Mark::Finished(Some(2))),
+                Segment::Mark(Mark::Cwd("/home/a b".into())),
+                Segment::Mark(Mark::PromptStart),
+                Segment::Text("$ ".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn records_commands_from_typed_input() {
+        let mut tracker = PaneTracker::default();
+        let feed = |tracker: &mut PaneTracker, text: &str| tracker.feed(text, "%3", &location());
+
+        assert!(feed(&mut tracker, "\x1b]7;file://h/srv\x07\x1b]133;A\x07$ \x1b]133;B\x07").is_empty());
+        let started = feed(&mut tracker, "make \x1b[1mtest\x1b[0m\r\n\x1b]133;C\x07building\r\n");
+        assert_eq!(started.len(), 1);
+        assert_eq!(started[0].command, "make test");
+        assert_eq!(started[0].cwd.as_deref(), Some("/srv"));
+        assert!(started[0].finished_at.is_none());
+
+        let finished = feed(&mut tracker, "\x1b]133;D;1\x07\x1b]133;A\x07$ ");
+        assert_eq!(finished.len(), 1);
+        assert_eq!(finished[0].id, started[0].id);
+        assert_eq!(finished[0].exit_code, Some(1));
+        assert_eq!(finished[0].window_index, 1);
+    }
+
+    #[test]
+    fn prefers_the_reported_command_line() {
+        let mut tracker = PaneTracker::default();
+        let text = "\x1b]633;B\x07ls\x1b]633;E;echo a\\x3bb \\\\n\x07\x1b]633;C\x07";
+        let started = tracker.feed(text, "%3", &location());
+        assert_eq!(started[0].command, "echo a;b \\n");
+    }
+}
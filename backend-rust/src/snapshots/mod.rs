@@ -0,0 +1,840 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::sync::RwLock;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::types::PaneTarget;
+use crate::{procinfo, storage, tmux};
+
+/// A point-in-time record of the tmux server layout, persisted under
+/// `~/.webmux/snapshots/<id>.json`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Snapshot {
+    pub id: String,
+    pub created_at: DateTime<Utc>,
+    pub label: Option<String>,
+    pub trigger: SnapshotTrigger,
+    pub sessions: Vec<SessionSnapshot>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SnapshotTrigger {
+    Manual,
+    Scheduled,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionSnapshot {
+    pub name: String,
+    pub windows: Vec<WindowSnapshot>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WindowSnapshot {
+    pub index: u32,
+    pub name: String,
+    pub layout: String,
+    pub active: bool,
+    pub panes: Vec<PaneSnapshot>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PaneSnapshot {
+    pub index: u32,
+    pub active: bool,
+    pub cwd: String,
+    /// Name of the foreground program as reported by tmux.
+    pub current_command: String,
+    /// Full command line of the foreground program, when it isn't the shell.
+    pub command_line: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scrollback: Option<String>,
+}
+
+/// Lightweight listing entry so clients don't receive full scrollback.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SnapshotSummary {
+    pub id: String,
+    pub created_at: DateTime<Utc>,
+    pub label: Option<String>,
+    pub trigger: SnapshotTrigger,
+    pub sessions: Vec<String>,
+    pub window_count: usize,
+    pub pane_count: usize,
+    pub has_scrollback: bool,
+}
+
+impl From<&Snapshot> for SnapshotSummary {
+    fn from(snapshot: &Snapshot) -> Self {
+        let windows = snapshot.sessions.iter().flat_map(|s| &s.windows);
+        Self {
+            id: snapshot.id.clone(),
+            created_at: snapshot.created_at,
+            label: snapshot.label.clone(),
+            trigger: snapshot.trigger,
+            sessions: snapshot.sessions.iter().map(|s| s.name.clone()).collect(),
+            window_count: windows.clone().count(),
+            pane_count: windows.clone().map(|w| w.panes.len()).sum(),
+            has_scrollback: windows
+                .flat_map(|w| &w.panes)
+                .any(|p| p.scrollback.is_some()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SnapshotSettings {
+    /// Take a scheduled snapshot every N minutes; `None` disables scheduling.
+    pub interval_minutes: Option<u32>,
+    pub include_scrollback: bool,
+    /// Number of scheduled snapshots to keep; manual ones are never pruned.
+    pub keep: usize,
+}
+
+impl Default for SnapshotSettings {
+    fn default() -> Self {
+        Self {
+            interval_minutes: None,
+            include_scrollback: false,
+            keep: 20,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RestoreOptions {
+    /// Restrict the restore to these sessions; all sessions when absent.
+    pub sessions: Option<Vec<String>>,
+    /// Re-run each pane's foreground command.
+    #[serde(default)]
+    pub restore_commands: bool,
+    /// Replay saved scrollback into the restored panes.
+    #[serde(default = "default_true")]
+    pub restore_scrollback: bool,
+}
+
+impl Default for RestoreOptions {
+    fn default() -> Self {
+        Self {
+            sessions: None,
+            restore_commands: false,
+            restore_scrollback: true,
+        }
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RestoreResult {
+    pub restored: Vec<String>,
+    /// Sessions left alone because a session with that name already exists.
+    pub skipped: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SnapshotDiff {
+    pub added_sessions: Vec<String>,
+    pub removed_sessions: Vec<String>,
+    pub changed_sessions: Vec<SessionDiff>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionDiff {
+    pub name: String,
+    pub added_windows: Vec<String>,
+    pub removed_windows: Vec<String>,
+    pub changed_windows: Vec<WindowDiff>,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct WindowDiff {
+    pub index: u32,
+    pub name: String,
+    pub changes: Vec<String>,
+}
+
+pub struct SnapshotManager {
+    settings: RwLock<SnapshotSettings>,
+}
+
+impl SnapshotManager {
+    pub fn new() -> Self {
+        Self {
+            settings: RwLock::new(SnapshotSettings::default()),
+        }
+    }
+
+    pub async fn initialize(&self) -> Result<()> {
+        let settings: SnapshotSettings = storage::load_json(&settings_path()?)?;
+        *self.settings.write().await = settings;
+        Ok(())
+    }
+
+    pub async fn get_settings(&self) -> SnapshotSettings {
+        self.settings.read().await.clone()
+    }
+
+    pub async fn update_settings(&self, settings: SnapshotSettings) -> Result<SnapshotSettings> {
+        if settings.interval_minutes == Some(0) {
+            anyhow::bail!("Snapshot interval must be at least one minute");
+        }
+        if settings.keep == 0 {
+            anyhow::bail!("Must keep at least one scheduled snapshot");
+        }
+        storage::save_json(&settings_path()?, &settings)?;
+        *self.settings.write().await = settings.clone();
+        info!("Updated snapshot settings: {:?}", settings);
+        Ok(settings)
+    }
+
+    /// Periodically take scheduled snapshots according to the current settings.
+    pub async fn run_scheduler(&self) {
+        let mut last_run = latest_scheduled_snapshot().unwrap_or(None);
+        let mut ticker = tokio::time::interval(Duration::from_secs(60));
+
+        loop {
+            ticker.tick().await;
+
+            let settings = self.get_settings().await;
+            let Some(interval) = settings.interval_minutes else {
+                continue;
+            };
+            let due = last_run.is_none_or(|t| {
+                Utc::now() - t >= chrono::Duration::minutes(interval as i64)
+            });
+            if !due {
+                continue;
+            }
+
+            match take_snapshot(None, SnapshotTrigger::Scheduled, settings.include_scrollback).await {
+                Ok(snapshot) => {
+                    last_run = Some(snapshot.created_at);
+                    if let Err(e) = prune_scheduled(settings.keep) {
+                        warn!("Failed to prune old snapshots: {}", e);
+                    }
+                }
+                Err(e) => error!("Scheduled snapshot failed: {:#}", e),
+            }
+        }
+    }
+}
+
+lazy_static::lazy_static! {
+    pub static ref SNAPSHOT_MANAGER: SnapshotManager = SnapshotManager::new();
+}
+
+// ---------------------------------------------------------------------------
+// Capture
+// ---------------------------------------------------------------------------
+
+/// Record every session, window and pane and persist the result.
+pub async fn take_snapshot(
+    label: Option<String>,
+    trigger: SnapshotTrigger,
+    include_scrollback: bool,
+) -> Result<Snapshot> {
+    let mut sessions = capture_layout().await?;
+
+    if include_scrollback {
+        for session in &mut sessions {
+            for window in &mut session.windows {
+                for pane in &mut window.panes {
+                    let target = PaneTarget {
+                        session_name: session.name.clone(),
+                        window_index: Some(window.index),
+                        pane_index: Some(pane.index),
+                    };
+                    match tmux::capture_pane(&target, true).await {
+                        Ok(content) => pane.scrollback = Some(format!("{}\n", content)),
+                        Err(e) => warn!("Failed to capture scrollback for {}: {}", target.tmux_target(), e),
+                    }
+                }
+            }
+        }
+    }
+
+    let snapshot = Snapshot {
+        id: Uuid::new_v4().to_string(),
+        created_at: Utc::now(),
+        label,
+        trigger,
+        sessions,
+    };
+    storage::save_json(&snapshot_path(&snapshot.id)?, &snapshot)?;
+
+    info!(
+        "Saved {:?} snapshot {} with {} sessions",
+        trigger,
+        snapshot.id,
+        snapshot.sessions.len()
+    );
+    Ok(snapshot)
+}
+
+/// Read the current layout of all sessions without persisting anything.
+async fn capture_layout() -> Result<Vec<SessionSnapshot>> {
+    if tmux::list_sessions().await?.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let format = [
+        "#{session_name}",
+        "#{window_index}",
+        "#{window_name}",
+        "#{window_layout}",
+        "#{window_active}",
+        "#{pane_index}",
+        "#{pane_active}",
+        "#{pane_current_path}",
+        "#{pane_current_command}",
+        "#{pane_pid}",
+        "#{pane_start_command}",
+    ]
+    .join("\t");
+    let output = tmux::run_tmux(&["list-panes", "-a", "-F", &format]).await?;
+
+    // Reading /proc blocks, so look up every pane's command line at once
+    // off the async runtime
+    let pane_pids: Vec<u32> = output
+        .lines()
+        .filter_map(|line| line.split('\t').nth(9)?.parse().ok())
+        .collect();
+    let command_lines: HashMap<u32, String> = tokio::task::spawn_blocking(move || {
+        pane_pids
+            .into_iter()
+            .filter_map(|pid| Some((pid, foreground_command_line(pid)?)))
+            .collect()
+    })
+    .await?;
+
+    let mut sessions: Vec<SessionSnapshot> = Vec::new();
+    for line in output.lines() {
+        let parts: Vec<&str> = line.split('\t').collect();
+        if parts.len() < 11 {
+            continue;
+        }
+
+        if sessions.last().map(|s| s.name.as_str()) != Some(parts[0]) {
+            sessions.push(SessionSnapshot {
+                name: parts[0].to_string(),
+                windows: Vec::new(),
+            });
+        }
+        let Some(session) = sessions.last_mut() else {
+            continue;
+        };
+
+        let window_index: u32 = parts[1].parse().unwrap_or(0);
+        if session.windows.last().map(|w| w.index) != Some(window_index) {
+            session.windows.push(WindowSnapshot {
+                index: window_index,
+                name: parts[2].to_string(),
+                layout: parts[3].to_string(),
+                active: parts[4] == "1",
+                panes: Vec::new(),
+            });
+        }
+        let Some(window) = session.windows.last_mut() else {
+            continue;
+        };
+
+        let pane_pid: u32 = parts[9].parse().unwrap_or(0);
+        window.panes.push(PaneSnapshot {
+            index: parts[5].parse().unwrap_or(0),
+            active: parts[6] == "1",
+            cwd: parts[7].to_string(),
+            current_command: parts[8].to_string(),
+            // A pane started directly with a command has no shell above it,
+            // so fall back to the command tmux launched it with
+            command_line: command_lines
+                .get(&pane_pid)
+                .cloned()
+                .or_else(|| unquote_start_command(parts[10])),
+            scrollback: None,
+        });
+    }
+
+    Ok(sessions)
+}
+
+/// Full command line of the pane's foreground process group leader, or
+/// `None` when the shell itself is in the foreground.
+fn foreground_command_line(pane_pid: u32) -> Option<String> {
+    let tpgid = u32::try_from(procinfo::read_stat(pane_pid)?.tpgid).ok()?;
+    if tpgid == 0 || tpgid == pane_pid {
+        return None;
+    }
+
+    let args: Vec<String> = procinfo::process_cmdline(tpgid)
+        .iter()
+        .map(|arg| shell_quote(arg))
+        .collect();
+    if args.is_empty() {
+        None
+    } else {
+        Some(args.join(" "))
+    }
+}
+
+/// tmux reports `pane_start_command` wrapped in double quotes; strip them.
+fn unquote_start_command(command: &str) -> Option<String> {
+    let command = match command.strip_prefix('"').and_then(|c| c.strip_suffix('"')) {
+        Some(inner) => inner.replace("\\\"", "\""),
+        None => command.to_string(),
+    };
+    Some(command).filter(|c| !c.is_empty())
+}
+
+fn shell_quote(arg: &str) -> String {
+    if !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c))
+    {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Storage
+// ---------------------------------------------------------------------------
+
+fn snapshots_dir() -> Result<PathBuf> {
+    storage::data_dir("snapshots")
+}
+
+fn settings_path() -> Result<PathBuf> {
+    Ok(snapshots_dir()?.join("settings.json"))
+}
+
+fn snapshot_path(id: &str) -> Result<PathBuf> {
+    if id.is_empty() || !id.chars().all(|c| c.is_ascii_hexdigit() || c == '-') {
+        anyhow::bail!("Invalid snapshot id: {}", id);
+    }
+    Ok(snapshots_dir()?.join(format!("{}.json", id)))
+}
+
+pub fn load_snapshot(id: &str) -> Result<Snapshot> {
+    let path = snapshot_path(id)?;
+    let content = fs::read_to_string(&path)
+        .with_context(|| format!("Snapshot not found: {}", id))?;
+    Ok(serde_json::from_str(&content)?)
+}
+
+fn load_all() -> Result<Vec<Snapshot>> {
+    let mut snapshots = Vec::new();
+    for entry in fs::read_dir(snapshots_dir()?)?.flatten() {
+        let path = entry.path();
+        let is_snapshot = path.extension().and_then(|e| e.to_str()) == Some("json")
+            && path.file_name() != Some(std::ffi::OsStr::new("settings.json"));
+        if !is_snapshot {
+            continue;
+        }
+        match fs::read_to_string(&path)
+            .map_err(anyhow::Error::from)
+            .and_then(|c| serde_json::from_str::<Snapshot>(&c).map_err(Into::into))
+        {
+            Ok(snapshot) => snapshots.push(snapshot),
+            Err(e) => warn!("Skipping unreadable snapshot {}: {}", path.display(), e),
+        }
+    }
+    snapshots.sort_by_key(|s| std::cmp::Reverse(s.created_at));
+    Ok(snapshots)
+}
+
+/// List snapshots, newest first.
+pub fn list_snapshots() -> Result<Vec<SnapshotSummary>> {
+    Ok(load_all()?.iter().map(SnapshotSummary::from).collect())
+}
+
+pub fn delete_snapshot(id: &str) -> Result<()> {
+    let path = snapshot_path(id)?;
+    fs::remove_file(&path).with_context(|| format!("Snapshot not found: {}", id))?;
+    info!("Deleted snapshot {}", id);
+    Ok(())
+}
+
+fn latest_scheduled_snapshot() -> Result<Option<DateTime<Utc>>> {
+    Ok(load_all()?
+        .iter()
+        .filter(|s| s.trigger == SnapshotTrigger::Scheduled)
+        .map(|s| s.created_at)
+        .max())
+}
+
+fn prune_scheduled(keep: usize) -> Result<()> {
+    let scheduled: Vec<Snapshot> = load_all()?
+        .into_iter()
+        .filter(|s| s.trigger == SnapshotTrigger::Scheduled)
+        .collect();
+    for snapshot in scheduled.iter().skip(keep) {
+        delete_snapshot(&snapshot.id)?;
+    }
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------
+// Diff
+// ---------------------------------------------------------------------------
+
+/// Compare a stored snapshot against another one, or against the live tmux
+/// state when `to` is `None`.
+pub async fn diff_snapshots(from: &str, to: Option<&str>) -> Result<SnapshotDiff> {
+    let from = load_snapshot(from)?;
+    let to_sessions = match to {
+        Some(id) => load_snapshot(id)?.sessions,
+        None => capture_layout().await?,
+    };
+    Ok(diff_sessions(&from.sessions, &to_sessions))
+}
+
+fn diff_sessions(from: &[SessionSnapshot], to: &[SessionSnapshot]) -> SnapshotDiff {
+    let from_map: BTreeMap<&str, &SessionSnapshot> =
+        from.iter().map(|s| (s.name.as_str(), s)).collect();
+    let to_map: BTreeMap<&str, &SessionSnapshot> =
+        to.iter().map(|s| (s.name.as_str(), s)).collect();
+
+    let mut diff = SnapshotDiff::default();
+    for (name, old) in &from_map {
+        match to_map.get(name) {
+            None => diff.removed_sessions.push(name.to_string()),
+            Some(new) => {
+                let session_diff = diff_windows(name, &old.windows, &new.windows);
+                if !session_diff.added_windows.is_empty()
+                    || !session_diff.removed_windows.is_empty()
+                    || !session_diff.changed_windows.is_empty()
+                {
+                    diff.changed_sessions.push(session_diff);
+                }
+            }
+        }
+    }
+    for name in to_map.keys() {
+        if !from_map.contains_key(name) {
+            diff.added_sessions.push(name.to_string());
+        }
+    }
+    diff
+}
+
+fn diff_windows(session: &str, from: &[WindowSnapshot], to: &[WindowSnapshot]) -> SessionDiff {
+    let from_map: BTreeMap<u32, &WindowSnapshot> = from.iter().map(|w| (w.index, w)).collect();
+    let to_map: BTreeMap<u32, &WindowSnapshot> = to.iter().map(|w| (w.index, w)).collect();
+
+    let mut diff = SessionDiff {
+        name: session.to_string(),
+        ..Default::default()
+    };
+
+    for (index, old) in &from_map {
+        let Some(new) = to_map.get(index) else {
+            diff.removed_windows.push(format!("{}:{}", index, old.name));
+            continue;
+        };
+
+        let mut changes = Vec::new();
+        if old.name != new.name {
+            changes.push(format!("renamed from '{}' to '{}'", old.name, new.name));
+        }
+        if old.panes.len() != new.panes.len() {
+            changes.push(format!("pane count {} -> {}", old.panes.len(), new.panes.len()));
+        } else if old.layout != new.layout {
+            changes.push("layout changed".to_string());
+        }
+        let new_panes: HashMap<u32, &PaneSnapshot> =
+            new.panes.iter().map(|p| (p.index, p)).collect();
+        for pane in &old.panes {
+            let Some(new_pane) = new_panes.get(&pane.index) else {
+                continue;
+            };
+            if pane.cwd != new_pane.cwd {
+                changes.push(format!("pane {} cwd {} -> {}", pane.index, pane.cwd, new_pane.cwd));
+            }
+            if pane.current_command != new_pane.current_command {
+                changes.push(format!(
+                    "pane {} command {} -> {}",
+                    pane.index, pane.current_command, new_pane.current_command
+                ));
+            }
+        }
+
+        if !changes.is_empty() {
+            diff.changed_windows.push(WindowDiff {
+                index: *index,
+                name: new.name.clone(),
+                changes,
+            });
+        }
+    }
+    for (index, new) in &to_map {
+        if !from_map.contains_key(index) {
+            diff.added_windows.push(format!("{}:{}", index, new.name));
+        }
+    }
+    diff
+}
+
+// ---------------------------------------------------------------------------
+// Restore
+// ---------------------------------------------------------------------------
+
+/// Rebuild the sessions recorded in a snapshot. Sessions that already exist
+/// are skipped rather than merged.
+pub async fn restore_snapshot(id: &str, options: RestoreOptions) -> Result<RestoreResult> {
+    let snapshot = load_snapshot(id)?;
+    tmux::ensure_tmux_server().await?;
+
+    let mut result = RestoreResult::default();
+    for session in &snapshot.sessions {
+        if let Some(only) = &options.sessions {
+            if !only.contains(&session.name) {
+                continue;
+            }
+        }
+        if tmux::has_session(&session.name).await {
+            result.skipped.push(session.name.clone());
+            continue;
+        }
+
+        info!("Restoring session {} from snapshot {}", session.name, id);
+        if let Err(e) = restore_session(session, &options).await {
+            error!("Failed to restore session {}: {:#}", session.name, e);
+            let _ = tmux::kill_session(&session.name).await;
+            return Err(e.context(format!("Failed to restore session {}", session.name)));
+        }
+        result.restored.push(session.name.clone());
+    }
+
+    Ok(result)
+}
+
+async fn restore_session(session: &SessionSnapshot, options: &RestoreOptions) -> Result<()> {
+    let scrollback_dir = storage::data_dir("snapshots/restore")?;
+    let mut active_window = None;
+
+    for (i, window) in session.windows.iter().enumerate() {
+        let mut window_id = String::new();
+        let mut active_pane = None;
+
+        for (j, pane) in window.panes.iter().enumerate() {
+            let cwd = if std::path::Path::new(&pane.cwd).is_dir() {
+                pane.cwd.clone()
+            } else {
+                std::env::var("HOME").unwrap_or_else(|_| "/".to_string())
+            };
+
+            let mut args: Vec<String> = match (i, j) {
+                (0, 0) => vec![
+                    "new-session".into(), "-d".into(), "-s".into(), session.name.clone(),
+                    "-n".into(), window.name.clone(),
+                ],
+                (_, 0) => vec![
+                    "new-window".into(), "-d".into(), "-t".into(),
+                    format!("={}:{}", session.name, window.index),
+                    "-n".into(), window.name.clone(),
+                ],
+                _ => vec!["split-window".into(), "-d".into(), "-t".into(), window_id.clone()],
+            };
+            args.extend(["-c".into(), cwd, "-P".into(), "-F".into(), "#{window_id} #{pane_id} #{window_index}".into()]);
+
+            // Replay saved scrollback by starting the pane with `cat` before
+            // handing over to the user's shell
+            if let Some(content) = pane.scrollback.as_ref().filter(|_| options.restore_scrollback) {
+                let file = scrollback_dir.join(format!("{}.txt", Uuid::new_v4()));
+                fs::write(&file, content)?;
+                args.extend([
+                    "sh".into(),
+                    "-c".into(),
+                    "cat \"$0\"; rm -f \"$0\"; exec \"${SHELL:-sh}\"".into(),
+                    file.to_string_lossy().to_string(),
+                ]);
+            }
+
+            let output = run(&args).await?;
+            let mut ids = output.split_whitespace();
+            window_id = ids.next().unwrap_or_default().to_string();
+            let pane_id = ids.next().unwrap_or_default().to_string();
+
+            let created_index = ids.next().and_then(|index| index.parse::<u32>().ok());
+            if i == 0 && j == 0 && created_index != Some(window.index) {
+                // new-session puts the first window at base-index
+                run(&[
+                    "move-window".into(), "-s".into(), window_id.clone(),
+                    "-t".into(), format!("={}:{}", session.name, window.index),
+                ]).await?;
+            }
+
+            if j > 0 {
+                // Keep splitting possible regardless of how many panes there are
+                run(&["select-layout".into(), "-t".into(), window_id.clone(), "tiled".into()]).await?;
+            }
+
+            if options.restore_commands {
+                if let Some(command) = &pane.command_line {
+                    tmux::run_tmux(&["send-keys", "-t", &pane_id, "-l", command]).await?;
+                    tmux::run_tmux(&["send-keys", "-t", &pane_id, "Enter"]).await?;
+                }
+            }
+
+            if pane.active {
+                active_pane = Some(pane_id);
+            }
+        }
+
+        if let Err(e) = tmux::run_tmux(&["select-layout", "-t", &window_id, &window.layout]).await {
+            warn!("Could not apply saved layout to {}: {}", window_id, e);
+        }
+        if let Some(pane_id) = active_pane {
+            tmux::run_tmux(&["select-pane", "-t", &pane_id]).await?;
+        }
+        if window.active {
+            active_window = Some(window_id);
+        }
+    }
+
+    if let Some(window_id) = active_window {
+        tmux::run_tmux(&["select-window", "-t", &window_id]).await?;
+    }
+    Ok(())
+}
+
+async fn run(args: &[String]) -> Result<String> {
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    tmux::run_tmux(&args).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pane(index: u32, cwd: &str, command: &str) -> PaneSnapshot {
+        PaneSnapshot {
+            index,
+            active: index == 0,
+            cwd: cwd.to_string(),
+            current_command: command.to_string(),
+            command_line: None,
+            scrollback: None,
+        }
+    }
+
+    fn window(index: u32, name: &str, panes: Vec<PaneSnapshot>) -> WindowSnapshot {
+        WindowSnapshot {
+            index,
+            name: name.to_string(),
+            layout: format!("layout-{}", panes.len()),
+            active: index == 0,
+            panes,
+        }
+    }
+
+    #[test]
+    fn diff_detects_session_and_window_changes() {
+        let from = vec![
+            SessionSnapshot {
+                name: "dev".into(),
+                windows: vec![
+                    window(0, "editor", vec![pane(0, "/src", "nvim")]),
+                    window(1, "logs", vec![pane(0, "/var/log", "tail")]),
+                ],
+            },
+            SessionSnapshot {
+                name: "old".into(),
+                windows: vec![window(0, "sh", vec![pane(0, "/", "bash")])],
+            },
+        ];
+        let to = vec![
+            SessionSnapshot {
+                name: "dev".into(),
+                windows: vec![
+                    window(0, "code", vec![pane(0, "/src/app", "nvim"), pane(1, "/src", "bash")]),
+                    window(2, "server", vec![pane(0, "/src", "cargo")]),
+                ],
+            },
+            SessionSnapshot {
+                name: "new".into(),
+                windows: vec![window(0, "sh", vec![pane(0, "/", "bash")])],
+            },
+        ];
+
+        let diff = diff_sessions(&from, &to);
+        assert_eq!(diff.added_sessions, vec!["new"]);
+        assert_eq!(diff.removed_sessions, vec!["old"]);
+        assert_eq!(diff.changed_sessions.len(), 1);
+
+        let dev = &diff.changed_sessions[0];
+        assert_eq!(dev.added_windows, vec!["2:server"]);
+        assert_eq!(dev.removed_windows, vec!["1:logs"]);
+        assert_eq!(dev.changed_windows.len(), 1);
+        let changes = &dev.changed_windows[0].changes;
+        assert!(changes.iter().any(|c| c.contains("renamed")));
+        assert!(changes.iter().any(|c| c.contains("pane count 1 -> 2")));
+        assert!(changes.iter().any(|c| c.contains("cwd /src -> /src/app")));
+    }
+
+    #[test]
+    fn identical_snapshots_have_empty_diff() {
+        let sessions = vec![SessionSnapshot {
+            name: "dev".into(),
+            windows: vec![window(0, "editor", vec![pane(0, "/src", "nvim")])],
+        }];
+        assert_eq!(diff_sessions(&sessions, &sessions), SnapshotDiff::default());
+    }
+
+    #[test]
+    fn unquotes_start_command() {
+        assert_eq!(unquote_start_command("\"sleep 300\"").as_deref(), Some("sleep 300"));
+        assert_eq!(unquote_start_command("top").as_deref(), Some("top"));
+        assert_eq!(unquote_start_command("\"echo \\\"hi\\\"\"").as_deref(), Some("echo \"hi\""));
+        assert_eq!(unquote_start_command(""), None);
+    }
+
+    #[test]
+    fn quotes_shell_arguments() {
+        assert_eq!(shell_quote("tail"), "tail");
+        assert_eq!(shell_quote("/var/log/syslog"), "/var/log/syslog");
+        assert_eq!(shell_quote("two words"), "'two words'");
+        assert_eq!(shell_quote("it's"), "'it'\\''s'");
+        assert_eq!(shell_quote(""), "''");
+    }
+
+    #[test]
+    fn restore_replays_scrollback_unless_disabled() {
+        use crate::types::WebSocketMessage;
+
+        let options = |json: &str| match serde_json::from_str(json).unwrap() {
+            WebSocketMessage::RestoreSnapshot { options, .. } => options,
+            _ => panic!("not a restore message"),
+        };
+        let omitted = options(r#"{"type":"restore-snapshot","id":"a"}"#);
+        let empty = options(r#"{"type":"restore-snapshot","id":"a","options":{}}"#);
+        let disabled =
+            options(r#"{"type":"restore-snapshot","id":"a","options":{"restoreScrollback":false}}"#);
+        assert!(omitted.restore_scrollback && !omitted.restore_commands);
+        assert!(empty.restore_scrollback && !empty.restore_commands);
+        assert!(!disabled.restore_scrollback);
+    }
+}
@@ -0,0 +1,213 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing::{debug, warn};
+
+use crate::tmux;
+
+const DEFAULT_INTERVAL_MS: u64 = 2000;
+/// Floor on the refresh interval, whatever the client asks for.
+const MIN_INTERVAL_MS: u64 = 500;
+const DEFAULT_COLS: u16 = 60;
+const DEFAULT_ROWS: u16 = 12;
+const MAX_COLS: u16 = 200;
+const MAX_ROWS: u16 = 60;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ThumbnailOptions {
+    /// Only these sessions; every session when absent.
+    pub sessions: Option<Vec<String>>,
+    pub interval_ms: Option<u64>,
+    pub cols: Option<u16>,
+    pub rows: Option<u16>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WindowKey {
+    pub session_name: String,
+    pub window_index: u32,
+}
+
+/// A text preview of a window's active pane.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Thumbnail {
+    #[serde(flatten)]
+    pub key: WindowKey,
+    pub window_name: String,
+    pub pane_width: u16,
+    pub pane_height: u16,
+    pub lines: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ThumbnailUpdate {
+    /// New or changed previews.
+    pub thumbnails: Vec<Thumbnail>,
+    /// Windows that no longer exist.
+    pub removed: Vec<WindowKey>,
+}
+
+struct WindowInfo {
+    key: WindowKey,
+    window_id: String,
+    name: String,
+    activity: i64,
+    pane_width: u16,
+    pane_height: u16,
+}
+
+async fn list_windows() -> Result<Vec<WindowInfo>> {
+    let format = [
+        "#{session_name}",
+        "#{window_index}",
+        "#{window_id}",
+        "#{window_activity}",
+        "#{pane_width}",
+        "#{pane_height}",
+        "#{window_name}",
+    ]
+    .join("\t");
+    let output = tmux::run_tmux(&["list-windows", "-a", "-F", &format]).await?;
+
+    Ok(output
+        .lines()
+        .filter_map(|line| {
+            let parts: Vec<&str> = line.splitn(7, '\t').collect();
+            if parts.len() < 7 {
+                return None;
+            }
+            Some(WindowInfo {
+                key: WindowKey {
+                    session_name: parts[0].to_string(),
+                    window_index: parts[1].parse().ok()?,
+                },
+                window_id: parts[2].to_string(),
+                activity: parts[3].parse().unwrap_or(0),
+                pane_width: parts[4].parse().unwrap_or(0),
+                pane_height: parts[5].parse().unwrap_or(0),
+                name: parts[6].to_string(),
+            })
+        })
+        .collect())
+}
+
+/// Shrink captured pane text to at most `rows` lines of `cols` characters,
+/// keeping the bottom of the screen where the latest output is.
+pub fn downscale(content: &str, cols: u16, rows: u16) -> Vec<String> {
+    let lines: Vec<&str> = content.trim_end().lines().collect();
+    let skip = lines.len().saturating_sub(rows as usize);
+    lines[skip..]
+        .iter()
+        .map(|line| line.chars().take(cols as usize).collect::<String>().trim_end().to_string())
+        .collect()
+}
+
+/// Push thumbnail updates until the receiver goes away. Windows are only
+/// recaptured when tmux reports activity since their last capture, so idle
+/// sessions cost one `list-windows` per tick.
+pub async fn run(options: ThumbnailOptions, updates: mpsc::UnboundedSender<ThumbnailUpdate>) {
+    let interval = options
+        .interval_ms
+        .unwrap_or(DEFAULT_INTERVAL_MS)
+        .max(MIN_INTERVAL_MS);
+    let cols = options.cols.unwrap_or(DEFAULT_COLS).clamp(1, MAX_COLS);
+    let rows = options.rows.unwrap_or(DEFAULT_ROWS).clamp(1, MAX_ROWS);
+
+    // Window id -> (key, name, time of the capture we last sent)
+    let mut captured: HashMap<String, (WindowKey, String, i64)> = HashMap::new();
+    let mut ticker = tokio::time::interval(Duration::from_millis(interval));
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+    loop {
+        ticker.tick().await;
+        // The subscriber dropped its receiver; stop before polling tmux
+        if updates.is_closed() {
+            break;
+        }
+        let windows = match list_windows().await {
+            Ok(windows) => windows,
+            Err(e) => {
+                debug!("Failed to list windows for thumbnails: {}", e);
+                Vec::new()
+            }
+        };
+        let windows: Vec<WindowInfo> = windows
+            .into_iter()
+            .filter(|w| {
+                options
+                    .sessions
+                    .as_ref()
+                    .is_none_or(|sessions| sessions.contains(&w.key.session_name))
+            })
+            .collect();
+
+        let mut update = ThumbnailUpdate {
+            thumbnails: Vec::new(),
+            removed: Vec::new(),
+        };
+
+        let live: Vec<&str> = windows.iter().map(|w| w.window_id.as_str()).collect();
+        captured.retain(|id, (key, _, _)| {
+            let keep = live.contains(&id.as_str());
+            if !keep {
+                update.removed.push(key.clone());
+            }
+            keep
+        });
+
+        for window in windows {
+            let now = chrono::Utc::now().timestamp();
+            if let Some((key, name, captured_at)) = captured.get(&window.window_id) {
+                // Activity is reported in whole seconds, so output in the
+                // same second as the last capture still counts
+                let unchanged = window.activity < *captured_at && *key == window.key && *name == window.name;
+                if unchanged {
+                    continue;
+                }
+            }
+
+            let content = match tmux::run_tmux(&["capture-pane", "-p", "-t", &window.window_id]).await {
+                Ok(content) => content,
+                Err(e) => {
+                    warn!("Failed to capture thumbnail for {}: {}", window.window_id, e);
+                    continue;
+                }
+            };
+            captured.insert(window.window_id.clone(), (window.key.clone(), window.name.clone(), now));
+            update.thumbnails.push(Thumbnail {
+                key: window.key,
+                window_name: window.name,
+                pane_width: window.pane_width,
+                pane_height: window.pane_height,
+                lines: downscale(&content, cols, rows),
+            });
+        }
+
+        if (!update.thumbnails.is_empty() || !update.removed.is_empty()) && updates.send(update).is_err() {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_bottom_rows_and_truncates_columns() {
+        let content = "one\ntwo two two\nthree\nfour   \n\n\n";
+        assert_eq!(downscale(content, 5, 3), vec!["two t", "three", "four"]);
+    }
+
+    #[test]
+    fn handles_empty_and_wide_text() {
+        assert!(downscale("\n\n", 10, 3).is_empty());
+        assert_eq!(downscale("héllo wörld", 7, 1), vec!["héllo w"]);
+    }
+}
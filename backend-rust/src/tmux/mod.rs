@@ -1,15 +1,43 @@
 use anyhow::Result;
 use chrono::{DateTime, Utc};
+use std::collections::HashSet;
 use std::process::Stdio;
+use tokio::io::AsyncWriteExt;
 use tokio::process::Command;
 use tracing::{debug, error, info};
 
-use crate::types::{TmuxSession, TmuxWindow};
+use crate::types::{PaneState, PaneTarget, RemainOnExit, SpawnOptions, TmuxBuffer, TmuxSession, TmuxWindow};
 
 fn escape_single_quotes(s: &str) -> String {
     s.replace('\'', "'\\''")
 }
 
+/// Run a tmux command and return its stdout, surfacing tmux's own error text
+/// when the command fails.
+pub async fn run_tmux(args: &[&str]) -> Result<String> {
+    let output = Command::new("tmux").args(args).output().await?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        anyhow::bail!("{}", stderr.trim());
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// Check whether a session with exactly this name exists.
+pub async fn has_session(name: &str) -> bool {
+    // `=` forces an exact match instead of tmux's prefix matching
+    let target = format!("={}", name);
+    Command::new("tmux")
+        .args(["has-session", "-t", &target])
+        .stderr(Stdio::null())
+        .status()
+        .await
+        .map(|s| s.success())
+        .unwrap_or(false)
+}
+
 pub async fn ensure_tmux_server() -> Result<()> {
     // Check if tmux server is running
     let output = Command::new("tmux")
@@ -89,28 +117,54 @@ async fn list_sessions_fallback() -> Result<Vec<TmuxSession>> {
     Ok(sessions)
 }
 
-pub async fn create_session(name: &str) -> Result<()> {
+pub async fn create_session(name: &str, options: &SpawnOptions) -> Result<()> {
     ensure_tmux_server().await?;
     
-    // Get the home directory to start sessions there
+    // Start in the requested directory, or the home directory by default
     let home_dir = std::env::var("HOME").unwrap_or_else(|_| "/".to_string());
+    let start_dir = match &options.cwd {
+        Some(cwd) => resolve_start_directory(cwd)?,
+        None => home_dir.clone(),
+    };
     
-    info!("Executing tmux new-session for: {} in directory: {}", name, home_dir);
-    let status = Command::new("tmux")
-        .args(&["new-session", "-d", "-s", name, "-c", &home_dir])
+    info!("Executing tmux new-session for: {} in directory: {}", name, start_dir);
+    let mut args = vec![
+        "new-session".to_string(),
+        "-d".to_string(),
+        "-s".to_string(),
+        name.to_string(),
+        "-c".to_string(),
+        start_dir,
+    ];
+    args.extend(options.tmux_args());
+
+    let arg_refs: Vec<&str> = args.iter().map(String::as_str).collect();
+    let output = Command::new("tmux")
+        .args(&arg_refs)
         .env("HOME", &home_dir)
-        .status()
+        .output()
         .await?;
 
-    if !status.success() {
-        error!("tmux new-session failed for: {}", name);
-        anyhow::bail!("Failed to create session");
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        error!("tmux new-session failed for {}: {}", name, stderr.trim());
+        anyhow::bail!("{}", stderr.trim());
     }
 
     info!("tmux new-session succeeded for: {}", name);
     Ok(())
 }
 
+/// Expand `~` and make sure a requested start directory exists, since tmux
+/// silently falls back to another directory otherwise.
+fn resolve_start_directory(cwd: &str) -> Result<String> {
+    let path = crate::storage::expand_home(cwd);
+    if !path.is_dir() {
+        anyhow::bail!("Directory does not exist: {}", path.display());
+    }
+    Ok(path.to_string_lossy().to_string())
+}
+
 pub async fn kill_session(name: &str) -> Result<()> {
     info!("Executing tmux kill-session for: {}", name);
     
@@ -164,7 +218,7 @@ pub async fn list_windows(session_name: &str) -> Result<Vec<TmuxWindow>> {
             "-t",
             session_name,
             "-F",
-            "#{window_index}:#{window_name}:#{window_active}:#{window_panes}",
+            "#{window_index}:#{window_name}:#{window_active}:#{window_panes}:#{?synchronize-panes,1,0}",
         ])
         .output()
         .await?;
@@ -179,12 +233,13 @@ pub async fn list_windows(session_name: &str) -> Result<Vec<TmuxWindow>> {
         .filter(|line| !line.is_empty())
         .filter_map(|line| {
             let parts: Vec<&str> = line.split(':').collect();
-            if parts.len() >= 4 {
+            if parts.len() >= 5 {
                 Some(TmuxWindow {
                     index: parts[0].parse().ok()?,
                     name: parts[1].to_string(),
                     active: parts[2] == "1",
                     panes: parts[3].parse().unwrap_or(1),
+                    synchronized: parts[4] == "1",
                 })
             } else {
                 None
@@ -195,39 +250,39 @@ pub async fn list_windows(session_name: &str) -> Result<Vec<TmuxWindow>> {
     Ok(windows)
 }
 
-pub async fn create_window(session_name: &str, window_name: Option<&str>) -> Result<()> {
-    // Try to get the current pane's working directory
-    let current_dir = get_current_pane_directory(session_name).await.ok();
-    
-    let args = vec!["new-window", "-a", "-t", session_name];
+pub async fn create_window(
+    session_name: &str,
+    window_name: Option<&str>,
+    options: &SpawnOptions,
+    select: bool,
+) -> Result<()> {
+    // Use the requested directory, or fall back to the current pane's
+    let start_dir = match &options.cwd {
+        Some(cwd) => Some(resolve_start_directory(cwd)?),
+        None => get_current_pane_directory(session_name).await.ok(),
+    };
     
-    // Store the directory in a variable that lives long enough
-    let dir_args: Vec<String>;
-    if let Some(dir) = current_dir {
-        dir_args = vec!["-c".to_string(), dir];
-    } else {
-        dir_args = vec![];
+    let mut args = vec![
+        "new-window".to_string(),
+        "-a".to_string(),
+        "-t".to_string(),
+        session_name.to_string(),
+    ];
+    if !select {
+        args.push("-d".to_string());
     }
-    
-    // Convert args to the correct format
-    let mut final_args: Vec<&str> = args.into_iter().collect();
-    for arg in &dir_args {
-        final_args.push(arg);
+    if let Some(dir) = start_dir {
+        args.push("-c".to_string());
+        args.push(dir);
     }
-    
     if let Some(name) = window_name {
-        final_args.push("-n");
-        final_args.push(name);
+        args.push("-n".to_string());
+        args.push(name.to_string());
     }
+    args.extend(options.tmux_args());
 
-    let status = Command::new("tmux")
-        .args(&final_args)
-        .status()
-        .await?;
-
-    if !status.success() {
-        anyhow::bail!("Failed to create window");
-    }
+    let arg_refs: Vec<&str> = args.iter().map(String::as_str).collect();
+    run_tmux(&arg_refs).await?;
 
     Ok(())
 }
@@ -301,28 +356,395 @@ pub async fn select_window(session_name: &str, window_index: &str) -> Result<()>
     Ok(())
 }
 
-// Alternative session management functions that avoid direct attachment
+/// Exact-match target for a window, immune to tmux's prefix matching.
+fn window_target(session_name: &str, window_index: Option<u32>) -> String {
+    match window_index {
+        Some(index) => format!("={}:{}", session_name, index),
+        None => format!("={}:", session_name),
+    }
+}
 
-pub async fn capture_pane(session_name: &str) -> Result<String> {
-    let output = Command::new("tmux")
-        .args(&[
-            "capture-pane",
-            "-t", session_name,
-            "-p",  // Print to stdout
-            "-e",  // Include escape sequences
-            "-J",  // Join wrapped lines
-            "-S", "-",  // Start from beginning of visible area
-            "-E", "-",  // End at bottom
-        ])
-        .output()
-        .await?;
-    
-    if output.status.success() {
-        Ok(String::from_utf8_lossy(&output.stdout).to_string())
-    } else {
+/// Move a window to another session (or another index in the same one).
+/// Without a target index the window takes the next free index.
+pub async fn move_window(
+    session_name: &str,
+    window_index: u32,
+    target_session: &str,
+    target_index: Option<u32>,
+) -> Result<()> {
+    let source = window_target(session_name, Some(window_index));
+    let target = window_target(target_session, target_index);
+    run_tmux(&["move-window", "-d", "-s", &source, "-t", &target]).await?;
+    Ok(())
+}
+
+pub async fn swap_windows(
+    session_name: &str,
+    window_index: u32,
+    target_session: &str,
+    target_index: u32,
+) -> Result<()> {
+    let source = window_target(session_name, Some(window_index));
+    let target = window_target(target_session, Some(target_index));
+    run_tmux(&["swap-window", "-d", "-s", &source, "-t", &target]).await?;
+    Ok(())
+}
+
+/// Link a window into another session so it appears in both.
+pub async fn link_window(
+    session_name: &str,
+    window_index: u32,
+    target_session: &str,
+    target_index: Option<u32>,
+) -> Result<()> {
+    let source = window_target(session_name, Some(window_index));
+    let target = window_target(target_session, target_index);
+    run_tmux(&["link-window", "-d", "-s", &source, "-t", &target]).await?;
+    Ok(())
+}
+
+/// Remove a window from one session. tmux refuses this for a window that is
+/// not linked anywhere else; use `kill_window` for that.
+pub async fn unlink_window(session_name: &str, window_index: u32) -> Result<()> {
+    let target = window_target(session_name, Some(window_index));
+    run_tmux(&["unlink-window", "-t", &target]).await?;
+    Ok(())
+}
+
+/// Close the gaps in a session's window indexes.
+pub async fn renumber_windows(session_name: &str) -> Result<()> {
+    let target = window_target(session_name, None);
+    run_tmux(&["move-window", "-r", "-t", &target]).await?;
+    Ok(())
+}
+
+/// Largest slice of input sent in one `send-keys`, kept well under tmux's
+/// command size limit once hex-encoded.
+const SEND_KEYS_CHUNK: usize = 2048;
+
+/// Write raw bytes to a pane as if typed, escape sequences included.
+pub async fn send_input(target: &PaneTarget, data: &[u8]) -> Result<()> {
+    send_bytes(&target.tmux_target(), data).await
+}
+
+/// Like `send_input`, for a raw tmux target such as a pane id.
+pub async fn send_bytes(target: &str, data: &[u8]) -> Result<()> {
+    for chunk in data.chunks(SEND_KEYS_CHUNK) {
+        // -H takes each byte as hex, so nothing in the data is parsed as a
+        // key name or a flag
+        let hex: Vec<String> = chunk.iter().map(|b| format!("{:02x}", b)).collect();
+        let mut args = vec!["send-keys", "-t", target, "-H"];
+        args.extend(hex.iter().map(String::as_str));
+        run_tmux(&args).await?;
+    }
+    Ok(())
+}
+
+const LOAD_BUFFER_CHUNK: usize = 64 * 1024;
+
+/// Store `data` in a paste buffer, named by tmux if `name` is `None`. The
+/// data goes through stdin, so neither its size nor its content touch the
+/// command line. `progress` is called with the number of bytes written
+/// after each chunk.
+pub async fn load_buffer(name: Option<&str>, data: &[u8], mut progress: impl FnMut(usize)) -> Result<()> {
+    let mut args = vec!["load-buffer"];
+    if let Some(name) = name {
+        args.extend(["-b", name]);
+    }
+    args.push("-");
+    let mut child = Command::new("tmux")
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    let mut stdin = child.stdin.take().ok_or_else(|| anyhow::anyhow!("tmux stdin unavailable"))?;
+    let mut written = 0;
+    for chunk in data.chunks(LOAD_BUFFER_CHUNK) {
+        stdin.write_all(chunk).await?;
+        written += chunk.len();
+        progress(written);
+    }
+    drop(stdin);
+
+    let output = child.wait_with_output().await?;
+    if !output.status.success() {
         let stderr = String::from_utf8_lossy(&output.stderr);
-        anyhow::bail!("Failed to capture pane: {}", stderr)
+        anyhow::bail!("{}", stderr.trim());
     }
+    Ok(())
+}
+
+/// List paste buffers, most recent first.
+pub async fn list_buffers() -> Result<Vec<TmuxBuffer>> {
+    let output = match run_tmux(&[
+        "list-buffers",
+        "-F",
+        "#{buffer_name}\t#{buffer_size}\t#{buffer_created}\t#{buffer_sample}",
+    ])
+    .await
+    {
+        Ok(output) => output,
+        // No server means no buffers
+        Err(e) if e.to_string().contains("no server running") => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+
+    Ok(output
+        .lines()
+        .filter_map(|line| {
+            let parts: Vec<&str> = line.splitn(4, '\t').collect();
+            if parts.len() < 4 {
+                return None;
+            }
+            Some(TmuxBuffer {
+                name: parts[0].to_string(),
+                size: parts[1].parse().unwrap_or(0),
+                created: DateTime::from_timestamp(parts[2].parse().ok()?, 0)?,
+                sample: parts[3].to_string(),
+            })
+        })
+        .collect())
+}
+
+pub async fn show_buffer(name: &str) -> Result<String> {
+    run_tmux(&["show-buffer", "-b", name]).await
+}
+
+pub async fn delete_buffer(name: &str) -> Result<()> {
+    run_tmux(&["delete-buffer", "-b", name]).await?;
+    Ok(())
+}
+
+/// Paste a buffer into a pane, deleting it afterwards. With `bracketed`,
+/// tmux adds bracketed-paste markers when the application has asked for
+/// them.
+pub async fn paste_buffer(name: &str, target: &PaneTarget, bracketed: bool) -> Result<()> {
+    let target = target.tmux_target();
+    let mut args = vec!["paste-buffer", "-d", "-b", name, "-t", &target];
+    if bracketed {
+        args.push("-p");
+    }
+    if let Err(e) = run_tmux(&args).await {
+        let _ = delete_buffer(name).await;
+        return Err(e);
+    }
+    Ok(())
+}
+
+/// Turn `synchronize-panes` on or off for a window.
+pub async fn set_synchronize_panes(session_name: &str, window_index: u32, enabled: bool) -> Result<()> {
+    let target = window_target(session_name, Some(window_index));
+    let value = if enabled { "on" } else { "off" };
+    run_tmux(&["set-window-option", "-t", &target, "synchronize-panes", value]).await?;
+    Ok(())
+}
+
+// Alternative session management functions that avoid direct attachment
+
+/// Capture a pane's full history plus the visible area, with wrapped lines
+/// joined. The blank rows below the cursor are dropped.
+pub async fn capture_pane(target: &PaneTarget, escapes: bool) -> Result<String> {
+    let target = target.tmux_target();
+    let mut args = vec![
+        "capture-pane",
+        "-t", &target,
+        "-p",  // Print to stdout
+        "-J",  // Join wrapped lines
+        "-S", "-",  // Start at the oldest history line
+        "-E", "-",  // End at the bottom of the visible area
+    ];
+    if escapes {
+        args.push("-e");  // Include escape sequences
+    }
+
+    let content = run_tmux(&args)
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to capture pane: {}", e))?;
+    Ok(content.trim_end_matches('\n').to_string())
+}
+
+/// Number of history lines and visible rows of a pane.
+pub async fn pane_line_counts(target: &PaneTarget) -> Result<(usize, usize)> {
+    let target = target.tmux_target();
+    let output = run_tmux(&["display-message", "-p", "-t", &target, "#{history_size} #{pane_height}"]).await?;
+    let mut counts = output.split_whitespace().map(|n| n.parse::<usize>());
+    match (counts.next(), counts.next()) {
+        (Some(Ok(history)), Some(Ok(height))) => Ok((history, height)),
+        _ => anyhow::bail!("Unexpected pane size: {}", output.trim()),
+    }
+}
+
+/// Capture rows `start..=end` of a pane without joining wrapped lines.
+/// Rows are numbered as tmux does: 0 is the top visible row and history
+/// rows are negative.
+pub async fn capture_rows(target: &PaneTarget, start: i64, end: i64, escapes: bool) -> Result<Vec<String>> {
+    let target = target.tmux_target();
+    let (start, end) = (start.to_string(), end.to_string());
+    let mut args = vec!["capture-pane", "-t", &target, "-p", "-S", &start, "-E", &end];
+    if escapes {
+        args.push("-e");
+    }
+
+    let content = run_tmux(&args)
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to capture pane: {}", e))?;
+    Ok(content.lines().map(str::to_string).collect())
+}
+
+/// Capture only what a pane currently shows, without history.
+pub async fn capture_visible(target: &PaneTarget, escapes: bool) -> Result<String> {
+    let target = target.tmux_target();
+    let mut args = vec!["capture-pane", "-t", &target, "-p"];
+    if escapes {
+        args.push("-e");
+    }
+
+    let content = run_tmux(&args)
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to capture pane: {}", e))?;
+    Ok(content.trim_end_matches('\n').to_string())
+}
+
+/// Expand a tmux format string (e.g. `#{pane_width}`) for one pane.
+pub async fn pane_format(target: &PaneTarget, format: &str) -> Result<String> {
+    let target = target.tmux_target();
+    let output = run_tmux(&["display-message", "-p", "-t", &target, format]).await?;
+    Ok(output.trim_end_matches('\n').to_string())
+}
+
+/// Panes of a session, or of one of its windows, including dead ones.
+pub async fn list_pane_states(session_name: &str, window_index: Option<u32>) -> Result<Vec<PaneState>> {
+    let format = [
+        "#{window_index}",
+        "#{pane_index}",
+        "#{pane_id}",
+        "#{pane_pid}",
+        "#{pane_dead}",
+        "#{pane_dead_status}",
+        "#{pane_dead_signal}",
+        "#{remain-on-exit}",
+        "#{pane_current_command}",
+    ]
+    .join("\t");
+    let output = match window_index {
+        Some(window) => {
+            let target = window_target(session_name, Some(window));
+            run_tmux(&["list-panes", "-t", &target, "-F", &format]).await?
+        }
+        None => run_tmux(&["list-panes", "-s", "-t", &format!("={}", session_name), "-F", &format]).await?,
+    };
+
+    Ok(output
+        .lines()
+        .filter_map(|line| {
+            let parts: Vec<&str> = line.splitn(9, '\t').collect();
+            if parts.len() < 9 {
+                return None;
+            }
+            Some(PaneState {
+                window_index: parts[0].parse().ok()?,
+                pane_index: parts[1].parse().ok()?,
+                pane_id: parts[2].to_string(),
+                pid: parts[3].parse().unwrap_or(0),
+                dead: parts[4] == "1",
+                dead_status: parts[5].parse().ok(),
+                dead_signal: parts[6].parse().ok(),
+                remain_on_exit: match parts[7] {
+                    "on" => RemainOnExit::On,
+                    "failed" => RemainOnExit::Failed,
+                    _ => RemainOnExit::Off,
+                },
+                current_command: parts[8].to_string(),
+            })
+        })
+        .collect())
+}
+
+/// Start a pane's program again, killing it first if it still runs.
+/// Without a command, the one the pane was created with is used.
+pub async fn respawn_pane(target: &PaneTarget, command: Option<&str>) -> Result<()> {
+    let target = target.tmux_target();
+    let mut args = vec!["respawn-pane", "-k", "-t", &target];
+    args.extend(command);
+    run_tmux(&args).await?;
+    Ok(())
+}
+
+pub async fn set_remain_on_exit(target: &PaneTarget, mode: RemainOnExit) -> Result<()> {
+    let target = target.tmux_target();
+    let value = match mode {
+        RemainOnExit::Off => "off",
+        RemainOnExit::On => "on",
+        RemainOnExit::Failed => "failed",
+    };
+    run_tmux(&["set-option", "-p", "-t", &target, "remain-on-exit", value]).await?;
+    Ok(())
+}
+
+/// Let applications set the clipboard with OSC 52. tmux's default of
+/// `external` swallows those sequences instead of passing them on to
+/// attached clients, so a yank in vim would never reach the browser.
+pub async fn enable_clipboard() -> Result<()> {
+    run_tmux(&["set-option", "-s", "set-clipboard", "on"]).await?;
+    Ok(())
+}
+
+/// tty of the tmux client running as process `pid`.
+async fn client_tty_by_pid(pid: u32) -> Result<String> {
+    let clients = run_tmux(&["list-clients", "-F", "#{client_pid} #{client_tty}"]).await?;
+    clients
+        .lines()
+        .filter_map(|line| line.split_once(' '))
+        .find(|(client_pid, _)| client_pid.parse() == Ok(pid))
+        .map(|(_, tty)| tty.to_string())
+        .ok_or_else(|| anyhow::anyhow!("No tmux client with pid {}", pid))
+}
+
+/// Make tmux redraw everything for the client running as process `pid`.
+pub async fn refresh_client_by_pid(pid: u32) -> Result<()> {
+    let tty = client_tty_by_pid(pid).await?;
+    run_tmux(&["refresh-client", "-t", &tty]).await?;
+    Ok(())
+}
+
+/// Leave the client running as process `pid` out of window sizing, or put
+/// it back.
+pub async fn set_client_ignore_size(pid: u32, ignore: bool) -> Result<()> {
+    let tty = client_tty_by_pid(pid).await?;
+    let flag = if ignore { "ignore-size" } else { "!ignore-size" };
+    run_tmux(&["refresh-client", "-t", &tty, "-f", flag]).await?;
+    Ok(())
+}
+
+/// Ids (`%N`) of every pane on the server.
+pub async fn list_pane_ids() -> Result<HashSet<String>> {
+    let output = run_tmux(&["list-panes", "-a", "-F", "#{pane_id}"]).await?;
+    Ok(output.lines().map(str::to_string).collect())
+}
+
+/// Every pane on the server, oldest session first.
+pub async fn list_all_panes() -> Result<Vec<PaneTarget>> {
+    let output = run_tmux(&[
+        "list-panes",
+        "-a",
+        "-F",
+        "#{session_name}\t#{window_index}\t#{pane_index}",
+    ])
+    .await?;
+
+    Ok(output
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.split('\t');
+            Some(PaneTarget {
+                session_name: parts.next()?.to_string(),
+                window_index: parts.next()?.parse().ok(),
+                pane_index: parts.next()?.parse().ok(),
+            })
+        })
+        .collect())
 }
 
 pub async fn send_keys_to_session(session_name: &str, keys: &str) -> Result<()> {
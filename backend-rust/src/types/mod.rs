@@ -12,6 +12,17 @@ pub struct TmuxSession {
     pub dimensions: String,
 }
 
+/// A tmux paste buffer, without its content.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TmuxBuffer {
+    pub name: String,
+    pub size: usize,
+    pub created: DateTime<Utc>,
+    /// Start of the content with control characters escaped.
+    pub sample: String,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct TmuxWindow {
@@ -19,6 +30,60 @@ pub struct TmuxWindow {
     pub name: String,
     pub active: bool,
     pub panes: u32,
+    /// Whether `synchronize-panes` is on, mirroring input to every pane.
+    pub synchronized: bool,
+}
+
+/// What tmux does with a pane whose program exits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RemainOnExit {
+    /// Close the pane.
+    Off,
+    /// Keep the pane open, marked dead, so it can be respawned.
+    On,
+    /// Keep it only if the program exited unsuccessfully.
+    Failed,
+}
+
+/// A pane with the state of its program.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PaneState {
+    pub window_index: u32,
+    pub pane_index: u32,
+    pub pane_id: String,
+    pub pid: u32,
+    pub current_command: String,
+    /// The program exited and the pane stayed open because of
+    /// `remain-on-exit`.
+    pub dead: bool,
+    pub dead_status: Option<i32>,
+    /// Signal that killed the program, if it did not exit by itself.
+    pub dead_signal: Option<i32>,
+    pub remain_on_exit: RemainOnExit,
+}
+
+/// A pane addressed by session, window and pane index. Missing indexes fall
+/// back to tmux's current window or active pane.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PaneTarget {
+    pub session_name: String,
+    pub window_index: Option<u32>,
+    pub pane_index: Option<u32>,
+}
+
+impl PaneTarget {
+    /// Exact-match tmux target string, e.g. `=work:1.2`.
+    pub fn tmux_target(&self) -> String {
+        match (self.window_index, self.pane_index) {
+            (Some(window), Some(pane)) => format!("={}:{}.{}", self.session_name, window, pane),
+            (Some(window), None) => format!("={}:{}", self.session_name, window),
+            (None, Some(pane)) => format!("={}:.{}", self.session_name, pane),
+            (None, None) => format!("={}:", self.session_name),
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -39,6 +104,66 @@ pub struct CreateWindowRequest {
     pub window_name: Option<String>,
 }
 
+/// Where and how to start the first pane of a new session or window.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpawnOptions {
+    /// Starting directory; must exist.
+    pub cwd: Option<String>,
+    /// Command to run instead of the default shell. The pane closes when it exits.
+    pub command: Option<String>,
+    pub env: Option<HashMap<String, String>>,
+}
+
+impl SpawnOptions {
+    /// Trailing `new-session`/`new-window` arguments for env and command.
+    pub fn tmux_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if let Some(env) = &self.env {
+            for (key, value) in env {
+                args.push("-e".to_string());
+                args.push(format!("{}={}", key, value));
+            }
+        }
+        if let Some(command) = self.command.as_ref().filter(|c| !c.trim().is_empty()) {
+            args.push(command.clone());
+        }
+        args
+    }
+}
+
+/// How a client attaches to a session.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AttachOptions {
+    /// Watch only; input from the client is dropped.
+    #[serde(default)]
+    pub read_only: bool,
+    /// Leave the session's size to other clients.
+    #[serde(default)]
+    pub ignore_size: bool,
+}
+
+impl AttachOptions {
+    /// Extra `attach-session` arguments.
+    pub fn tmux_args(&self) -> Vec<&'static str> {
+        let mut args = Vec::new();
+        if self.read_only {
+            args.push("-r");
+        }
+        if self.ignore_size {
+            args.extend(["-f", "ignore-size"]);
+        }
+        args
+    }
+
+    /// Whether the tmux client stays out of window sizing. A client parked
+    /// in the warm pool always does; an active one follows the option.
+    pub fn ignores_size(&self, parked: bool) -> bool {
+        parked || self.ignore_size
+    }
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct RenameWindowRequest {
@@ -92,6 +217,13 @@ pub struct CronJob {
     pub tmux_session: Option<String>,
 }
 
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BroadcastFailure {
+    pub target: PaneTarget,
+    pub error: String,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "kebab-case")]
 pub enum WebSocketMessage {
@@ -101,6 +233,8 @@ pub enum WebSocketMessage {
         session_name: String,
         cols: u16,
         rows: u16,
+        #[serde(flatten)]
+        options: AttachOptions,
     },
     Input {
         data: String,
@@ -126,6 +260,10 @@ pub enum WebSocketMessage {
     // Session management
     CreateSession {
         name: Option<String>,
+        #[serde(flatten)]
+        options: SpawnOptions,
+        /// When `false`, attach this client to the new session right away
+        detached: Option<bool>,
     },
     KillSession {
         #[serde(rename = "sessionName")]
@@ -143,6 +281,10 @@ pub enum WebSocketMessage {
         session_name: String,
         #[serde(rename = "windowName")]
         window_name: Option<String>,
+        #[serde(flatten)]
+        options: SpawnOptions,
+        /// Make the new window current (default); `false` creates it in the background
+        select: Option<bool>,
     },
     KillWindow {
         #[serde(rename = "sessionName")]
@@ -158,6 +300,283 @@ pub enum WebSocketMessage {
         #[serde(rename = "newName")]
         new_name: String,
     },
+    MoveWindow {
+        #[serde(rename = "sessionName")]
+        session_name: String,
+        #[serde(rename = "windowIndex")]
+        window_index: u32,
+        #[serde(rename = "targetSession")]
+        target_session: String,
+        #[serde(rename = "targetIndex")]
+        target_index: Option<u32>,
+    },
+    SwapWindows {
+        #[serde(rename = "sessionName")]
+        session_name: String,
+        #[serde(rename = "windowIndex")]
+        window_index: u32,
+        /// Defaults to the source session
+        #[serde(rename = "targetSession")]
+        target_session: Option<String>,
+        #[serde(rename = "targetIndex")]
+        target_index: u32,
+    },
+    LinkWindow {
+        #[serde(rename = "sessionName")]
+        session_name: String,
+        #[serde(rename = "windowIndex")]
+        window_index: u32,
+        #[serde(rename = "targetSession")]
+        target_session: String,
+        #[serde(rename = "targetIndex")]
+        target_index: Option<u32>,
+    },
+    UnlinkWindow {
+        #[serde(rename = "sessionName")]
+        session_name: String,
+        #[serde(rename = "windowIndex")]
+        window_index: u32,
+    },
+    RenumberWindows {
+        #[serde(rename = "sessionName")]
+        session_name: String,
+    },
+    // Input broadcasting
+    BroadcastInput {
+        targets: Vec<PaneTarget>,
+        data: String,
+    },
+    /// Large pastes, staged in a tmux buffer instead of typed into the PTY.
+    Paste {
+        /// Echoed back in progress and result messages.
+        id: Option<String>,
+        data: String,
+        /// Defaults to the active pane of the attached session.
+        #[serde(flatten)]
+        target: Option<PaneTarget>,
+        /// Wrap in bracketed-paste markers if the application wants them;
+        /// on unless set to `false`.
+        bracketed: Option<bool>,
+    },
+    // Paste buffers
+    ListBuffers,
+    ShowBuffer {
+        name: String,
+    },
+    SetBuffer {
+        /// tmux picks a name when absent.
+        name: Option<String>,
+        content: String,
+    },
+    DeleteBuffer {
+        name: String,
+    },
+    SetSynchronizePanes {
+        #[serde(rename = "sessionName")]
+        session_name: String,
+        #[serde(rename = "windowIndex")]
+        window_index: u32,
+        enabled: bool,
+    },
+    // Scrollback
+    GetScrollback {
+        target: PaneTarget,
+        start: Option<usize>,
+        end: Option<usize>,
+        #[serde(default)]
+        escapes: bool,
+    },
+    SearchScrollback {
+        pattern: String,
+        /// Search every pane when absent.
+        target: Option<PaneTarget>,
+        #[serde(flatten)]
+        options: crate::scrollback::SearchOptions,
+    },
+    // Session recording
+    StartRecording {
+        title: Option<String>,
+        /// Also record keystrokes sent by this client.
+        #[serde(rename = "recordInput", default)]
+        record_input: bool,
+    },
+    StopRecording,
+    ListRecordings,
+    GetRecording {
+        id: String,
+    },
+    DeleteRecording {
+        id: String,
+    },
+    // Recording playback
+    PlayRecording {
+        id: String,
+        speed: Option<f64>,
+        /// Cap on pauses between events, in seconds.
+        #[serde(rename = "idleTimeLimit")]
+        idle_time_limit: Option<f64>,
+        /// Seconds into the recording to start from.
+        start: Option<f64>,
+    },
+    PlaybackControl {
+        action: crate::recording::player::PlaybackAction,
+        position: Option<f64>,
+        speed: Option<f64>,
+    },
+    // Screen model
+    GetScreen {
+        /// Any attached client's view of this session; this client's own
+        /// attachment when absent.
+        #[serde(rename = "sessionName")]
+        session_name: Option<String>,
+        /// Include escape sequences that redraw the screen.
+        #[serde(default)]
+        formatted: bool,
+    },
+    SetStreamMode {
+        mode: StreamMode,
+        /// Diff frames per second; defaults to 10.
+        fps: Option<u32>,
+    },
+    // Window alerts
+    GetAlertSettings,
+    SetWindowAlerts {
+        #[serde(flatten)]
+        settings: crate::alerts::WindowAlertSettings,
+    },
+    // Output watches
+    ListWatches,
+    SaveWatch {
+        #[serde(flatten)]
+        rule: crate::watches::WatchRule,
+    },
+    DeleteWatch {
+        id: String,
+    },
+    GetWatchHistory,
+    ClearWatchHistory,
+    // Automation scripts
+    RunAutomation {
+        /// Run id; one is generated if missing.
+        id: Option<String>,
+        #[serde(flatten)]
+        script: crate::automation::AutomationScript,
+    },
+    StopAutomation {
+        #[serde(rename = "runId")]
+        run_id: String,
+    },
+    ListAutomations,
+    // One-off scheduled input
+    ScheduleInput {
+        name: Option<String>,
+        #[serde(flatten)]
+        target: PaneTarget,
+        #[serde(default)]
+        text: String,
+        /// Press Enter after the text; on unless set to `false`.
+        enter: Option<bool>,
+        /// When to send; alternatively `delaySeconds` from now.
+        #[serde(rename = "runAt")]
+        run_at: Option<DateTime<Utc>>,
+        #[serde(rename = "delaySeconds")]
+        delay_seconds: Option<u64>,
+    },
+    ListScheduledInputs,
+    CancelScheduledInput {
+        id: String,
+    },
+    // Shell integration
+    GetShellIntegration,
+    SetShellIntegration {
+        #[serde(rename = "sessionName")]
+        session_name: String,
+        enabled: bool,
+    },
+    GetCommandHistory {
+        #[serde(rename = "sessionName")]
+        session_name: String,
+        #[serde(rename = "windowIndex")]
+        window_index: Option<u32>,
+        #[serde(rename = "paneIndex")]
+        pane_index: Option<u32>,
+        /// Defaults to 100.
+        limit: Option<usize>,
+    },
+    // Process inspector
+    InspectPane {
+        #[serde(flatten)]
+        target: PaneTarget,
+        /// Mask secret-looking environment values; on unless set to `false`.
+        #[serde(rename = "maskSecrets")]
+        mask_secrets: Option<bool>,
+    },
+    // Pane process control
+    SignalPane {
+        #[serde(flatten)]
+        target: PaneTarget,
+        signal: crate::procinfo::Signal,
+        /// Defaults to the foreground process group.
+        scope: Option<crate::procinfo::SignalScope>,
+    },
+    RespawnPane {
+        #[serde(flatten)]
+        target: PaneTarget,
+        /// Replaces the command the pane was started with.
+        command: Option<String>,
+    },
+    SetRemainOnExit {
+        #[serde(flatten)]
+        target: PaneTarget,
+        mode: RemainOnExit,
+    },
+    ListPaneStates {
+        #[serde(rename = "sessionName")]
+        session_name: String,
+        #[serde(rename = "windowIndex")]
+        window_index: Option<u32>,
+    },
+    // Pane output logs
+    StartPaneLog {
+        #[serde(flatten)]
+        target: PaneTarget,
+        #[serde(flatten)]
+        options: crate::pane_log::LogOptions,
+    },
+    StopPaneLog {
+        #[serde(flatten)]
+        target: PaneTarget,
+    },
+    ListPaneLogs,
+    TailPaneLog {
+        #[serde(rename = "sessionName")]
+        session_name: String,
+        file: String,
+        /// Defaults to 100.
+        lines: Option<usize>,
+    },
+    // Multi-client resize policy
+    GetResizePolicy {
+        #[serde(rename = "sessionName")]
+        session_name: String,
+    },
+    SetResizePolicy {
+        #[serde(rename = "sessionName")]
+        session_name: String,
+        /// `None` restores tmux's global behaviour.
+        policy: Option<crate::resize_policy::ResizePolicy>,
+    },
+    // Session overview thumbnails
+    SubscribeThumbnails {
+        #[serde(flatten)]
+        options: crate::thumbnails::ThumbnailOptions,
+    },
+    UnsubscribeThumbnails,
+    // Export
+    ExportPane {
+        #[serde(flatten)]
+        request: crate::export::ExportRequest,
+    },
     // System stats
     GetStats,
     // Cron management
@@ -204,6 +623,51 @@ pub enum WebSocketMessage {
         window_index: u32,
     },
     UnwatchChatLog,
+    // Session templates
+    ListSessionTemplates,
+    GetSessionTemplate {
+        name: String,
+    },
+    SaveSessionTemplate {
+        content: String,
+        format: crate::session_templates::TemplateFormat,
+        #[serde(rename = "originalName")]
+        original_name: Option<String>,
+    },
+    DeleteSessionTemplate {
+        name: String,
+    },
+    InstantiateSessionTemplate {
+        name: String,
+        #[serde(rename = "sessionName")]
+        session_name: Option<String>,
+        #[serde(default)]
+        variables: HashMap<String, String>,
+    },
+    // Session snapshots
+    CreateSnapshot {
+        label: Option<String>,
+        #[serde(rename = "includeScrollback")]
+        include_scrollback: Option<bool>,
+    },
+    ListSnapshots,
+    DiffSnapshots {
+        from: String,
+        /// Compare against the live tmux state when absent
+        to: Option<String>,
+    },
+    RestoreSnapshot {
+        id: String,
+        #[serde(default)]
+        options: crate::snapshots::RestoreOptions,
+    },
+    DeleteSnapshot {
+        id: String,
+    },
+    GetSnapshotSettings,
+    UpdateSnapshotSettings {
+        settings: crate::snapshots::SnapshotSettings,
+    },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -213,6 +677,15 @@ pub enum AudioAction {
     Stop,
 }
 
+/// How terminal output reaches a client: every PTY byte, or periodic
+/// screen diffs rendered from the server's screen model.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StreamMode {
+    Raw,
+    Diff,
+}
+
 #[derive(Debug, Clone, Serialize)]
 #[serde(tag = "type", rename_all = "kebab-case")]
 pub enum ServerMessage {
@@ -222,6 +695,8 @@ pub enum ServerMessage {
     Attached {
         #[serde(rename = "sessionName")]
         session_name: String,
+        #[serde(flatten)]
+        options: AttachOptions,
     },
     Output {
         data: String,
@@ -282,6 +757,79 @@ pub enum ServerMessage {
         #[serde(skip_serializing_if = "Option::is_none")]
         error: Option<String>,
     },
+    WindowMoved {
+        success: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+    WindowsSwapped {
+        success: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+    WindowLinked {
+        success: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+    WindowUnlinked {
+        success: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+    WindowsRenumbered {
+        success: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+    InputBroadcast {
+        success: bool,
+        delivered: u32,
+        /// Targets that could not be written to, with tmux's reason.
+        failed: Vec<BroadcastFailure>,
+    },
+    PasteProgress {
+        id: String,
+        sent: usize,
+        total: usize,
+    },
+    Pasted {
+        id: String,
+        success: bool,
+        bytes: usize,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+    BuffersList {
+        buffers: Vec<TmuxBuffer>,
+    },
+    BufferContent {
+        name: String,
+        content: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+    BufferSet {
+        success: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+    BufferDeleted {
+        name: String,
+        success: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+    /// Text an application in the attached session copied with OSC 52.
+    Clipboard {
+        #[serde(flatten)]
+        event: crate::clipboard::ClipboardEvent,
+    },
+    SynchronizePanesSet {
+        success: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
     // System stats response
     Stats {
         stats: SystemStats,
@@ -348,4 +896,325 @@ pub enum ServerMessage {
     ChatLogError {
         error: String,
     },
+    // Session template responses
+    SessionTemplatesList {
+        templates: Vec<crate::session_templates::SessionTemplate>,
+    },
+    SessionTemplateContent {
+        name: String,
+        content: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        format: Option<crate::session_templates::TemplateFormat>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+    SessionTemplateSaved {
+        success: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        template: Option<crate::session_templates::SessionTemplate>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+    SessionTemplateDeleted {
+        name: String,
+        success: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+    SessionTemplateInstantiated {
+        success: bool,
+        #[serde(rename = "sessionName", skip_serializing_if = "Option::is_none")]
+        session_name: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+    // Session snapshot responses
+    SnapshotCreated {
+        success: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        snapshot: Option<crate::snapshots::SnapshotSummary>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+    SnapshotsList {
+        snapshots: Vec<crate::snapshots::SnapshotSummary>,
+    },
+    SnapshotDiff {
+        from: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        to: Option<String>,
+        diff: crate::snapshots::SnapshotDiff,
+    },
+    SnapshotRestored {
+        id: String,
+        success: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        result: Option<crate::snapshots::RestoreResult>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+    SnapshotDeleted {
+        id: String,
+        success: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+    SnapshotSettings {
+        settings: crate::snapshots::SnapshotSettings,
+    },
+    Scrollback {
+        target: PaneTarget,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        range: Option<crate::scrollback::ScrollbackRange>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+    ScrollbackSearchResults {
+        pattern: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        results: Option<crate::scrollback::SearchResults>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+    RecordingStarted {
+        success: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        recording: Option<crate::recording::RecordingInfo>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+    RecordingStopped {
+        success: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        recording: Option<crate::recording::RecordingInfo>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+    RecordingsList {
+        recordings: Vec<crate::recording::RecordingInfo>,
+    },
+    RecordingContent {
+        id: String,
+        /// Raw asciicast v2 file.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        content: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+    RecordingDeleted {
+        id: String,
+        success: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+    PlaybackStatus {
+        status: crate::recording::player::PlaybackStatus,
+    },
+    PlaybackStopped {
+        id: String,
+    },
+    ScreenSnapshot {
+        #[serde(rename = "sessionName", skip_serializing_if = "Option::is_none")]
+        session_name: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        snapshot: Option<crate::screen::ScreenSnapshot>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+    StreamModeChanged {
+        mode: StreamMode,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        fps: Option<u32>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+    AlertSettings {
+        settings: Vec<crate::alerts::WindowAlertSettings>,
+    },
+    /// A window that opted in rang its bell, had output, or went quiet.
+    WindowAlert {
+        #[serde(rename = "sessionName")]
+        session_name: String,
+        #[serde(rename = "windowIndex")]
+        window_index: u32,
+        #[serde(rename = "windowName")]
+        window_name: String,
+        kind: crate::alerts::AlertKind,
+    },
+    WatchesList {
+        rules: Vec<crate::watches::WatchRule>,
+    },
+    WatchSaved {
+        success: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        rule: Option<crate::watches::WatchRule>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+    WatchDeleted {
+        id: String,
+        success: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+    WatchHistory {
+        matches: Vec<crate::watches::WatchMatch>,
+    },
+    /// A watch rule matched a pane's output.
+    WatchTriggered {
+        #[serde(rename = "match")]
+        watch_match: crate::watches::WatchMatch,
+    },
+    /// Sent to the client that started a run as it moves through its steps.
+    AutomationStatus {
+        #[serde(flatten)]
+        status: crate::automation::RunStatus,
+    },
+    AutomationRuns {
+        runs: Vec<crate::automation::RunStatus>,
+    },
+    InputScheduled {
+        success: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        input: Option<crate::scheduled_input::ScheduledInput>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+    ScheduledInputsList {
+        inputs: Vec<crate::scheduled_input::ScheduledInput>,
+    },
+    ScheduledInputCancelled {
+        id: String,
+        success: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+    /// Sessions with shell integration turned on.
+    ShellIntegration {
+        sessions: Vec<String>,
+    },
+    CommandHistory {
+        commands: Vec<crate::shell_integration::CommandBlock>,
+    },
+    /// A command started or finished in a session with shell integration.
+    ShellCommand {
+        command: crate::shell_integration::CommandBlock,
+    },
+    PaneProcesses {
+        #[serde(flatten)]
+        target: PaneTarget,
+        success: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        inspection: Option<crate::procinfo::PaneInspection>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+    PaneSignalled {
+        #[serde(flatten)]
+        target: PaneTarget,
+        signal: crate::procinfo::Signal,
+        success: bool,
+        pids: Vec<u32>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+    PaneRespawned {
+        #[serde(flatten)]
+        target: PaneTarget,
+        success: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+    RemainOnExitSet {
+        #[serde(flatten)]
+        target: PaneTarget,
+        mode: RemainOnExit,
+        success: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+    PaneStates {
+        #[serde(rename = "sessionName")]
+        session_name: String,
+        panes: Vec<PaneState>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+    PaneLogStarted {
+        success: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        log: Option<crate::pane_log::PaneLog>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+    PaneLogStopped {
+        success: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        log: Option<crate::pane_log::PaneLog>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+    PaneLogsList {
+        active: Vec<crate::pane_log::PaneLog>,
+        files: Vec<crate::pane_log::LogFile>,
+    },
+    PaneLogTail {
+        #[serde(rename = "sessionName")]
+        session_name: String,
+        file: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        content: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+    /// Scheduled input came due and was sent, or couldn't be.
+    ScheduledInputSent {
+        input: crate::scheduled_input::ScheduledInput,
+        success: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+    ResizePolicy {
+        #[serde(rename = "sessionName")]
+        session_name: String,
+        policy: Option<crate::resize_policy::ResizePolicy>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+    /// The size tmux settled on for a session, broadcast whenever it may
+    /// have changed so clients can letterbox.
+    SessionSize {
+        #[serde(rename = "sessionName")]
+        session_name: String,
+        cols: u16,
+        rows: u16,
+    },
+    Thumbnails {
+        thumbnails: Vec<crate::thumbnails::Thumbnail>,
+        removed: Vec<crate::thumbnails::WindowKey>,
+    },
+    PaneExported {
+        format: crate::export::ExportFormat,
+        filename: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        content: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ignore_size_attachment_keeps_ignoring_after_reactivation() {
+        let watcher = AttachOptions { read_only: false, ignore_size: true };
+        assert!(watcher.ignores_size(true));
+        assert!(watcher.ignores_size(false));
+
+        let sizing = AttachOptions::default();
+        assert!(sizing.ignores_size(true));
+        assert!(!sizing.ignores_size(false));
+    }
 }
@@ -0,0 +1,426 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, Notify, RwLock};
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use crate::{
+    control::{PaneEvent, PaneLocation, CONTROL_HUB},
+    export::strip_ansi,
+    storage,
+    terminal_buffer::Utf8StreamDecoder,
+    tmux,
+    types::{PaneTarget, ServerMessage},
+};
+
+const MAX_HISTORY: usize = 200;
+/// Longest line kept for matching; the rest of a very long line is ignored.
+const MAX_LINE_CHARS: usize = 4096;
+const COMMAND_TIMEOUT_SECS: &str = "60";
+/// Shortest cooldown of a send-keys rule. Keys echoed back by the pane can
+/// match the rule again, so without one it would fire on every echo.
+const MIN_SEND_KEYS_COOLDOWN_SECS: u32 = 1;
+
+/// What to do when a rule matches.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum WatchAction {
+    /// Only broadcast the match to clients.
+    Notify,
+    /// Run a shell command, with the match in `WEBMUX_MATCH`.
+    Command { command: String },
+    /// Type into the pane that matched.
+    SendKeys { keys: String },
+}
+
+/// A regex watched for in a pane's output. A target without a window or
+/// pane index covers every window or pane below it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WatchRule {
+    #[serde(default)]
+    pub id: String,
+    pub name: Option<String>,
+    #[serde(flatten)]
+    pub target: PaneTarget,
+    pub pattern: String,
+    #[serde(default)]
+    pub case_insensitive: bool,
+    pub action: WatchAction,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Minimum seconds between two firings of the rule; at least
+    /// `MIN_SEND_KEYS_COOLDOWN_SECS` for send-keys rules.
+    #[serde(default)]
+    pub cooldown_seconds: u32,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl WatchRule {
+    fn compile(&self) -> Result<Regex> {
+        Ok(RegexBuilder::new(&self.pattern)
+            .case_insensitive(self.case_insensitive)
+            .build()?)
+    }
+
+    /// Time the rule waits before firing again. Rules saved before
+    /// send-keys needed a cooldown get the minimum.
+    fn cooldown(&self) -> Duration {
+        let seconds = match self.action {
+            WatchAction::SendKeys { .. } => self.cooldown_seconds.max(MIN_SEND_KEYS_COOLDOWN_SECS),
+            _ => self.cooldown_seconds,
+        };
+        Duration::from_secs(seconds as u64)
+    }
+
+    fn covers(&self, pane: &PaneLocation) -> bool {
+        self.target.session_name == pane.session_name
+            && self.target.window_index.is_none_or(|w| w == pane.window_index)
+            && self.target.pane_index.is_none_or(|p| p == pane.pane_index)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WatchMatch {
+    pub rule_id: String,
+    pub rule_name: Option<String>,
+    pub session_name: String,
+    pub window_index: u32,
+    pub pane_index: u32,
+    /// The output line that matched, escape sequences removed.
+    pub line: String,
+    pub matched_at: DateTime<Utc>,
+    pub action: WatchAction,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+struct CompiledRule {
+    rule: WatchRule,
+    regex: Regex,
+}
+
+/// Splits a pane's output into lines for matching. The unfinished last line
+/// is tested as well, so prompts without a newline match; a rule fires at
+/// most once per line.
+#[derive(Default)]
+struct LineMatcher {
+    current: String,
+    fired: HashSet<String>,
+}
+
+impl LineMatcher {
+    fn feed<'a>(&mut self, text: &str, rules: &[&'a CompiledRule]) -> Vec<(&'a CompiledRule, String)> {
+        let mut matches = Vec::new();
+        for c in text.chars() {
+            match c {
+                '\n' => {
+                    self.test(rules, &mut matches);
+                    self.current.clear();
+                    self.fired.clear();
+                }
+                '\r' => {}
+                _ if self.current.len() < MAX_LINE_CHARS => self.current.push(c),
+                _ => {}
+            }
+        }
+        if !self.current.is_empty() {
+            self.test(rules, &mut matches);
+        }
+        matches
+    }
+
+    fn test<'a>(&mut self, rules: &[&'a CompiledRule], matches: &mut Vec<(&'a CompiledRule, String)>) {
+        let line = strip_ansi(&self.current);
+        for rule in rules {
+            if !self.fired.contains(&rule.rule.id) && rule.regex.is_match(&line) {
+                self.fired.insert(rule.rule.id.clone());
+                matches.push((rule, line.trim_end().to_string()));
+            }
+        }
+    }
+}
+
+/// Watch rules, kept in `~/.webmux/watches/rules.json`, and recent matches.
+pub struct WatchManager {
+    rules: RwLock<Vec<WatchRule>>,
+    history: RwLock<VecDeque<WatchMatch>>,
+    rules_changed: Notify,
+}
+
+impl WatchManager {
+    pub fn new() -> Self {
+        Self {
+            rules: RwLock::new(Vec::new()),
+            history: RwLock::new(VecDeque::new()),
+            rules_changed: Notify::new(),
+        }
+    }
+
+    pub async fn initialize(&self) -> Result<()> {
+        let rules: Vec<WatchRule> = storage::load_json(&rules_path()?)?;
+        *self.rules.write().await = rules;
+        Ok(())
+    }
+
+    pub async fn list_rules(&self) -> Vec<WatchRule> {
+        self.rules.read().await.clone()
+    }
+
+    /// Add a rule, or replace the one with the same id.
+    pub async fn save_rule(&self, mut rule: WatchRule) -> Result<WatchRule> {
+        if rule.target.session_name.trim().is_empty() {
+            anyhow::bail!("Session name cannot be empty");
+        }
+        rule.compile()?;
+        match &rule.action {
+            WatchAction::Command { command } if command.trim().is_empty() => {
+                anyhow::bail!("Command cannot be empty");
+            }
+            WatchAction::SendKeys { .. } if rule.cooldown_seconds < MIN_SEND_KEYS_COOLDOWN_SECS => {
+                anyhow::bail!("Send-keys watches need a cooldown of at least {} second(s)", MIN_SEND_KEYS_COOLDOWN_SECS);
+            }
+            _ => {}
+        }
+
+        let mut rules = self.rules.write().await;
+        if rule.id.is_empty() {
+            rule.id = Uuid::new_v4().to_string();
+            rules.push(rule.clone());
+        } else {
+            let existing = rules
+                .iter_mut()
+                .find(|r| r.id == rule.id)
+                .ok_or_else(|| anyhow::anyhow!("Watch not found: {}", rule.id))?;
+            *existing = rule.clone();
+        }
+        storage::save_json(&rules_path()?, &*rules)?;
+        drop(rules);
+        self.rules_changed.notify_one();
+        info!("Saved watch {} on {}", rule.id, rule.target.tmux_target());
+        Ok(rule)
+    }
+
+    pub async fn delete_rule(&self, id: &str) -> Result<()> {
+        let mut rules = self.rules.write().await;
+        let before = rules.len();
+        rules.retain(|r| r.id != id);
+        if rules.len() == before {
+            anyhow::bail!("Watch not found: {}", id);
+        }
+        storage::save_json(&rules_path()?, &*rules)?;
+        drop(rules);
+        self.rules_changed.notify_one();
+        Ok(())
+    }
+
+    /// Recent matches, newest first.
+    pub async fn history(&self) -> Vec<WatchMatch> {
+        self.history.read().await.iter().rev().cloned().collect()
+    }
+
+    pub async fn clear_history(&self) {
+        self.history.write().await.clear();
+    }
+
+    /// Follow the output of every session with enabled rules through the
+    /// control hub, and act on matches.
+    pub async fn run(&self, broadcast_tx: mpsc::UnboundedSender<ServerMessage>) {
+        let mut subscription = CONTROL_HUB.subscribe(HashSet::new()).await;
+        let mut compiled: Vec<CompiledRule> = Vec::new();
+        let mut decoders: HashMap<String, (Utf8StreamDecoder, LineMatcher)> = HashMap::new();
+        let mut last_fired: HashMap<String, Instant> = HashMap::new();
+        let mut reload = true;
+
+        loop {
+            if reload {
+                compiled = self
+                    .list_rules()
+                    .await
+                    .into_iter()
+                    .filter(|rule| rule.enabled)
+                    .filter_map(|rule| match rule.compile() {
+                        Ok(regex) => Some(CompiledRule { rule, regex }),
+                        Err(e) => {
+                            warn!("Skipping watch {} with bad pattern: {}", rule.id, e);
+                            None
+                        }
+                    })
+                    .collect();
+                last_fired.retain(|id, _| compiled.iter().any(|c| c.rule.id == *id));
+                let wanted: HashSet<String> = compiled
+                    .iter()
+                    .map(|c| c.rule.target.session_name.clone())
+                    .collect();
+                subscription.set_sessions(wanted).await;
+                reload = false;
+            }
+
+            let event = tokio::select! {
+                _ = self.rules_changed.notified() => {
+                    reload = true;
+                    continue;
+                }
+                Some(event) = subscription.events.recv() => event,
+            };
+
+            match event {
+                PaneEvent::Output { pane_id, location, data } => {
+                    let rules: Vec<&CompiledRule> = compiled.iter().filter(|c| c.rule.covers(&location)).collect();
+                    let (decoder, matcher) = decoders
+                        .entry(pane_id.clone())
+                        .or_insert_with(|| (Utf8StreamDecoder::new(), LineMatcher::default()));
+                    let (text, _) = decoder.decode_chunk(&data);
+                    if rules.is_empty() {
+                        continue;
+                    }
+
+                    for (rule, line) in matcher.feed(&text, &rules) {
+                        let rule = &rule.rule;
+                        if last_fired.get(&rule.id).is_some_and(|at| at.elapsed() < rule.cooldown()) {
+                            continue;
+                        }
+                        last_fired.insert(rule.id.clone(), Instant::now());
+
+                        let error = perform(&rule.action, &pane_id, &location, &line).await.err();
+                        let watch_match = WatchMatch {
+                            rule_id: rule.id.clone(),
+                            rule_name: rule.name.clone(),
+                            session_name: location.session_name.clone(),
+                            window_index: location.window_index,
+                            pane_index: location.pane_index,
+                            line,
+                            matched_at: Utc::now(),
+                            action: rule.action.clone(),
+                            error: error.map(|e| e.to_string()),
+                        };
+                        {
+                            let mut history = self.history.write().await;
+                            history.push_back(watch_match.clone());
+                            if history.len() > MAX_HISTORY {
+                                history.pop_front();
+                            }
+                        }
+                        let _ = broadcast_tx.send(ServerMessage::WatchTriggered { watch_match });
+                    }
+                }
+                PaneEvent::PaneClosed(pane_id) => {
+                    decoders.remove(&pane_id);
+                }
+                PaneEvent::SessionClosed(session_name) => {
+                    debug!("Stopped watching session {}", session_name);
+                }
+            }
+        }
+    }
+}
+
+async fn perform(action: &WatchAction, pane_id: &str, location: &PaneLocation, line: &str) -> Result<()> {
+    match action {
+        WatchAction::Notify => Ok(()),
+        WatchAction::SendKeys { keys } => tmux::send_bytes(pane_id, keys.as_bytes()).await,
+        WatchAction::Command { command } => {
+            let mut child = tokio::process::Command::new("timeout")
+                .args([COMMAND_TIMEOUT_SECS, "sh", "-c", command])
+                .env("WEBMUX_SESSION", &location.session_name)
+                .env("WEBMUX_WINDOW", location.window_index.to_string())
+                .env("WEBMUX_PANE", location.pane_index.to_string())
+                .env("WEBMUX_MATCH", line)
+                .stdin(std::process::Stdio::null())
+                .stdout(std::process::Stdio::null())
+                .stderr(std::process::Stdio::null())
+                .spawn()?;
+            // Don't hold up matching while the command runs
+            tokio::spawn(async move {
+                let _ = child.wait().await;
+            });
+            Ok(())
+        }
+    }
+}
+
+fn rules_path() -> Result<PathBuf> {
+    Ok(storage::data_dir("watches")?.join("rules.json"))
+}
+
+lazy_static::lazy_static! {
+    pub static ref WATCH_MANAGER: WatchManager = WatchManager::new();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(id: &str, pattern: &str) -> CompiledRule {
+        let rule = WatchRule {
+            id: id.into(),
+            name: None,
+            target: PaneTarget {
+                session_name: "work".into(),
+                window_index: Some(1),
+                pane_index: None,
+            },
+            pattern: pattern.into(),
+            case_insensitive: true,
+            action: WatchAction::Notify,
+            enabled: true,
+            cooldown_seconds: 0,
+        };
+        let regex = rule.compile().unwrap();
+        CompiledRule { rule, regex }
+    }
+
+    #[test]
+    fn matches_lines_split_across_chunks_once() {
+        let error = rule("error", r"^error:");
+        let rules = [&error];
+        let mut matcher = LineMatcher::default();
+
+        assert!(matcher.feed("ok\r\nerr", &rules).is_empty());
+        let matches = matcher.feed("or: \x1b[31mboom\x1b[0m\r\nERROR: again\n", &rules);
+        let lines: Vec<&str> = matches.iter().map(|(_, line)| line.as_str()).collect();
+        assert_eq!(lines, vec!["error: boom", "ERROR: again"]);
+    }
+
+    #[test]
+    fn prompts_match_before_the_newline() {
+        let password = rule("password", r"password:\s*$");
+        let rules = [&password];
+        let mut matcher = LineMatcher::default();
+
+        assert_eq!(matcher.feed("[sudo] password: ", &rules).len(), 1);
+        // Typing and the newline don't fire it again
+        assert!(matcher.feed("\r\n", &rules).is_empty());
+    }
+
+    #[test]
+    fn targets_cover_windows_and_panes() {
+        let rule = rule("r", "x").rule;
+        let pane = |window_index, pane_index| PaneLocation {
+            session_name: "work".into(),
+            window_index,
+            pane_index,
+        };
+        assert!(rule.covers(&pane(1, 3)));
+        assert!(!rule.covers(&pane(2, 0)));
+    }
+
+    #[test]
+    fn send_keys_rules_always_cool_down() {
+        let mut rule = rule("r", "x").rule;
+        assert_eq!(rule.cooldown(), Duration::ZERO);
+        rule.action = WatchAction::SendKeys { keys: "y\r".into() };
+        assert_eq!(rule.cooldown(), Duration::from_secs(MIN_SEND_KEYS_COOLDOWN_SECS as u64));
+        rule.cooldown_seconds = 30;
+        assert_eq!(rule.cooldown(), Duration::from_secs(30));
+    }
+}
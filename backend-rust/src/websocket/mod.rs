@@ -2,14 +2,17 @@
 use axum::{
     extract::{
         ws::{Message, WebSocket, WebSocketUpgrade},
-        State,
+        Query, State,
     },
     response::IntoResponse,
 };
 use futures::{sink::SinkExt, stream::StreamExt};
 use portable_pty::{native_pty_system, CommandBuilder, PtySize};
 use std::{
-    sync::Arc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
     io::{Read, Write},
     collections::HashMap,
 };
@@ -20,17 +23,35 @@ use tokio::{
 use tracing::{debug, error, info};
 use uuid::Uuid;
 use bytes::Bytes;
+use serde::Deserialize;
 
 use crate::{
     audio,
+    recording,
+    resize_policy::{self, RESIZE_POLICIES},
+    screen::{self, SCREEN_REGISTRY},
+    scrollback,
     tmux,
     types::*,
     AppState,
 };
 use sysinfo::System;
 
+mod pool;
+
+use pool::{ParkedPools, WarmPool};
+
 type ClientId = String;
 
+/// Background attachments kept per client, besides the active one.
+const WARM_PTY_LIMIT: usize = 3;
+/// How long a disconnected client's attachments wait for it to reconnect.
+const RECONNECT_GRACE: std::time::Duration = std::time::Duration::from_secs(30);
+
+lazy_static::lazy_static! {
+    static ref PARKED_PTYS: ParkedPools<PtySession> = ParkedPools::new();
+}
+
 // Pre-serialized message for zero-copy broadcasting
 #[derive(Clone)]
 pub enum BroadcastMessage {
@@ -86,34 +107,131 @@ impl ClientManager {
     }
 }
 
+/// Where a PTY's output goes while it is the client's active attachment.
+#[derive(Clone)]
+struct OutputSink {
+    tx: mpsc::UnboundedSender<BroadcastMessage>,
+    diff_mode: Arc<AtomicBool>,
+}
+
 struct PtySession {
     writer: Arc<Mutex<Box<dyn Write + Send>>>,
     master: Arc<Mutex<Box<dyn portable_pty::MasterPty + Send>>>,
     reader_task: JoinHandle<()>,
     child: Arc<Mutex<Box<dyn portable_pty::Child + Send>>>,
     tmux_session: String,
+    options: AttachOptions,
+    recorder: Arc<Mutex<Option<recording::Recorder>>>,
+    screen: screen::SharedScreen,
+    /// `None` while the PTY waits in the warm pool; it keeps feeding the
+    /// screen model so it can be shown again without a respawn, but its
+    /// recording pauses until then.
+    output: Arc<std::sync::Mutex<Option<OutputSink>>>,
+}
+
+impl PtySession {
+    fn is_alive(&self) -> bool {
+        !self.reader_task.is_finished()
+    }
+
+    /// Stop sending output anywhere.
+    fn deactivate(&self) {
+        *self.output.lock().unwrap() = None;
+    }
+
+    /// Stop sending output and keep the idle tmux client from holding the
+    /// session's windows to its size while it waits in the warm pool.
+    async fn park(&self) {
+        self.deactivate();
+        self.set_ignore_size(self.options.ignores_size(true)).await;
+    }
+
+    async fn set_ignore_size(&self, ignore: bool) {
+        let pid = self.child.lock().await.process_id();
+        if let Some(pid) = pid {
+            if let Err(e) = tmux::set_client_ignore_size(pid, ignore).await {
+                debug!("Failed to update size flag of tmux client {}: {}", pid, e);
+            }
+        }
+    }
+
+    /// Route output to a client, starting with a repaint of the screen as
+    /// it is now.
+    async fn activate(&self, state: &WsState) -> anyhow::Result<()> {
+        self.set_ignore_size(self.options.ignores_size(false)).await;
+        // Holding the screen lock keeps the reader from slipping output in
+        // between the repaint and the switch. In diff mode the diff task
+        // sends a full frame for the new screen itself.
+        let screen = self.screen.lock().await;
+        let repaint = String::from_utf8_lossy(&screen.screen().state_formatted()).into_owned();
+        if let Some(recorder) = self.recorder.lock().await.as_mut() {
+            recorder.output(&repaint);
+        }
+        if !state.diff_mode.load(Ordering::Relaxed) {
+            send_message(&state.message_tx, ServerMessage::Output { data: repaint }).await?;
+        }
+        *self.output.lock().unwrap() = Some(OutputSink {
+            tx: state.message_tx.clone(),
+            diff_mode: state.diff_mode.clone(),
+        });
+        Ok(())
+    }
+}
+
+/// A recording being replayed to this client in place of a live PTY.
+struct PlaybackHandle {
+    id: String,
+    commands: mpsc::UnboundedSender<recording::player::PlayerCommand>,
+    task: JoinHandle<()>,
 }
 
 struct WsState {
     client_id: ClientId,
+    /// Stable id the page supplies so it can reclaim its PTYs after a reload.
+    client_key: Option<String>,
     current_pty: Arc<Mutex<Option<PtySession>>>,
+    warm_ptys: Arc<Mutex<WarmPool<PtySession>>>,
     current_session: Arc<Mutex<Option<String>>>,
     audio_tx: Option<mpsc::UnboundedSender<BroadcastMessage>>,
     message_tx: mpsc::UnboundedSender<BroadcastMessage>,
+    broadcast_tx: mpsc::UnboundedSender<ServerMessage>,
     chat_log_handle: Arc<Mutex<Option<JoinHandle<()>>>>,
+    playback: Arc<Mutex<Option<PlaybackHandle>>>,
+    /// Set while the client receives screen diffs instead of raw output.
+    diff_mode: Arc<AtomicBool>,
+    diff_task: Arc<Mutex<Option<JoinHandle<()>>>>,
+    thumbnails_handle: Arc<Mutex<Option<JoinHandle<()>>>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WsParams {
+    #[serde(rename = "clientKey")]
+    client_key: Option<String>,
 }
 
 pub async fn ws_handler(
     ws: WebSocketUpgrade,
+    Query(params): Query<WsParams>,
     State(state): State<Arc<AppState>>,
 ) -> impl IntoResponse {
-    ws.on_upgrade(|socket| handle_socket(socket, state))
+    let client_key = params.client_key.filter(|key| !key.is_empty());
+    ws.on_upgrade(|socket| handle_socket(socket, state, client_key))
 }
 
-async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
+async fn handle_socket(socket: WebSocket, state: Arc<AppState>, client_key: Option<String>) {
     let client_id = Uuid::new_v4().to_string();
     info!("New WebSocket connection established: {}", client_id);
 
+    // Pick up attachments left behind by this page before a reload
+    let mut warm_ptys = WarmPool::new(WARM_PTY_LIMIT);
+    if let Some(key) = &client_key {
+        for pty in PARKED_PTYS.claim(key) {
+            for evicted in warm_ptys.park(pty) {
+                shutdown_pty(evicted).await;
+            }
+        }
+    }
+
     let (mut sender, mut receiver) = socket.split();
     
     // Create channel for server messages
@@ -124,11 +242,18 @@ async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
     
     let mut ws_state = WsState {
         client_id: client_id.clone(),
+        client_key,
         current_pty: Arc::new(Mutex::new(None)),
+        warm_ptys: Arc::new(Mutex::new(warm_ptys)),
         current_session: Arc::new(Mutex::new(None)),
         audio_tx: None,
         message_tx: tx.clone(),
+        broadcast_tx: state.broadcast_tx.clone(),
         chat_log_handle: Arc::new(Mutex::new(None)),
+        playback: Arc::new(Mutex::new(None)),
+        diff_mode: Arc::new(AtomicBool::new(false)),
+        diff_task: Arc::new(Mutex::new(None)),
+        thumbnails_handle: Arc::new(Mutex::new(None)),
     };
     
     // Clone client_id for the spawned task
@@ -195,20 +320,27 @@ async fn handle_message(
             send_message(&state.message_tx, response).await?;
         }
         
-        WebSocketMessage::AttachSession { session_name, cols, rows } => {
-            info!("Attaching to session: {}", session_name);
-            attach_to_session(state, &session_name, cols, rows).await?;
+        WebSocketMessage::AttachSession { session_name, cols, rows, options } => {
+            info!("Attaching to session: {} ({:?})", session_name, options);
+            attach_to_session(state, &session_name, cols, rows, options).await?;
         }
         
         WebSocketMessage::Input { data } => {
             let pty_opt = state.current_pty.lock().await;
             if let Some(ref pty) = *pty_opt {
+                if pty.options.read_only {
+                    debug!("Read-only attachment, ignoring input");
+                    return Ok(());
+                }
                 let mut writer = pty.writer.lock().await;
                 if let Err(e) = writer.write_all(data.as_bytes()) {
                     error!("Failed to write to PTY: {}", e);
                     return Err(e.into());
                 }
                 writer.flush()?;
+                if let Some(recorder) = pty.recorder.lock().await.as_mut() {
+                    recorder.input(&data);
+                }
             } else {
                 debug!("No PTY session active, ignoring input");
             }
@@ -217,6 +349,11 @@ async fn handle_message(
         WebSocketMessage::Resize { cols, rows } => {
             let pty_opt = state.current_pty.lock().await;
             if let Some(ref pty) = *pty_opt {
+                // Log the resize ahead of the redraw it triggers
+                if let Some(recorder) = pty.recorder.lock().await.as_mut() {
+                    recorder.resize(cols, rows);
+                }
+                pty.screen.lock().await.resize(rows, cols);
                 let master = pty.master.lock().await;
                 master.resize(PtySize {
                     rows,
@@ -225,6 +362,7 @@ async fn handle_message(
                     pixel_height: 0,
                 })?;
                 debug!("Resized PTY to {}x{}", cols, rows);
+                broadcast_session_size(state, &pty.tmux_session);
             } else {
                 debug!("No PTY session active, ignoring resize");
             }
@@ -259,7 +397,9 @@ async fn handle_message(
                 drop(current_session);
                 // Need to switch sessions first
                 info!("Switching to session {} before selecting window", session_name);
-                attach_to_session(state, &session_name, 80, 24).await?;
+                // A watcher stays a watcher when switching sessions
+                let options = state.current_pty.lock().await.as_ref().map(|pty| pty.options).unwrap_or_default();
+                attach_to_session(state, &session_name, 80, 24, options).await?;
             }
             
             // Now select the window using tmux command
@@ -312,19 +452,23 @@ async fn handle_message(
         }
         
         // Session management
-        WebSocketMessage::CreateSession { name } => {
+        WebSocketMessage::CreateSession { name, options, detached } => {
             let session_name = name.unwrap_or_else(|| format!("session-{}", chrono::Utc::now().timestamp_millis()));
             info!("Creating session: {}", session_name);
             
-            match tmux::create_session(&session_name).await {
+            match tmux::create_session(&session_name, &options).await {
                 Ok(_) => {
                     info!("Successfully created session: {}", session_name);
                     let response = ServerMessage::SessionCreated {
                         success: true,
-                        session_name: Some(session_name),
+                        session_name: Some(session_name.clone()),
                         error: None,
                     };
                     send_message(&state.message_tx, response).await?;
+
+                    if detached == Some(false) {
+                        attach_to_session(state, &session_name, 80, 24, AttachOptions::default()).await?;
+                    }
                 }
                 Err(e) => {
                     error!("Failed to create session: {}", e);
@@ -389,8 +533,13 @@ async fn handle_message(
         }
         
         // Window management
-        WebSocketMessage::CreateWindow { session_name, window_name } => {
-            match tmux::create_window(&session_name, window_name.as_deref()).await {
+        WebSocketMessage::CreateWindow { session_name, window_name, options, select } => {
+            match tmux::create_window(
+                &session_name,
+                window_name.as_deref(),
+                &options,
+                select.unwrap_or(true),
+            ).await {
                 Ok(_) => {
                     let response = ServerMessage::WindowCreated {
                         success: true,
@@ -426,33 +575,869 @@ async fn handle_message(
                 }
             }
         }
-        
-        WebSocketMessage::RenameWindow { session_name, window_index, new_name } => {
-            if new_name.trim().is_empty() {
-                let response = ServerMessage::WindowRenamed {
-                    success: false,
-                    error: Some("Window name cannot be empty".to_string()),
-                };
-                send_message(&state.message_tx, response).await?;
-            } else {
-                match tmux::rename_window(&session_name, &window_index, &new_name).await {
-                    Ok(_) => {
-                        let response = ServerMessage::WindowRenamed {
-                            success: true,
-                            error: None,
-                        };
-                        send_message(&state.message_tx, response).await?;
+        
+        WebSocketMessage::RenameWindow { session_name, window_index, new_name } => {
+            if new_name.trim().is_empty() {
+                let response = ServerMessage::WindowRenamed {
+                    success: false,
+                    error: Some("Window name cannot be empty".to_string()),
+                };
+                send_message(&state.message_tx, response).await?;
+            } else {
+                match tmux::rename_window(&session_name, &window_index, &new_name).await {
+                    Ok(_) => {
+                        let response = ServerMessage::WindowRenamed {
+                            success: true,
+                            error: None,
+                        };
+                        send_message(&state.message_tx, response).await?;
+                    }
+                    Err(e) => {
+                        let response = ServerMessage::WindowRenamed {
+                            success: false,
+                            error: Some(format!("Failed to rename window: {}", e)),
+                        };
+                        send_message(&state.message_tx, response).await?;
+                    }
+                }
+            }
+        }
+        
+        WebSocketMessage::MoveWindow { session_name, window_index, target_session, target_index } => {
+            let result = match validate_window_op(state, &session_name, &target_session).await {
+                Ok(_) if session_name == target_session && target_index == Some(window_index) => {
+                    Err(anyhow::anyhow!("Window is already at that position"))
+                }
+                Ok(_) => tmux::move_window(&session_name, window_index, &target_session, target_index).await,
+                Err(e) => Err(e),
+            };
+            let response = ServerMessage::WindowMoved {
+                success: result.is_ok(),
+                error: result.as_ref().err().map(|e| format!("Failed to move window: {}", e)),
+            };
+            send_message(&state.message_tx, response).await?;
+            if result.is_ok() {
+                broadcast_windows_lists(state, &[&session_name, &target_session]).await;
+            }
+        }
+
+        WebSocketMessage::SwapWindows { session_name, window_index, target_session, target_index } => {
+            let target_session = target_session.unwrap_or_else(|| session_name.clone());
+            let result = match validate_window_op(state, &session_name, &target_session).await {
+                Ok(_) if session_name == target_session && target_index == window_index => {
+                    Err(anyhow::anyhow!("Cannot swap a window with itself"))
+                }
+                Ok(_) => tmux::swap_windows(&session_name, window_index, &target_session, target_index).await,
+                Err(e) => Err(e),
+            };
+            let response = ServerMessage::WindowsSwapped {
+                success: result.is_ok(),
+                error: result.as_ref().err().map(|e| format!("Failed to swap windows: {}", e)),
+            };
+            send_message(&state.message_tx, response).await?;
+            if result.is_ok() {
+                broadcast_windows_lists(state, &[&session_name, &target_session]).await;
+            }
+        }
+
+        WebSocketMessage::LinkWindow { session_name, window_index, target_session, target_index } => {
+            let result = match validate_window_op(state, &session_name, &target_session).await {
+                Ok(_) if session_name == target_session => {
+                    Err(anyhow::anyhow!("Target session must differ from the source session"))
+                }
+                Ok(_) => tmux::link_window(&session_name, window_index, &target_session, target_index).await,
+                Err(e) => Err(e),
+            };
+            let response = ServerMessage::WindowLinked {
+                success: result.is_ok(),
+                error: result.as_ref().err().map(|e| format!("Failed to link window: {}", e)),
+            };
+            send_message(&state.message_tx, response).await?;
+            if result.is_ok() {
+                broadcast_windows_lists(state, &[&session_name, &target_session]).await;
+            }
+        }
+
+        WebSocketMessage::UnlinkWindow { session_name, window_index } => {
+            let result = match validate_window_op(state, &session_name, &session_name).await {
+                Ok(_) => tmux::unlink_window(&session_name, window_index).await,
+                Err(e) => Err(e),
+            };
+            let response = ServerMessage::WindowUnlinked {
+                success: result.is_ok(),
+                error: result.as_ref().err().map(|e| format!("Failed to unlink window: {}", e)),
+            };
+            send_message(&state.message_tx, response).await?;
+            if result.is_ok() {
+                broadcast_windows_lists(state, &[&session_name]).await;
+            }
+        }
+
+        WebSocketMessage::RenumberWindows { session_name } => {
+            let result = match validate_window_op(state, &session_name, &session_name).await {
+                Ok(_) => tmux::renumber_windows(&session_name).await,
+                Err(e) => Err(e),
+            };
+            let response = ServerMessage::WindowsRenumbered {
+                success: result.is_ok(),
+                error: result.as_ref().err().map(|e| format!("Failed to renumber windows: {}", e)),
+            };
+            send_message(&state.message_tx, response).await?;
+            if result.is_ok() {
+                broadcast_windows_lists(state, &[&session_name]).await;
+            }
+        }
+
+        // Input broadcasting
+        WebSocketMessage::BroadcastInput { targets, data } => {
+            let mut delivered = 0;
+            let mut failed = Vec::new();
+            let mut seen = Vec::new();
+            for target in targets {
+                let key = target.tmux_target();
+                if seen.contains(&key) {
+                    continue;
+                }
+                seen.push(key);
+
+                let result = match check_writable(state, &target.session_name).await {
+                    Ok(()) => tmux::send_input(&target, data.as_bytes()).await,
+                    Err(e) => Err(e),
+                };
+                match result {
+                    Ok(_) => delivered += 1,
+                    Err(e) => failed.push(BroadcastFailure {
+                        target,
+                        error: e.to_string(),
+                    }),
+                }
+            }
+            if !failed.is_empty() {
+                debug!("Broadcast input failed for {} target(s)", failed.len());
+            }
+            let response = ServerMessage::InputBroadcast {
+                success: failed.is_empty(),
+                delivered,
+                failed,
+            };
+            send_message(&state.message_tx, response).await?;
+        }
+
+        WebSocketMessage::Paste { id, data, target, bracketed } => {
+            let id = id.unwrap_or_else(|| Uuid::new_v4().to_string());
+            let total = data.len();
+            let result = paste(state, &id, data, target, bracketed.unwrap_or(true)).await;
+            if let Err(e) = &result {
+                debug!("Paste {} failed: {}", id, e);
+            }
+            let response = ServerMessage::Pasted {
+                id,
+                success: result.is_ok(),
+                bytes: total,
+                error: result.err().map(|e| e.to_string()),
+            };
+            send_message(&state.message_tx, response).await?;
+        }
+
+        // Paste buffers
+        WebSocketMessage::ListBuffers => {
+            match tmux::list_buffers().await {
+                Ok(buffers) => {
+                    send_message(&state.message_tx, ServerMessage::BuffersList { buffers }).await?;
+                }
+                Err(e) => {
+                    let response = ServerMessage::Error {
+                        message: format!("Failed to list buffers: {}", e),
+                    };
+                    send_message(&state.message_tx, response).await?;
+                }
+            }
+        }
+
+        WebSocketMessage::ShowBuffer { name } => {
+            let response = match tmux::show_buffer(&name).await {
+                Ok(content) => ServerMessage::BufferContent {
+                    name,
+                    content: Some(content),
+                    error: None,
+                },
+                Err(e) => ServerMessage::BufferContent {
+                    name,
+                    content: None,
+                    error: Some(e.to_string()),
+                },
+            };
+            send_message(&state.message_tx, response).await?;
+        }
+
+        WebSocketMessage::SetBuffer { name, content } => {
+            let result = if content.len() > MAX_PASTE_BYTES {
+                Err(anyhow::anyhow!("Buffer is larger than {} MB", MAX_PASTE_BYTES / (1024 * 1024)))
+            } else if let Err(e) = check_not_read_only(state).await {
+                Err(e)
+            } else {
+                tmux::load_buffer(name.as_deref(), content.as_bytes(), |_| {}).await
+            };
+            let response = ServerMessage::BufferSet {
+                success: result.is_ok(),
+                error: result.err().map(|e| e.to_string()),
+            };
+            send_message(&state.message_tx, response).await?;
+        }
+
+        WebSocketMessage::DeleteBuffer { name } => {
+            let result = match check_not_read_only(state).await {
+                Ok(()) => tmux::delete_buffer(&name).await,
+                Err(e) => Err(e),
+            };
+            let response = ServerMessage::BufferDeleted {
+                name,
+                success: result.is_ok(),
+                error: result.err().map(|e| e.to_string()),
+            };
+            send_message(&state.message_tx, response).await?;
+        }
+
+        WebSocketMessage::SetSynchronizePanes { session_name, window_index, enabled } => {
+            let result = match validate_window_op(state, &session_name, &session_name).await {
+                Ok(_) => tmux::set_synchronize_panes(&session_name, window_index, enabled).await,
+                Err(e) => Err(e),
+            };
+            let response = ServerMessage::SynchronizePanesSet {
+                success: result.is_ok(),
+                error: result.as_ref().err().map(|e| format!("Failed to set synchronize-panes: {}", e)),
+            };
+            send_message(&state.message_tx, response).await?;
+            if result.is_ok() {
+                broadcast_windows_lists(state, &[&session_name]).await;
+            }
+        }
+
+        // Scrollback
+        WebSocketMessage::GetScrollback { target, start, end, escapes } => {
+            let result = scrollback::get_range(&target, start, end, escapes).await;
+            let response = ServerMessage::Scrollback {
+                target,
+                error: result.as_ref().err().map(|e| e.to_string()),
+                range: result.ok(),
+            };
+            send_message(&state.message_tx, response).await?;
+        }
+
+        WebSocketMessage::SearchScrollback { pattern, target, options } => {
+            let result = scrollback::search(&pattern, target.as_ref(), &options).await;
+            let response = ServerMessage::ScrollbackSearchResults {
+                pattern,
+                error: result.as_ref().err().map(|e| e.to_string()),
+                results: result.ok(),
+            };
+            send_message(&state.message_tx, response).await?;
+        }
+
+        // Session recording
+        WebSocketMessage::StartRecording { title, record_input } => {
+            let pty_opt = state.current_pty.lock().await;
+            let result = match pty_opt.as_ref() {
+                Some(pty) => {
+                    let mut recorder = pty.recorder.lock().await;
+                    if recorder.is_some() {
+                        Err(anyhow::anyhow!("Already recording this session"))
+                    } else {
+                        let size = pty.master.lock().await.get_size()?;
+                        recording::Recorder::start(&pty.tmux_session, title, size.cols, size.rows, record_input)
+                            .map(|r| recorder.insert(r).info().clone())
+                    }
+                }
+                None => Err(anyhow::anyhow!("Not attached to a session")),
+            };
+            drop(pty_opt);
+            let response = ServerMessage::RecordingStarted {
+                success: result.is_ok(),
+                error: result.as_ref().err().map(|e| format!("Failed to start recording: {}", e)),
+                recording: result.ok(),
+            };
+            send_message(&state.message_tx, response).await?;
+        }
+
+        WebSocketMessage::StopRecording => {
+            let pty_opt = state.current_pty.lock().await;
+            let recorder = match pty_opt.as_ref() {
+                Some(pty) => pty.recorder.lock().await.take(),
+                None => None,
+            };
+            drop(pty_opt);
+            let result = match recorder {
+                Some(recorder) => recorder.finish(),
+                None => Err(anyhow::anyhow!("No recording in progress")),
+            };
+            let response = ServerMessage::RecordingStopped {
+                success: result.is_ok(),
+                error: result.as_ref().err().map(|e| format!("Failed to stop recording: {}", e)),
+                recording: result.ok(),
+            };
+            send_message(&state.message_tx, response).await?;
+        }
+
+        WebSocketMessage::ListRecordings => {
+            let recordings = recording::list_recordings().unwrap_or_else(|e| {
+                error!("Failed to list recordings: {}", e);
+                Vec::new()
+            });
+            let response = ServerMessage::RecordingsList { recordings };
+            send_message(&state.message_tx, response).await?;
+        }
+
+        WebSocketMessage::GetRecording { id } => {
+            let result = recording::read_recording(&id);
+            let response = ServerMessage::RecordingContent {
+                id,
+                error: result.as_ref().err().map(|e| e.to_string()),
+                content: result.ok(),
+            };
+            send_message(&state.message_tx, response).await?;
+        }
+
+        WebSocketMessage::DeleteRecording { id } => {
+            let result = recording::delete_recording(&id);
+            let response = ServerMessage::RecordingDeleted {
+                id,
+                success: result.is_ok(),
+                error: result.err().map(|e| e.to_string()),
+            };
+            send_message(&state.message_tx, response).await?;
+        }
+
+        // Recording playback
+        WebSocketMessage::PlayRecording { id, speed, idle_time_limit, start } => {
+            let cast = recording::read_recording(&id)
+                .and_then(|content| recording::player::parse_cast(&content, idle_time_limit));
+            let cast = match cast {
+                Ok(cast) => cast,
+                Err(e) => {
+                    let response = ServerMessage::Error {
+                        message: format!("Failed to play recording: {}", e),
+                    };
+                    send_message(&state.message_tx, response).await?;
+                    return Ok(());
+                }
+            };
+
+            // Playback takes over the terminal, so drop the live PTY first
+            stop_playback(state).await;
+            close_pty(state).await;
+
+            let (command_tx, command_rx) = mpsc::unbounded_channel();
+            let (event_tx, mut event_rx) = mpsc::unbounded_channel();
+            let speed = clamp_playback_speed(speed.unwrap_or(1.0));
+            tokio::spawn(recording::player::run(
+                id.clone(),
+                cast,
+                start.unwrap_or(0.0),
+                speed,
+                command_rx,
+                event_tx,
+            ));
+
+            // Forward player events until the player stops
+            let message_tx = state.message_tx.clone();
+            let task = tokio::spawn(async move {
+                while let Some(event) = event_rx.recv().await {
+                    let msg = match event {
+                        recording::player::PlayerEvent::Output(data) => ServerMessage::Output { data },
+                        recording::player::PlayerEvent::Status(status) => {
+                            ServerMessage::PlaybackStatus { status }
+                        }
+                    };
+                    if send_message(&message_tx, msg).await.is_err() {
+                        break;
+                    }
+                }
+            });
+
+            *state.playback.lock().await = Some(PlaybackHandle {
+                id,
+                commands: command_tx,
+                task,
+            });
+        }
+
+        WebSocketMessage::PlaybackControl { action, position, speed } => {
+            use recording::player::{PlaybackAction, PlayerCommand};
+
+            if action == PlaybackAction::Stop {
+                stop_playback(state).await;
+                return Ok(());
+            }
+
+            let command = match action {
+                PlaybackAction::Pause => Some(PlayerCommand::Pause),
+                PlaybackAction::Resume => Some(PlayerCommand::Resume),
+                PlaybackAction::Seek => position.map(PlayerCommand::Seek),
+                PlaybackAction::SetSpeed => speed.map(|s| PlayerCommand::SetSpeed(clamp_playback_speed(s))),
+                PlaybackAction::Stop => None,
+            };
+            let error = match (command, state.playback.lock().await.as_ref()) {
+                (None, _) => Some("Seek needs a position and set-speed needs a speed".to_string()),
+                (_, None) => Some("No recording is playing".to_string()),
+                (Some(command), Some(playback)) => playback
+                    .commands
+                    .send(command)
+                    .err()
+                    .map(|_| "Playback has ended".to_string()),
+            };
+            if let Some(message) = error {
+                send_message(&state.message_tx, ServerMessage::Error { message }).await?;
+            }
+        }
+
+        // Screen model
+        WebSocketMessage::GetScreen { session_name, formatted } => {
+            let attachment = match &session_name {
+                Some(name) => SCREEN_REGISTRY.for_session(name, None).await.map(|s| (name.clone(), s)),
+                None => SCREEN_REGISTRY.for_client(&state.client_id).await,
+            };
+            let response = match attachment {
+                Some((name, screen)) => ServerMessage::ScreenSnapshot {
+                    session_name: Some(name),
+                    snapshot: Some(screen.lock().await.snapshot(formatted)),
+                    error: None,
+                },
+                None => ServerMessage::ScreenSnapshot {
+                    error: Some(match &session_name {
+                        Some(name) => format!("No client is attached to session {}", name),
+                        None => "Not attached to a session".to_string(),
+                    }),
+                    session_name,
+                    snapshot: None,
+                },
+            };
+            send_message(&state.message_tx, response).await?;
+        }
+
+        // Output streaming mode
+        WebSocketMessage::SetStreamMode { mode, fps } => {
+            if let Some(task) = state.diff_task.lock().await.take() {
+                task.abort();
+            }
+
+            let mut error = None;
+            let fps = match mode {
+                StreamMode::Diff => {
+                    let fps = fps.unwrap_or(DEFAULT_DIFF_FPS).clamp(1, MAX_DIFF_FPS);
+                    state.diff_mode.store(true, Ordering::Relaxed);
+                    *state.diff_task.lock().await = Some(spawn_screen_diff(state, fps));
+                    Some(fps)
+                }
+                StreamMode::Raw => {
+                    state.diff_mode.store(false, Ordering::Relaxed);
+                    // The client's screen came from diffs; have tmux repaint
+                    // it so the raw stream continues from a known state
+                    let pid = match state.current_pty.lock().await.as_ref() {
+                        Some(pty) => pty.child.lock().await.process_id(),
+                        None => None,
+                    };
+                    if let Some(pid) = pid {
+                        if let Err(e) = tmux::refresh_client_by_pid(pid).await {
+                            error = Some(format!("Failed to redraw terminal: {}", e));
+                        }
+                    }
+                    None
+                }
+            };
+            let response = ServerMessage::StreamModeChanged { mode, fps, error };
+            send_message(&state.message_tx, response).await?;
+        }
+
+        // Window alerts
+        WebSocketMessage::GetAlertSettings => {
+            let settings = crate::alerts::ALERT_MANAGER.get_settings().await;
+            send_message(&state.message_tx, ServerMessage::AlertSettings { settings }).await?;
+        }
+
+        WebSocketMessage::SetWindowAlerts { settings } => {
+            let result = match check_writable(state, &settings.session_name).await {
+                Ok(()) => crate::alerts::ALERT_MANAGER.set_window(settings).await,
+                Err(e) => Err(e),
+            };
+            match result {
+                Ok(settings) => {
+                    send_message(&state.message_tx, ServerMessage::AlertSettings { settings }).await?;
+                }
+                Err(e) => {
+                    let response = ServerMessage::Error {
+                        message: format!("Failed to update alert settings: {}", e),
+                    };
+                    send_message(&state.message_tx, response).await?;
+                }
+            }
+        }
+
+        // Output watches
+        WebSocketMessage::ListWatches => {
+            let rules = crate::watches::WATCH_MANAGER.list_rules().await;
+            send_message(&state.message_tx, ServerMessage::WatchesList { rules }).await?;
+        }
+
+        WebSocketMessage::SaveWatch { rule } => {
+            let result = match rule.action {
+                crate::watches::WatchAction::SendKeys { .. } => check_writable(state, &rule.target.session_name).await,
+                _ => Ok(()),
+            };
+            let result = match result {
+                Ok(()) => crate::watches::WATCH_MANAGER.save_rule(rule).await,
+                Err(e) => Err(e),
+            };
+            let response = match result {
+                Ok(rule) => ServerMessage::WatchSaved {
+                    success: true,
+                    rule: Some(rule),
+                    error: None,
+                },
+                Err(e) => ServerMessage::WatchSaved {
+                    success: false,
+                    rule: None,
+                    error: Some(e.to_string()),
+                },
+            };
+            send_message(&state.message_tx, response).await?;
+        }
+
+        WebSocketMessage::DeleteWatch { id } => {
+            let result = crate::watches::WATCH_MANAGER.delete_rule(&id).await;
+            let response = ServerMessage::WatchDeleted {
+                id,
+                success: result.is_ok(),
+                error: result.err().map(|e| e.to_string()),
+            };
+            send_message(&state.message_tx, response).await?;
+        }
+
+        WebSocketMessage::GetWatchHistory => {
+            let matches = crate::watches::WATCH_MANAGER.history().await;
+            send_message(&state.message_tx, ServerMessage::WatchHistory { matches }).await?;
+        }
+
+        WebSocketMessage::ClearWatchHistory => {
+            crate::watches::WATCH_MANAGER.clear_history().await;
+            send_message(&state.message_tx, ServerMessage::WatchHistory { matches: Vec::new() }).await?;
+        }
+
+        // Automation scripts
+        WebSocketMessage::RunAutomation { id, script } => {
+            let run_id = id.unwrap_or_else(|| Uuid::new_v4().to_string());
+            let message_tx = state.message_tx.clone();
+            let report = move |status: &crate::automation::RunStatus| {
+                let message = ServerMessage::AutomationStatus { status: status.clone() };
+                if let Ok(json) = serde_json::to_string(&message) {
+                    let _ = message_tx.send(BroadcastMessage::Text(Arc::new(json)));
+                }
+            };
+            let result = match check_writable(state, &script.target.session_name).await {
+                Ok(()) => crate::automation::AUTOMATIONS.start(run_id.clone(), script.clone(), report).await,
+                Err(e) => Err(e),
+            };
+            if let Err(e) = result {
+                let status = crate::automation::RunStatus::rejected(run_id, &script, e.to_string());
+                send_message(&state.message_tx, ServerMessage::AutomationStatus { status }).await?;
+            }
+        }
+
+        WebSocketMessage::StopAutomation { run_id } => {
+            match crate::automation::AUTOMATIONS.stop(&run_id).await {
+                Ok(status) => {
+                    send_message(&state.message_tx, ServerMessage::AutomationStatus { status }).await?;
+                }
+                Err(e) => {
+                    let response = ServerMessage::Error {
+                        message: format!("Failed to stop automation: {}", e),
+                    };
+                    send_message(&state.message_tx, response).await?;
+                }
+            }
+        }
+
+        WebSocketMessage::ListAutomations => {
+            let runs = crate::automation::AUTOMATIONS.list().await;
+            send_message(&state.message_tx, ServerMessage::AutomationRuns { runs }).await?;
+        }
+
+        // One-off scheduled input
+        WebSocketMessage::ScheduleInput { name, target, text, enter, run_at, delay_seconds } => {
+            let run_at = match (run_at, delay_seconds) {
+                (Some(run_at), None) => Ok(run_at),
+                (None, Some(delay)) => crate::scheduled_input::run_at_after(delay),
+                _ => Err(anyhow::anyhow!("Specify either runAt or delaySeconds")),
+            };
+            let run_at = match check_writable(state, &target.session_name).await {
+                Ok(()) => run_at,
+                Err(e) => Err(e),
+            };
+            let result = match run_at {
+                Ok(run_at) => {
+                    crate::scheduled_input::SCHEDULED_INPUTS
+                        .schedule(name, target, text, enter.unwrap_or(true), run_at)
+                        .await
+                }
+                Err(e) => Err(e),
+            };
+            let response = match result {
+                Ok(input) => ServerMessage::InputScheduled {
+                    success: true,
+                    input: Some(input),
+                    error: None,
+                },
+                Err(e) => ServerMessage::InputScheduled {
+                    success: false,
+                    input: None,
+                    error: Some(e.to_string()),
+                },
+            };
+            send_message(&state.message_tx, response).await?;
+        }
+
+        WebSocketMessage::ListScheduledInputs => {
+            let inputs = crate::scheduled_input::SCHEDULED_INPUTS.list().await;
+            send_message(&state.message_tx, ServerMessage::ScheduledInputsList { inputs }).await?;
+        }
+
+        WebSocketMessage::CancelScheduledInput { id } => {
+            let inputs = crate::scheduled_input::SCHEDULED_INPUTS.list().await;
+            let session = inputs.iter().find(|input| input.id == id).map(|input| &input.target.session_name);
+            let result = match session {
+                Some(session) => check_writable(state, session).await,
+                None => Ok(()),
+            };
+            let result = match result {
+                Ok(()) => crate::scheduled_input::SCHEDULED_INPUTS.cancel(&id).await,
+                Err(e) => Err(e),
+            };
+            let response = ServerMessage::ScheduledInputCancelled {
+                id,
+                success: result.is_ok(),
+                error: result.err().map(|e| e.to_string()),
+            };
+            send_message(&state.message_tx, response).await?;
+        }
+
+        // Shell integration
+        WebSocketMessage::GetShellIntegration => {
+            let sessions = crate::shell_integration::SHELL_INTEGRATION.sessions().await;
+            send_message(&state.message_tx, ServerMessage::ShellIntegration { sessions }).await?;
+        }
+
+        WebSocketMessage::SetShellIntegration { session_name, enabled } => {
+            match crate::shell_integration::SHELL_INTEGRATION.set_enabled(&session_name, enabled).await {
+                Ok(sessions) => {
+                    send_message(&state.message_tx, ServerMessage::ShellIntegration { sessions }).await?;
+                }
+                Err(e) => {
+                    let response = ServerMessage::Error {
+                        message: format!("Failed to update shell integration: {}", e),
+                    };
+                    send_message(&state.message_tx, response).await?;
+                }
+            }
+        }
+
+        WebSocketMessage::GetCommandHistory { session_name, window_index, pane_index, limit } => {
+            let commands = crate::shell_integration::SHELL_INTEGRATION
+                .history(&session_name, window_index, pane_index, limit)
+                .await;
+            send_message(&state.message_tx, ServerMessage::CommandHistory { commands }).await?;
+        }
+
+        // Process inspector
+        WebSocketMessage::InspectPane { target, mask_secrets } => {
+            let result = crate::procinfo::inspect_pane(&target, mask_secrets.unwrap_or(true)).await;
+            let response = ServerMessage::PaneProcesses {
+                target,
+                success: result.is_ok(),
+                error: result.as_ref().err().map(|e| e.to_string()),
+                inspection: result.ok(),
+            };
+            send_message(&state.message_tx, response).await?;
+        }
+
+        // Pane process control
+        WebSocketMessage::SignalPane { target, signal, scope } => {
+            let result = match check_writable(state, &target.session_name).await {
+                Ok(()) => crate::procinfo::signal_pane(&target, signal, scope.unwrap_or_default()).await,
+                Err(e) => Err(e),
+            };
+            if let Err(e) = &result {
+                error!("Failed to send {} to {}: {}", signal.name(), target.tmux_target(), e);
+            }
+            let response = ServerMessage::PaneSignalled {
+                target,
+                signal,
+                success: result.is_ok(),
+                error: result.as_ref().err().map(|e| e.to_string()),
+                pids: result.unwrap_or_default(),
+            };
+            send_message(&state.message_tx, response).await?;
+        }
+
+        WebSocketMessage::RespawnPane { target, command } => {
+            let result = match check_writable(state, &target.session_name).await {
+                Ok(()) => tmux::respawn_pane(&target, command.as_deref().filter(|c| !c.trim().is_empty())).await,
+                Err(e) => Err(e),
+            };
+            let response = ServerMessage::PaneRespawned {
+                target,
+                success: result.is_ok(),
+                error: result.err().map(|e| e.to_string()),
+            };
+            send_message(&state.message_tx, response).await?;
+        }
+
+        WebSocketMessage::SetRemainOnExit { target, mode } => {
+            let result = match check_writable(state, &target.session_name).await {
+                Ok(()) => tmux::set_remain_on_exit(&target, mode).await,
+                Err(e) => Err(e),
+            };
+            let response = ServerMessage::RemainOnExitSet {
+                target,
+                mode,
+                success: result.is_ok(),
+                error: result.err().map(|e| e.to_string()),
+            };
+            send_message(&state.message_tx, response).await?;
+        }
+
+        WebSocketMessage::ListPaneStates { session_name, window_index } => {
+            let result = tmux::list_pane_states(&session_name, window_index).await;
+            let response = ServerMessage::PaneStates {
+                session_name,
+                error: result.as_ref().err().map(|e| e.to_string()),
+                panes: result.unwrap_or_default(),
+            };
+            send_message(&state.message_tx, response).await?;
+        }
+
+        // Pane output logs
+        WebSocketMessage::StartPaneLog { target, options } => {
+            let result = match check_writable(state, &target.session_name).await {
+                Ok(()) => crate::pane_log::PANE_LOGS.start(&target, options).await,
+                Err(e) => Err(e),
+            };
+            let response = ServerMessage::PaneLogStarted {
+                success: result.is_ok(),
+                error: result.as_ref().err().map(|e| e.to_string()),
+                log: result.ok(),
+            };
+            send_message(&state.message_tx, response).await?;
+        }
+
+        WebSocketMessage::StopPaneLog { target } => {
+            let result = match check_writable(state, &target.session_name).await {
+                Ok(()) => crate::pane_log::PANE_LOGS.stop(&target).await,
+                Err(e) => Err(e),
+            };
+            let response = ServerMessage::PaneLogStopped {
+                success: result.is_ok(),
+                error: result.as_ref().err().map(|e| e.to_string()),
+                log: result.ok(),
+            };
+            send_message(&state.message_tx, response).await?;
+        }
+
+        WebSocketMessage::ListPaneLogs => {
+            match crate::pane_log::PANE_LOGS.list_files() {
+                Ok(files) => {
+                    let active = crate::pane_log::PANE_LOGS.list_active();
+                    send_message(&state.message_tx, ServerMessage::PaneLogsList { active, files }).await?;
+                }
+                Err(e) => {
+                    let response = ServerMessage::Error {
+                        message: format!("Failed to list pane logs: {}", e),
+                    };
+                    send_message(&state.message_tx, response).await?;
+                }
+            }
+        }
+
+        WebSocketMessage::TailPaneLog { session_name, file, lines } => {
+            let result = crate::pane_log::tail(&session_name, &file, lines);
+            let response = ServerMessage::PaneLogTail {
+                session_name,
+                file,
+                error: result.as_ref().err().map(|e| e.to_string()),
+                content: result.ok(),
+            };
+            send_message(&state.message_tx, response).await?;
+        }
+
+        // Multi-client resize policy
+        WebSocketMessage::GetResizePolicy { session_name } => {
+            let policy = RESIZE_POLICIES.get(&session_name).await;
+            send_message(&state.message_tx, ServerMessage::ResizePolicy {
+                session_name,
+                policy,
+                error: None,
+            }).await?;
+        }
+
+        WebSocketMessage::SetResizePolicy { session_name, policy } => {
+            let response = match RESIZE_POLICIES.set(&session_name, policy).await {
+                Ok(()) => {
+                    broadcast_session_size(state, &session_name);
+                    ServerMessage::ResizePolicy {
+                        session_name,
+                        policy,
+                        error: None,
                     }
-                    Err(e) => {
-                        let response = ServerMessage::WindowRenamed {
-                            success: false,
-                            error: Some(format!("Failed to rename window: {}", e)),
-                        };
-                        send_message(&state.message_tx, response).await?;
+                }
+                Err(e) => ServerMessage::ResizePolicy {
+                    policy: RESIZE_POLICIES.get(&session_name).await,
+                    session_name,
+                    error: Some(e.to_string()),
+                },
+            };
+            send_message(&state.message_tx, response).await?;
+        }
+
+        // Session overview thumbnails
+        WebSocketMessage::SubscribeThumbnails { options } => {
+            let message_tx = state.message_tx.clone();
+            let handle = tokio::spawn(async move {
+                let (update_tx, mut update_rx) = mpsc::unbounded_channel();
+                let capture = tokio::spawn(crate::thumbnails::run(options, update_tx));
+
+                while let Some(update) = update_rx.recv().await {
+                    let msg = ServerMessage::Thumbnails {
+                        thumbnails: update.thumbnails,
+                        removed: update.removed,
+                    };
+                    if send_message(&message_tx, msg).await.is_err() {
+                        break;
                     }
                 }
+                capture.abort();
+            });
+
+            // A new subscription replaces the old one
+            if let Some(old) = state.thumbnails_handle.lock().await.replace(handle) {
+                old.abort();
+            }
+        }
+
+        WebSocketMessage::UnsubscribeThumbnails => {
+            if let Some(handle) = state.thumbnails_handle.lock().await.take() {
+                handle.abort();
             }
         }
+
+        // Export
+        WebSocketMessage::ExportPane { request } => {
+            let result = crate::export::export_pane(&request).await;
+            let response = ServerMessage::PaneExported {
+                format: request.format,
+                filename: request.filename(),
+                error: result.as_ref().err().map(|e| format!("Failed to export pane: {}", e)),
+                content: result.ok(),
+            };
+            send_message(&state.message_tx, response).await?;
+        }
         
         // System stats
         WebSocketMessage::GetStats => {
@@ -745,11 +1730,335 @@ async fn handle_message(
                 handle.abort();
             }
         }
+
+        // Session templates
+        WebSocketMessage::ListSessionTemplates => {
+            match crate::session_templates::list_templates() {
+                Ok(templates) => {
+                    let response = ServerMessage::SessionTemplatesList { templates };
+                    send_message(&state.message_tx, response).await?;
+                }
+                Err(e) => {
+                    let response = ServerMessage::Error {
+                        message: format!("Failed to list session templates: {}", e)
+                    };
+                    send_message(&state.message_tx, response).await?;
+                }
+            }
+        }
+
+        WebSocketMessage::GetSessionTemplate { name } => {
+            let response = match crate::session_templates::read_template(&name) {
+                Ok((content, format)) => ServerMessage::SessionTemplateContent {
+                    name,
+                    content,
+                    format: Some(format),
+                    error: None,
+                },
+                Err(e) => ServerMessage::SessionTemplateContent {
+                    name,
+                    content: String::new(),
+                    format: None,
+                    error: Some(format!("{}", e)),
+                },
+            };
+            send_message(&state.message_tx, response).await?;
+        }
+
+        WebSocketMessage::SaveSessionTemplate { content, format, original_name } => {
+            let response = match crate::session_templates::save_template(
+                &content,
+                format,
+                original_name.as_deref(),
+            ) {
+                Ok(template) => ServerMessage::SessionTemplateSaved {
+                    success: true,
+                    template: Some(template),
+                    error: None,
+                },
+                Err(e) => ServerMessage::SessionTemplateSaved {
+                    success: false,
+                    template: None,
+                    error: Some(format!("{:#}", e)),
+                },
+            };
+            send_message(&state.message_tx, response).await?;
+        }
+
+        WebSocketMessage::DeleteSessionTemplate { name } => {
+            let response = match crate::session_templates::delete_template(&name) {
+                Ok(_) => ServerMessage::SessionTemplateDeleted {
+                    name,
+                    success: true,
+                    error: None,
+                },
+                Err(e) => ServerMessage::SessionTemplateDeleted {
+                    name,
+                    success: false,
+                    error: Some(format!("{}", e)),
+                },
+            };
+            send_message(&state.message_tx, response).await?;
+        }
+
+        WebSocketMessage::InstantiateSessionTemplate { name, session_name, variables } => {
+            info!("Instantiating session template: {}", name);
+            let response = match crate::session_templates::instantiate(
+                &name,
+                session_name,
+                variables,
+            ).await {
+                Ok(session_name) => ServerMessage::SessionTemplateInstantiated {
+                    success: true,
+                    session_name: Some(session_name),
+                    error: None,
+                },
+                Err(e) => {
+                    error!("Failed to instantiate template {}: {:#}", name, e);
+                    ServerMessage::SessionTemplateInstantiated {
+                        success: false,
+                        session_name: None,
+                        error: Some(format!("{:#}", e)),
+                    }
+                }
+            };
+            send_message(&state.message_tx, response).await?;
+        }
+
+        // Session snapshots
+        WebSocketMessage::CreateSnapshot { label, include_scrollback } => {
+            let include_scrollback = match include_scrollback {
+                Some(include) => include,
+                None => crate::snapshots::SNAPSHOT_MANAGER.get_settings().await.include_scrollback,
+            };
+            let response = match crate::snapshots::take_snapshot(
+                label,
+                crate::snapshots::SnapshotTrigger::Manual,
+                include_scrollback,
+            ).await {
+                Ok(snapshot) => ServerMessage::SnapshotCreated {
+                    success: true,
+                    snapshot: Some((&snapshot).into()),
+                    error: None,
+                },
+                Err(e) => ServerMessage::SnapshotCreated {
+                    success: false,
+                    snapshot: None,
+                    error: Some(format!("Failed to create snapshot: {:#}", e)),
+                },
+            };
+            send_message(&state.message_tx, response).await?;
+        }
+
+        WebSocketMessage::ListSnapshots => {
+            match crate::snapshots::list_snapshots() {
+                Ok(snapshots) => {
+                    let response = ServerMessage::SnapshotsList { snapshots };
+                    send_message(&state.message_tx, response).await?;
+                }
+                Err(e) => {
+                    let response = ServerMessage::Error {
+                        message: format!("Failed to list snapshots: {}", e)
+                    };
+                    send_message(&state.message_tx, response).await?;
+                }
+            }
+        }
+
+        WebSocketMessage::DiffSnapshots { from, to } => {
+            match crate::snapshots::diff_snapshots(&from, to.as_deref()).await {
+                Ok(diff) => {
+                    let response = ServerMessage::SnapshotDiff { from, to, diff };
+                    send_message(&state.message_tx, response).await?;
+                }
+                Err(e) => {
+                    let response = ServerMessage::Error {
+                        message: format!("Failed to diff snapshots: {}", e)
+                    };
+                    send_message(&state.message_tx, response).await?;
+                }
+            }
+        }
+
+        WebSocketMessage::RestoreSnapshot { id, options } => {
+            info!("Restoring snapshot: {}", id);
+            let response = match crate::snapshots::restore_snapshot(&id, options).await {
+                Ok(result) => ServerMessage::SnapshotRestored {
+                    id,
+                    success: true,
+                    result: Some(result),
+                    error: None,
+                },
+                Err(e) => ServerMessage::SnapshotRestored {
+                    id,
+                    success: false,
+                    result: None,
+                    error: Some(format!("{:#}", e)),
+                },
+            };
+            send_message(&state.message_tx, response).await?;
+        }
+
+        WebSocketMessage::DeleteSnapshot { id } => {
+            let response = match crate::snapshots::delete_snapshot(&id) {
+                Ok(_) => ServerMessage::SnapshotDeleted {
+                    id,
+                    success: true,
+                    error: None,
+                },
+                Err(e) => ServerMessage::SnapshotDeleted {
+                    id,
+                    success: false,
+                    error: Some(format!("{}", e)),
+                },
+            };
+            send_message(&state.message_tx, response).await?;
+        }
+
+        WebSocketMessage::GetSnapshotSettings => {
+            let settings = crate::snapshots::SNAPSHOT_MANAGER.get_settings().await;
+            send_message(&state.message_tx, ServerMessage::SnapshotSettings { settings }).await?;
+        }
+
+        WebSocketMessage::UpdateSnapshotSettings { settings } => {
+            match crate::snapshots::SNAPSHOT_MANAGER.update_settings(settings).await {
+                Ok(settings) => {
+                    send_message(&state.message_tx, ServerMessage::SnapshotSettings { settings }).await?;
+                }
+                Err(e) => {
+                    let response = ServerMessage::Error {
+                        message: format!("Failed to update snapshot settings: {}", e)
+                    };
+                    send_message(&state.message_tx, response).await?;
+                }
+            }
+        }
     }
     
     Ok(())
 }
 
+/// Largest paste accepted in one `Paste` message.
+const MAX_PASTE_BYTES: usize = 4 * 1024 * 1024;
+
+/// Stage `data` in a tmux buffer and paste it into `target`, or into the
+/// attached session when no target is given.
+async fn paste(
+    state: &WsState,
+    id: &str,
+    data: String,
+    target: Option<PaneTarget>,
+    bracketed: bool,
+) -> anyhow::Result<()> {
+    if data.len() > MAX_PASTE_BYTES {
+        anyhow::bail!("Paste is larger than {} MB", MAX_PASTE_BYTES / (1024 * 1024));
+    }
+    let attached = state.current_pty.lock().await.as_ref().map(|pty| pty.tmux_session.clone());
+    let target = match target {
+        Some(target) => target,
+        None => PaneTarget {
+            session_name: attached.ok_or_else(|| anyhow::anyhow!("Not attached to a session"))?,
+            window_index: None,
+            pane_index: None,
+        },
+    };
+    check_writable(state, &target.session_name).await?;
+
+    let buffer = format!("webmux-paste-{}", Uuid::new_v4());
+    let total = data.len();
+    tmux::load_buffer(Some(&buffer), data.as_bytes(), |sent| {
+        let progress = ServerMessage::PasteProgress {
+            id: id.to_string(),
+            sent,
+            total,
+        };
+        if let Ok(json) = serde_json::to_string(&progress) {
+            let _ = state.message_tx.send(BroadcastMessage::Text(Arc::new(json)));
+        }
+    }).await?;
+    tmux::paste_buffer(&buffer, &target, bracketed).await
+}
+
+/// Refuse changes to the session this client is attached to read-only.
+async fn check_writable(state: &WsState, session_name: &str) -> anyhow::Result<()> {
+    if let Some(pty) = state.current_pty.lock().await.as_ref() {
+        if pty.options.read_only && pty.tmux_session == session_name {
+            anyhow::bail!("Attached read-only");
+        }
+    }
+    Ok(())
+}
+
+/// Refuse changes to state every session shares, such as paste buffers,
+/// while this client is attached read-only anywhere.
+async fn check_not_read_only(state: &WsState) -> anyhow::Result<()> {
+    if let Some(pty) = state.current_pty.lock().await.as_ref() {
+        if pty.options.read_only {
+            anyhow::bail!("Attached read-only");
+        }
+    }
+    Ok(())
+}
+
+/// Check that both sessions of a window operation exist and that this
+/// client may change them.
+async fn validate_window_op(state: &WsState, session_name: &str, target_session: &str) -> anyhow::Result<()> {
+    for name in [session_name, target_session] {
+        if name.trim().is_empty() {
+            anyhow::bail!("Session name cannot be empty");
+        }
+        if !tmux::has_session(name).await {
+            anyhow::bail!("Session not found: {}", name);
+        }
+        check_writable(state, name).await?;
+    }
+    Ok(())
+}
+
+/// Push fresh window lists for sessions whose windows changed to every client.
+async fn broadcast_windows_lists(state: &WsState, sessions: &[&str]) {
+    let mut seen = Vec::new();
+    for session_name in sessions {
+        if seen.contains(session_name) {
+            continue;
+        }
+        seen.push(session_name);
+
+        match tmux::list_windows(session_name).await {
+            Ok(windows) => {
+                let message = ServerMessage::WindowsList {
+                    session_name: session_name.to_string(),
+                    windows,
+                };
+                if let Err(e) = state.broadcast_tx.send(message) {
+                    error!("Failed to broadcast windows list: {}", e);
+                }
+            }
+            Err(e) => error!("Failed to list windows for session {}: {}", session_name, e),
+        }
+    }
+}
+
+/// Tell every client the size tmux gave a session, once tmux has had a
+/// moment to react to a resize.
+fn broadcast_session_size(state: &WsState, session_name: &str) {
+    let broadcast_tx = state.broadcast_tx.clone();
+    let session_name = session_name.to_string();
+    tokio::spawn(async move {
+        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+        match resize_policy::session_size(&session_name).await {
+            Ok(size) => {
+                let _ = broadcast_tx.send(ServerMessage::SessionSize {
+                    session_name,
+                    cols: size.cols,
+                    rows: size.rows,
+                });
+            }
+            Err(e) => debug!("Failed to read size of session {}: {}", session_name, e),
+        }
+    });
+}
+
 async fn send_message(tx: &mpsc::UnboundedSender<BroadcastMessage>, msg: ServerMessage) -> anyhow::Result<()> {
     if let Ok(json) = serde_json::to_string(&msg) {
         tx.send(BroadcastMessage::Text(Arc::new(json)))?;
@@ -762,7 +2071,9 @@ async fn attach_to_session(
     session_name: &str,
     cols: u16,
     rows: u16,
+    options: AttachOptions,
 ) -> anyhow::Result<()> {
+    stop_playback(state).await;
     let tx = &state.message_tx;
     // Update current session
     {
@@ -770,26 +2081,56 @@ async fn attach_to_session(
         *current = Some(session_name.to_string());
     }
     
-    // Clean up any existing PTY session first
+    // Keep the current PTY warm and reuse a warm one for this session
     let mut pty_guard = state.current_pty.lock().await;
-    if let Some(old_pty) = pty_guard.take() {
-        debug!("Cleaning up previous PTY session for tmux: {}", old_pty.tmux_session);
-        // Kill the child process
-        {
-            let mut child = old_pty.child.lock().await;
-            let _ = child.kill();
-            let _ = child.wait();
-        }
-        // Abort the reader task
-        old_pty.reader_task.abort();
-        let _ = old_pty.reader_task.await;
+    let reused = {
+        let mut warm = state.warm_ptys.lock().await;
+        let mut evicted = Vec::new();
+        if let Some(old_pty) = pty_guard.take() {
+            debug!("Keeping PTY for tmux session {} warm", old_pty.tmux_session);
+            old_pty.park().await;
+            evicted = warm.park(old_pty);
+        }
+        evicted.extend(warm.remove_where(|pty| !pty.is_alive()));
+        for pty in evicted {
+            shutdown_pty(pty).await;
+        }
+        warm.take(|pty| pty.tmux_session == session_name && pty.options == options)
+    };
+
+    if let Some(pty) = reused {
+        debug!("Reusing warm PTY for: {}", session_name);
+        if pty.screen.lock().await.screen().size() != (rows, cols) {
+            pty.screen.lock().await.resize(rows, cols);
+            pty.master.lock().await.resize(PtySize {
+                rows,
+                cols,
+                pixel_width: 0,
+                pixel_height: 0,
+            })?;
+            if let Some(recorder) = pty.recorder.lock().await.as_mut() {
+                recorder.resize(cols, rows);
+            }
+        }
+        pty.activate(state).await?;
+        let screen = pty.screen.clone();
+        *pty_guard = Some(pty);
+        drop(pty_guard);
+        SCREEN_REGISTRY.register(&state.client_id, session_name, screen).await;
+
+        send_message(tx, ServerMessage::Attached {
+            session_name: session_name.to_string(),
+            options,
+        }).await?;
+        broadcast_session_size(state, session_name);
+        return Ok(());
     }
     
-    // Small delay to ensure cleanup is complete
-    tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
-    
     // Create new PTY session
     debug!("Creating new PTY session for: {}", session_name);
+    if let Err(e) = tmux::enable_clipboard().await {
+        error!("Failed to enable OSC 52 clipboard forwarding: {}", e);
+    }
     
     let pty_system = native_pty_system();
     let pair = pty_system.openpty(PtySize {
@@ -800,7 +2141,9 @@ async fn attach_to_session(
     })?;
     
     let mut cmd = CommandBuilder::new("tmux");
-    cmd.args(&["attach-session", "-t", session_name]);
+    cmd.arg("attach-session");
+    cmd.args(options.tmux_args());
+    cmd.args(["-t", session_name]);
     cmd.env("TERM", "xterm-256color");
     cmd.env("COLORTERM", "truecolor");
     
@@ -829,15 +2172,34 @@ async fn attach_to_session(
     if !check_output.status.success() {
         // Create the session first
         info!("Session {} doesn't exist, creating it", session_name);
-        tmux::create_session(session_name).await?;
+        tmux::create_session(session_name, &SpawnOptions::default()).await?;
+        RESIZE_POLICIES.reapply(session_name).await;
+    }
+    
+    // Paint what other viewers already see while tmux redraws for us
+    if let Some(existing) = SCREEN_REGISTRY.for_session(session_name, Some(&state.client_id)).await {
+        let snapshot = existing.lock().await.snapshot(true);
+        send_message(tx, ServerMessage::ScreenSnapshot {
+            session_name: Some(session_name.to_string()),
+            snapshot: Some(snapshot),
+            error: None,
+        }).await?;
     }
     
     let child = pair.slave.spawn_command(cmd)?;
     let child: Arc<Mutex<Box<dyn portable_pty::Child + Send>>> = Arc::new(Mutex::new(child));
     
     // Set up reader task - DIRECT sending for now to fix the issue
-    let tx_clone = tx.clone();
+    let output = Arc::new(std::sync::Mutex::new(Some(OutputSink {
+        tx: tx.clone(),
+        diff_mode: state.diff_mode.clone(),
+    })));
+    let reader_output = output.clone();
     let client_id = state.client_id.clone();
+    let recorder: Arc<Mutex<Option<recording::Recorder>>> = Arc::new(Mutex::new(None));
+    let reader_recorder = recorder.clone();
+    let screen: screen::SharedScreen = Arc::new(Mutex::new(screen::TerminalScreen::new(rows, cols)));
+    let reader_screen = screen.clone();
     let reader_task = tokio::task::spawn_blocking(move || {
         let mut reader = reader;
         let mut buffer = vec![0u8; 8192]; // Smaller buffer to prevent overwhelming
@@ -846,16 +2208,18 @@ async fn attach_to_session(
         let mut pending_output = String::with_capacity(16384);
         let mut last_send = std::time::Instant::now();
         let mut bytes_since_pause = 0usize;
+        let mut clipboard = crate::clipboard::Osc52Scanner::default();
         
         loop {
             match reader.read(&mut buffer) {
                 Ok(0) => {
                     info!("PTY EOF for client {}", client_id);
                     // Send any pending output
-                    if !pending_output.is_empty() {
+                    let sink = reader_output.lock().unwrap().clone();
+                    if let Some(sink) = sink.filter(|_| !pending_output.is_empty()) {
                         let output = ServerMessage::Output { data: pending_output };
                         if let Ok(json) = serde_json::to_string(&output) {
-                            let _ = tx_clone.send(BroadcastMessage::Text(Arc::new(json)));
+                            let _ = sink.tx.send(BroadcastMessage::Text(Arc::new(json)));
                         }
                     }
                     break;
@@ -866,6 +2230,33 @@ async fn attach_to_session(
                     // Decode and accumulate
                     let (text, _) = utf8_decoder.decode_chunk(&buffer[..n]);
                     if !text.is_empty() {
+                        // Read the sink under the screen lock; see `PtySession::activate`
+                        let sink = {
+                            let mut screen = reader_screen.blocking_lock();
+                            screen.process(text.as_bytes());
+                            reader_output.lock().unwrap().clone()
+                        };
+                        // A parked PTY's recording pauses; `activate` records a
+                        // repaint when the client comes back
+                        if sink.is_some() {
+                            if let Some(recorder) = reader_recorder.blocking_lock().as_mut() {
+                                recorder.output(&text);
+                            }
+                        }
+                        for event in clipboard.scan(&text) {
+                            if let Some(sink) = &sink {
+                                let message = ServerMessage::Clipboard { event };
+                                if let Ok(json) = serde_json::to_string(&message) {
+                                    let _ = sink.tx.send(BroadcastMessage::Text(Arc::new(json)));
+                                }
+                            }
+                        }
+                        let Some(sink) = sink.filter(|sink| !sink.diff_mode.load(Ordering::Relaxed)) else {
+                            // Warm in the pool, or the diff task sends
+                            // screen updates instead
+                            pending_output.clear();
+                            continue;
+                        };
                         pending_output.push_str(&text);
                         
                         bytes_since_pause += text.len();
@@ -878,9 +2269,10 @@ async fn attach_to_session(
                         if should_send && !pending_output.is_empty() {
                             let output = ServerMessage::Output { data: pending_output.clone() };
                             if let Ok(json) = serde_json::to_string(&output) {
-                                if tx_clone.send(BroadcastMessage::Text(Arc::new(json))).is_err() {
-                                    error!("Client {} disconnected, stopping PTY reader", client_id);
-                                    break;
+                                if sink.tx.send(BroadcastMessage::Text(Arc::new(json))).is_err() {
+                                    // The PTY may be parked for a reconnect;
+                                    // it gets closed if nobody claims it
+                                    debug!("Client {} disconnected, dropping PTY output", client_id);
                                 }
                             }
                             pending_output.clear();
@@ -906,9 +2298,12 @@ async fn attach_to_session(
             }
         }
         
-        let disconnected = ServerMessage::Disconnected;
-        if let Ok(json) = serde_json::to_string(&disconnected) {
-            let _ = tx_clone.send(BroadcastMessage::Text(Arc::new(json)));
+        let sink = reader_output.lock().unwrap().clone();
+        if let Some(sink) = sink {
+            let disconnected = ServerMessage::Disconnected;
+            if let Ok(json) = serde_json::to_string(&disconnected) {
+                let _ = sink.tx.send(BroadcastMessage::Text(Arc::new(json)));
+            }
         }
     });
     
@@ -918,42 +2313,172 @@ async fn attach_to_session(
         reader_task,
         child,
         tmux_session: session_name.to_string(),
+        options,
+        recorder,
+        screen: screen.clone(),
+        output,
     };
     
     *pty_guard = Some(pty_session);
     drop(pty_guard);
+    SCREEN_REGISTRY.register(&state.client_id, session_name, screen).await;
     
     // Send attached confirmation
     let response = ServerMessage::Attached {
         session_name: session_name.to_string(),
+        options,
     };
     send_message(tx, response).await?;
+    broadcast_session_size(state, session_name);
     
     Ok(())
 }
 
-async fn cleanup_session(state: &WsState) {
-    info!("Cleaning up session for client: {}", state.client_id);
-    
-    // Clean up PTY session
+const DEFAULT_DIFF_FPS: u32 = 10;
+const MAX_DIFF_FPS: u32 = 30;
+
+/// Send the client's screen as diffs at up to `fps` frames per second.
+/// Frames in between are never sent; each tick covers all changes since the
+/// last one.
+fn spawn_screen_diff(state: &WsState, fps: u32) -> JoinHandle<()> {
+    let current_pty = state.current_pty.clone();
+    let message_tx = state.message_tx.clone();
+    tokio::spawn(async move {
+        let mut differ = screen::ScreenDiffer::default();
+        let mut current_screen: Option<screen::SharedScreen> = None;
+        let mut interval = tokio::time::interval(std::time::Duration::from_millis(1000 / fps as u64));
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+        loop {
+            interval.tick().await;
+            let Some(screen) = current_pty.lock().await.as_ref().map(|pty| pty.screen.clone()) else {
+                continue;
+            };
+            // A new attachment starts with a full redraw
+            if !current_screen.as_ref().is_some_and(|s| Arc::ptr_eq(s, &screen)) {
+                differ.reset();
+                current_screen = Some(screen.clone());
+            }
+
+            let frame = differ.frame(screen.lock().await.screen());
+            if let Some(frame) = frame {
+                let output = ServerMessage::Output {
+                    data: String::from_utf8_lossy(&frame).into_owned(),
+                };
+                if send_message(&message_tx, output).await.is_err() {
+                    break;
+                }
+            }
+        }
+    })
+}
+
+const MIN_PLAYBACK_SPEED: f64 = 0.1;
+const MAX_PLAYBACK_SPEED: f64 = 32.0;
+
+fn clamp_playback_speed(speed: f64) -> f64 {
+    if speed.is_finite() {
+        speed.clamp(MIN_PLAYBACK_SPEED, MAX_PLAYBACK_SPEED)
+    } else {
+        1.0
+    }
+}
+
+/// End this client's playback, if any, and tell it so.
+async fn stop_playback(state: &WsState) {
+    let Some(playback) = state.playback.lock().await.take() else {
+        return;
+    };
+    // Dropping the command sender ends the player, which ends the forwarder
+    drop(playback.commands);
+    let _ = playback.task.await;
+    let _ = send_message(&state.message_tx, ServerMessage::PlaybackStopped { id: playback.id }).await;
+}
+
+/// Move this client's live PTY to the warm pool, leaving the tmux session
+/// running.
+async fn close_pty(state: &WsState) {
     let mut pty_guard = state.current_pty.lock().await;
     if let Some(pty) = pty_guard.take() {
-        info!("Cleaning up PTY for tmux session: {}", pty.tmux_session);
-        
-        // Kill the child process first
-        {
-            let mut child = pty.child.lock().await;
-            let _ = child.kill();
-            let _ = child.wait();
+        debug!("Parking PTY for tmux session: {}", pty.tmux_session);
+        pty.park().await;
+        // The client's terminal is being taken over, so say it lost the PTY
+        let _ = send_message(&state.message_tx, ServerMessage::Disconnected).await;
+        let evicted = state.warm_ptys.lock().await.park(pty);
+        for pty in evicted {
+            shutdown_pty(pty).await;
         }
-        
-        // Abort the reader task
-        pty.reader_task.abort();
-        
-        // Writer and master will be dropped automatically
     }
     drop(pty_guard);
+    SCREEN_REGISTRY.unregister(&state.client_id).await;
+    *state.current_session.lock().await = None;
+}
+
+/// Detach a PTY's tmux client for good.
+async fn shutdown_pty(pty: PtySession) {
+    debug!("Closing PTY for tmux session: {}", pty.tmux_session);
+    pty.deactivate();
+    {
+        let mut child = pty.child.lock().await;
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+    pty.reader_task.abort();
+    let _ = pty.reader_task.await;
+    stop_recording(&pty.recorder).await;
+}
+
+/// Finish any recording running on a PTY that is going away.
+async fn stop_recording(recorder: &Mutex<Option<recording::Recorder>>) {
+    if let Some(recorder) = recorder.lock().await.take() {
+        if let Err(e) = recorder.finish() {
+            error!("Failed to finish recording: {}", e);
+        }
+    }
+}
+
+async fn cleanup_session(state: &WsState) {
+    info!("Cleaning up session for client: {}", state.client_id);
+    
+    // Clean up PTY sessions, or hold them for a reconnect
+    let mut ptys = state.warm_ptys.lock().await.drain();
+    if let Some(pty) = state.current_pty.lock().await.take() {
+        ptys.push(pty);
+    }
+    SCREEN_REGISTRY.unregister(&state.client_id).await;
+    match &state.client_key {
+        Some(key) if !ptys.is_empty() => {
+            info!("Holding {} PTYs for client {} to reconnect", ptys.len(), state.client_id);
+            for pty in &ptys {
+                pty.park().await;
+            }
+            let (token, replaced) = PARKED_PTYS.park(key, ptys);
+            for pty in replaced {
+                shutdown_pty(pty).await;
+            }
+            let key = key.clone();
+            tokio::spawn(async move {
+                tokio::time::sleep(RECONNECT_GRACE).await;
+                for pty in PARKED_PTYS.expire(&key, token) {
+                    shutdown_pty(pty).await;
+                }
+            });
+        }
+        _ => {
+            for pty in ptys {
+                shutdown_pty(pty).await;
+            }
+        }
+    }
     
+    stop_playback(state).await;
+    if let Some(task) = state.diff_task.lock().await.take() {
+        task.abort();
+    }
+    if let Some(handle) = state.thumbnails_handle.lock().await.take() {
+        handle.abort();
+    }
+
     // Clean up chat log watcher
     {
         let mut handle_guard = state.chat_log_handle.lock().await;
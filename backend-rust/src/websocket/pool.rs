@@ -0,0 +1,123 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+/// Recently used attachments kept alive in the background, oldest first.
+pub struct WarmPool<T> {
+    entries: VecDeque<T>,
+    limit: usize,
+}
+
+impl<T> WarmPool<T> {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            entries: VecDeque::new(),
+            limit,
+        }
+    }
+
+    /// Add an entry as the most recently used, returning whatever no longer
+    /// fits.
+    pub fn park(&mut self, entry: T) -> Vec<T> {
+        self.entries.push_back(entry);
+        let excess = self.entries.len().saturating_sub(self.limit);
+        self.entries.drain(..excess).collect()
+    }
+
+    /// Remove and return the most recently used entry matching `pred`.
+    pub fn take(&mut self, pred: impl Fn(&T) -> bool) -> Option<T> {
+        let index = self.entries.iter().rposition(pred)?;
+        self.entries.remove(index)
+    }
+
+    /// Remove every entry matching `pred`.
+    pub fn remove_where(&mut self, pred: impl Fn(&T) -> bool) -> Vec<T> {
+        let (removed, kept) = self.entries.drain(..).partition(pred);
+        self.entries = kept;
+        removed.into()
+    }
+
+    pub fn drain(&mut self) -> Vec<T> {
+        self.entries.drain(..).collect()
+    }
+}
+
+/// Pools of disconnected clients, held for a grace period so a reloading
+/// page can pick its attachments back up.
+pub struct ParkedPools<T> {
+    parked: Mutex<HashMap<String, (u64, Vec<T>)>>,
+    next_token: AtomicU64,
+}
+
+impl<T> ParkedPools<T> {
+    pub fn new() -> Self {
+        Self {
+            parked: Mutex::new(HashMap::new()),
+            next_token: AtomicU64::new(0),
+        }
+    }
+
+    /// Park `entries` under `key`, returning a token for `expire`. Anything
+    /// already parked under the key is returned for closing.
+    pub fn park(&self, key: &str, entries: Vec<T>) -> (u64, Vec<T>) {
+        let token = self.next_token.fetch_add(1, Ordering::Relaxed);
+        let replaced = self
+            .parked
+            .lock()
+            .unwrap()
+            .insert(key.to_string(), (token, entries))
+            .map(|(_, old)| old)
+            .unwrap_or_default();
+        (token, replaced)
+    }
+
+    pub fn claim(&self, key: &str) -> Vec<T> {
+        self.parked
+            .lock()
+            .unwrap()
+            .remove(key)
+            .map(|(_, entries)| entries)
+            .unwrap_or_default()
+    }
+
+    /// Remove entries parked with `token`, unless they were claimed or
+    /// replaced since.
+    pub fn expire(&self, key: &str, token: u64) -> Vec<T> {
+        let mut parked = self.parked.lock().unwrap();
+        match parked.get(key) {
+            Some((current, _)) if *current == token => parked.remove(key).map(|(_, e)| e).unwrap_or_default(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let mut pool = WarmPool::new(2);
+        assert!(pool.park("a").is_empty());
+        assert!(pool.park("b").is_empty());
+        assert_eq!(pool.take(|e| *e == "a"), Some("a"));
+        assert!(pool.park("a").is_empty());
+        assert_eq!(pool.park("c"), vec!["b"]);
+        assert_eq!(pool.remove_where(|e| *e == "c"), vec!["c"]);
+        assert_eq!(pool.drain(), vec!["a"]);
+    }
+
+    #[test]
+    fn expiry_skips_claimed_or_replaced_pools() {
+        let parked = ParkedPools::new();
+        let (first, _) = parked.park("tab", vec![1]);
+        let (second, replaced) = parked.park("tab", vec![2]);
+        assert_eq!(replaced, vec![1]);
+        assert!(parked.expire("tab", first).is_empty());
+        assert_eq!(parked.expire("tab", second), vec![2]);
+
+        let (token, _) = parked.park("tab", vec![3]);
+        assert_eq!(parked.claim("tab"), vec![3]);
+        assert!(parked.expire("tab", token).is_empty());
+    }
+}
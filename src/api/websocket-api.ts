@@ -5,6 +5,7 @@ import type {
   SessionCreateResponse, 
   SessionActionResponse,
   WindowCreateResponse,
+  SpawnOptions,
   SystemStats,
   WsMessage 
 } from '@/types'
@@ -110,10 +111,10 @@ export const websocketApi = {
     return response.sessions
   },
 
-  async createSession(name?: string): Promise<SessionCreateResponse> {
+  async createSession(name?: string, options: SpawnOptions & { detached?: boolean } = {}): Promise<SessionCreateResponse> {
     const response = await sendRequest<{ success: boolean; sessionName?: string; error?: string }>(
       'create-session',
-      { name },
+      { name, ...options },
       'session-created'
     )
     return {
@@ -163,10 +164,10 @@ export const websocketApi = {
     return response.windows
   },
 
-  async createWindow(sessionName: string, windowName?: string): Promise<WindowCreateResponse> {
+  async createWindow(sessionName: string, windowName?: string, options: SpawnOptions & { select?: boolean } = {}): Promise<WindowCreateResponse> {
     const response = await sendRequest<{ success: boolean; error?: string }>(
       'create-window',
-      { sessionName, windowName },
+      { sessionName, windowName, ...options },
       'window-created'
     )
     return {
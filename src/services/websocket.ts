@@ -3,6 +3,18 @@ import type { WsMessage } from '@/types'
 type MessageHandler<T extends WsMessage = WsMessage> = (data: T) => void
 type DisconnectHandler = () => void
 
+// Per-tab id that survives reloads, so the server can hand back this tab's
+// warm terminal attachments after a reconnect
+const getClientKey = (): string => {
+  let key = sessionStorage.getItem('webmux-client-key')
+  if (!key) {
+    // crypto.randomUUID is unavailable over plain http on LAN addresses
+    key = `${Date.now().toString(36)}-${Math.random().toString(36).slice(2)}`
+    sessionStorage.setItem('webmux-client-key', key)
+  }
+  return key
+}
+
 // Singleton WebSocket manager to ensure single connection
 class WebSocketManager {
   private ws: WebSocket | null = null
@@ -38,6 +50,8 @@ class WebSocketManager {
         wsUrl = `${protocol}//${window.location.host}/ws`
       }
       
+      wsUrl += `?clientKey=${encodeURIComponent(getClientKey())}`
+      
       console.log('Connecting to WebSocket:', wsUrl)
       this.ws = new WebSocket(wsUrl)
       
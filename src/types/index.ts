@@ -4,181 +4,1189 @@ export interface WsMessage {
   [key: string]: string | number | boolean | object | null | undefined;
 }
 
-export interface AttachSessionMessage extends WsMessage {
+// Watch-only attachments. With readOnly the server drops the client's input;
+// with ignoreSize the client's terminal size doesn't affect the session's.
+export interface AttachOptions {
+  readOnly?: boolean;
+  ignoreSize?: boolean;
+}
+
+export interface AttachSessionMessage extends WsMessage, AttachOptions {
   type: 'attach-session';
   sessionName: string;
   cols: number;
   rows: number;
 }
 
-export interface InputMessage extends WsMessage {
-  type: 'input';
-  data: string;
+export interface InputMessage extends WsMessage {
+  type: 'input';
+  data: string;
+}
+
+export interface ResizeMessage extends WsMessage {
+  type: 'resize';
+  cols: number;
+  rows: number;
+}
+
+export interface ListWindowsMessage extends WsMessage {
+  type: 'list-windows';
+  sessionName: string;
+}
+
+export interface SelectWindowMessage extends WsMessage {
+  type: 'select-window';
+  sessionName: string;
+  windowIndex: number;
+}
+
+// Server response types
+export interface OutputMessage extends WsMessage {
+  type: 'output';
+  data: string;
+}
+
+export interface AttachedMessage extends WsMessage {
+  type: 'attached';
+  sessionName: string;
+  readOnly: boolean;
+  ignoreSize: boolean;
+}
+
+export interface DisconnectedMessage extends WsMessage {
+  type: 'disconnected';
+}
+
+export interface WindowsListMessage extends WsMessage {
+  type: 'windows-list';
+  sessionName: string;
+  windows: TmuxWindow[];
+}
+
+// TMUX types
+export interface TmuxSession {
+  name: string;
+  windows: number;
+  created: string;
+  attached: boolean;
+  dimensions?: string;
+}
+
+export interface TmuxWindow {
+  index: number;
+  name: string;
+  active: boolean;
+  panes: number;
+  synchronized: boolean;
+}
+
+// A pane addressed by session, window and pane; missing indexes mean the
+// current window / active pane
+export interface PaneTarget {
+  sessionName: string;
+  windowIndex?: number;
+  paneIndex?: number;
+}
+
+// Options for starting a new session or window
+export interface SpawnOptions {
+  cwd?: string;
+  command?: string;
+  env?: Record<string, string>;
+}
+
+// API response types
+export interface ApiResponse<T = unknown> {
+  success: boolean;
+  data?: T;
+  error?: string;
+}
+
+// Terminal types
+export interface TerminalSize {
+  cols: number;
+  rows: number;
+}
+
+// System stats
+export interface SystemStats {
+  hostname: string;
+  platform: string;
+  arch: string;
+  uptime: number;
+  cpu: {
+    model: string;
+    cores: number;
+    usage: number;
+    loadAvg: number[];
+  };
+  memory: {
+    total: number;
+    used: number;
+    free: number;
+    percent: string;
+  };
+}
+
+// Additional WebSocket messages
+export interface SessionsListMessage extends WsMessage {
+  type: 'sessions-list';
+  sessions: TmuxSession[];
+}
+
+export interface StatsMessage extends WsMessage {
+  type: 'stats';
+  stats: {
+    cpu: {
+      cores: number;
+      model: string;
+      usage: number;
+      loadAvg: number[];
+    };
+    memory: {
+      total: number;
+      used: number;
+      free: number;
+      percent: string;
+    };
+    uptime: number;
+    hostname: string;
+    platform: string;
+    arch: string;
+  };
+}
+
+export interface ErrorMessage extends WsMessage {
+  type: 'error';
+  message: string;
+}
+
+export interface WindowSelectedMessage extends WsMessage {
+  type: 'window-selected';
+  sessionName: string;
+  windowIndex: number;
+}
+
+export interface SessionCreatedMessage extends WsMessage {
+  type: 'session-created';
+  success: boolean;
+  sessionName?: string;
+  error?: string;
+}
+
+export interface SessionKilledMessage extends WsMessage {
+  type: 'session-killed';
+  success: boolean;
+  error?: string;
+}
+
+export interface SessionRenamedMessage extends WsMessage {
+  type: 'session-renamed';
+  success: boolean;
+  error?: string;
+}
+
+export interface WindowCreatedMessage extends WsMessage {
+  type: 'window-created';
+  success: boolean;
+  error?: string;
+}
+
+export interface WindowKilledMessage extends WsMessage {
+  type: 'window-killed';
+  success: boolean;
+  error?: string;
+}
+
+export interface WindowRenamedMessage extends WsMessage {
+  type: 'window-renamed';
+  success: boolean;
+  error?: string;
+}
+
+// Window reordering and linking
+export interface MoveWindowMessage extends WsMessage {
+  type: 'move-window';
+  sessionName: string;
+  windowIndex: number;
+  targetSession: string;
+  targetIndex?: number;
+}
+
+export interface SwapWindowsMessage extends WsMessage {
+  type: 'swap-windows';
+  sessionName: string;
+  windowIndex: number;
+  targetSession?: string;
+  targetIndex: number;
+}
+
+export interface LinkWindowMessage extends WsMessage {
+  type: 'link-window';
+  sessionName: string;
+  windowIndex: number;
+  targetSession: string;
+  targetIndex?: number;
+}
+
+export interface UnlinkWindowMessage extends WsMessage {
+  type: 'unlink-window';
+  sessionName: string;
+  windowIndex: number;
+}
+
+export interface RenumberWindowsMessage extends WsMessage {
+  type: 'renumber-windows';
+  sessionName: string;
+}
+
+export interface WindowMovedMessage extends WsMessage {
+  type: 'window-moved';
+  success: boolean;
+  error?: string;
+}
+
+export interface WindowsSwappedMessage extends WsMessage {
+  type: 'windows-swapped';
+  success: boolean;
+  error?: string;
+}
+
+export interface WindowLinkedMessage extends WsMessage {
+  type: 'window-linked';
+  success: boolean;
+  error?: string;
+}
+
+export interface WindowUnlinkedMessage extends WsMessage {
+  type: 'window-unlinked';
+  success: boolean;
+  error?: string;
+}
+
+export interface WindowsRenumberedMessage extends WsMessage {
+  type: 'windows-renumbered';
+  success: boolean;
+  error?: string;
+}
+
+// Large pastes, staged in a tmux buffer and pasted with bracketed-paste
+// markers when the application wants them. Without a target the paste goes
+// to the attached session's active pane. Limited to 4 MB.
+export interface PasteMessage extends WsMessage, Partial<PaneTarget> {
+  type: 'paste';
+  id?: string;
+  data: string;
+  bracketed?: boolean;
+}
+
+export interface PasteProgressMessage extends WsMessage {
+  type: 'paste-progress';
+  id: string;
+  sent: number;
+  total: number;
+}
+
+export interface PastedMessage extends WsMessage {
+  type: 'pasted';
+  id: string;
+  success: boolean;
+  bytes: number;
+  error?: string;
+}
+
+// tmux paste buffers
+export interface TmuxBuffer {
+  name: string;
+  size: number;
+  created: string;
+  // Start of the content, control characters escaped
+  sample: string;
+}
+
+export interface ListBuffersMessage extends WsMessage {
+  type: 'list-buffers';
+}
+
+export interface ShowBufferMessage extends WsMessage {
+  type: 'show-buffer';
+  name: string;
+}
+
+export interface SetBufferMessage extends WsMessage {
+  type: 'set-buffer';
+  // tmux picks a name when omitted
+  name?: string;
+  content: string;
+}
+
+export interface DeleteBufferMessage extends WsMessage {
+  type: 'delete-buffer';
+  name: string;
+}
+
+export interface BuffersListMessage extends WsMessage {
+  type: 'buffers-list';
+  buffers: TmuxBuffer[];
+}
+
+export interface BufferContentMessage extends WsMessage {
+  type: 'buffer-content';
+  name: string;
+  content: string | null;
+  error?: string;
+}
+
+export interface BufferSetMessage extends WsMessage {
+  type: 'buffer-set';
+  success: boolean;
+  error?: string;
+}
+
+export interface BufferDeletedMessage extends WsMessage {
+  type: 'buffer-deleted';
+  name: string;
+  success: boolean;
+  error?: string;
+}
+
+// Text an application in the attached session copied with OSC 52, e.g. a
+// yank in vim or tmux copy-mode. selection is 'c' (clipboard), 'p'
+// (primary), 's0' (default) and so on.
+export interface ClipboardMessage extends WsMessage {
+  type: 'clipboard';
+  selection: string;
+  text: string;
+}
+
+// Input broadcasting
+export interface BroadcastInputMessage extends WsMessage {
+  type: 'broadcast-input';
+  targets: PaneTarget[];
+  data: string;
+}
+
+export interface SetSynchronizePanesMessage extends WsMessage {
+  type: 'set-synchronize-panes';
+  sessionName: string;
+  windowIndex: number;
+  enabled: boolean;
+}
+
+export interface InputBroadcastMessage extends WsMessage {
+  type: 'input-broadcast';
+  success: boolean;
+  delivered: number;
+  failed: { target: PaneTarget; error: string }[];
+}
+
+export interface SynchronizePanesSetMessage extends WsMessage {
+  type: 'synchronize-panes-set';
+  success: boolean;
+  error?: string;
+}
+
+// Scrollback retrieval and search
+export interface GetScrollbackMessage extends WsMessage {
+  type: 'get-scrollback';
+  target: PaneTarget;
+  start?: number;
+  end?: number;
+  escapes?: boolean;
+}
+
+export interface SearchScrollbackMessage extends WsMessage {
+  type: 'search-scrollback';
+  pattern: string;
+  target?: PaneTarget;
+  caseSensitive?: boolean;
+  context?: number;
+  maxResults?: number;
+}
+
+// Line numbers count screen rows from the oldest history row, starting at 0;
+// a wrapped line spans several rows
+export interface ScrollbackRange {
+  start: number;
+  lines: string[];
+  totalLines: number;
+}
+
+export interface ScrollbackLineMatch {
+  line: number;
+  text: string;
+  ranges: [number, number][];
+  before: string[];
+  after: string[];
+}
+
+export interface ScrollbackSearchResults {
+  panes: { target: PaneTarget; matches: ScrollbackLineMatch[] }[];
+  totalMatches: number;
+  truncated: boolean;
+}
+
+export interface ScrollbackMessage extends WsMessage {
+  type: 'scrollback';
+  target: PaneTarget;
+  range?: ScrollbackRange;
+  error?: string;
+}
+
+export interface ScrollbackSearchResultsMessage extends WsMessage {
+  type: 'scrollback-search-results';
+  pattern: string;
+  results?: ScrollbackSearchResults;
+  error?: string;
+}
+
+// Session recording (asciicast v2)
+export interface RecordingInfo {
+  id: string;
+  sessionName: string;
+  title?: string;
+  startedAt: string;
+  // Seconds; null while recording or if the recording was never finished
+  duration: number | null;
+  width: number;
+  height: number;
+  inputRecorded: boolean;
+  size: number;
+  active: boolean;
+}
+
+export interface StartRecordingMessage extends WsMessage {
+  type: 'start-recording';
+  title?: string;
+  recordInput?: boolean;
+}
+
+export interface StopRecordingMessage extends WsMessage {
+  type: 'stop-recording';
+}
+
+export interface ListRecordingsMessage extends WsMessage {
+  type: 'list-recordings';
+}
+
+export interface GetRecordingMessage extends WsMessage {
+  type: 'get-recording';
+  id: string;
+}
+
+export interface DeleteRecordingMessage extends WsMessage {
+  type: 'delete-recording';
+  id: string;
+}
+
+export interface RecordingStartedMessage extends WsMessage {
+  type: 'recording-started';
+  success: boolean;
+  recording?: RecordingInfo;
+  error?: string;
+}
+
+export interface RecordingStoppedMessage extends WsMessage {
+  type: 'recording-stopped';
+  success: boolean;
+  recording?: RecordingInfo;
+  error?: string;
+}
+
+export interface RecordingsListMessage extends WsMessage {
+  type: 'recordings-list';
+  recordings: RecordingInfo[];
+}
+
+export interface RecordingContentMessage extends WsMessage {
+  type: 'recording-content';
+  id: string;
+  content?: string;
+  error?: string;
+}
+
+export interface RecordingDeletedMessage extends WsMessage {
+  type: 'recording-deleted';
+  id: string;
+  success: boolean;
+  error?: string;
+}
+
+// Recording playback
+export interface PlayRecordingMessage extends WsMessage {
+  type: 'play-recording';
+  id: string;
+  speed?: number;
+  idleTimeLimit?: number;
+  start?: number;
+}
+
+export interface PlaybackControlMessage extends WsMessage {
+  type: 'playback-control';
+  action: 'pause' | 'resume' | 'seek' | 'set-speed' | 'stop';
+  position?: number;
+  speed?: number;
+}
+
+export interface PlaybackStatus {
+  id: string;
+  state: 'playing' | 'paused' | 'finished';
+  // Seconds into the idle-capped recording
+  position: number;
+  duration: number;
+  speed: number;
+  width: number;
+  height: number;
+}
+
+export interface PlaybackStatusMessage extends WsMessage {
+  type: 'playback-status';
+  status: PlaybackStatus;
+}
+
+export interface PlaybackStoppedMessage extends WsMessage {
+  type: 'playback-stopped';
+  id: string;
+}
+
+// Server-side screen model
+export interface ScreenSnapshot {
+  cols: number;
+  rows: number;
+  cursorRow: number;
+  cursorCol: number;
+  cursorVisible: boolean;
+  alternateScreen: boolean;
+  title: string;
+  text: string;
+  // Escape sequences that redraw the screen; only present when requested
+  // or sent to a client joining an already-viewed session
+  formatted?: string;
+}
+
+export interface GetScreenMessage extends WsMessage {
+  type: 'get-screen';
+  sessionName?: string;
+  formatted?: boolean;
+}
+
+export interface ScreenSnapshotMessage extends WsMessage {
+  type: 'screen-snapshot';
+  sessionName?: string;
+  snapshot?: ScreenSnapshot;
+  error?: string;
+}
+
+// Output streaming mode. In 'diff' mode `output` messages carry screen
+// updates at a capped frame rate instead of every PTY byte.
+export type StreamMode = 'raw' | 'diff';
+
+export interface SetStreamModeMessage extends WsMessage {
+  type: 'set-stream-mode';
+  mode: StreamMode;
+  fps?: number;
+}
+
+export interface StreamModeChangedMessage extends WsMessage {
+  type: 'stream-mode-changed';
+  mode: StreamMode;
+  fps?: number;
+  error?: string;
+}
+
+// Pane export. The same fields work as query parameters on GET /api/export,
+// which returns the rendered file as a download.
+export type ExportFormat = 'html' | 'svg' | 'text';
+
+export interface ExportPaneMessage extends WsMessage {
+  type: 'export-pane';
+  sessionName: string;
+  windowIndex?: number;
+  paneIndex?: number;
+  format: ExportFormat;
+  // Without a range the visible screen is exported
+  start?: number;
+  end?: number;
+}
+
+export interface PaneExportedMessage extends WsMessage {
+  type: 'pane-exported';
+  format: ExportFormat;
+  filename: string;
+  content?: string;
+  error?: string;
+}
+
+// Window alerts. Windows opt in per alert kind; window-alert is broadcast
+// when tmux raises a bell, activity or silence flag on an opted-in window.
+export type AlertKind = 'bell' | 'activity' | 'silence';
+
+export interface WindowAlertSettings {
+  sessionName: string;
+  windowIndex: number;
+  bell?: boolean;
+  activity?: boolean;
+  // Alert after this many seconds without output
+  silenceSeconds?: number | null;
+}
+
+export interface GetAlertSettingsMessage extends WsMessage {
+  type: 'get-alert-settings';
+}
+
+// Turning every alert off removes the window's settings
+export interface SetWindowAlertsMessage extends WsMessage, WindowAlertSettings {
+  type: 'set-window-alerts';
+}
+
+export interface AlertSettingsMessage extends WsMessage {
+  type: 'alert-settings';
+  settings: WindowAlertSettings[];
+}
+
+export interface WindowAlertMessage extends WsMessage {
+  type: 'window-alert';
+  sessionName: string;
+  windowIndex: number;
+  windowName: string;
+  kind: AlertKind;
+}
+
+// Output watches. A rule is a regex matched against each line a pane prints
+// (escape sequences removed); watch-triggered is broadcast on every match.
+export type WatchAction =
+  | { kind: 'notify' }
+  // Runs with WEBMUX_SESSION, WEBMUX_WINDOW, WEBMUX_PANE and WEBMUX_MATCH set
+  | { kind: 'command'; command: string }
+  // Typed into the pane that matched
+  | { kind: 'send-keys'; keys: string };
+
+export interface WatchRule extends PaneTarget {
+  // Empty or missing on new rules
+  id?: string;
+  name?: string | null;
+  pattern: string;
+  caseInsensitive?: boolean;
+  action: WatchAction;
+  enabled?: boolean;
+  // Seconds between two firings; send-keys rules need at least 1
+  cooldownSeconds?: number;
+}
+
+export interface WatchMatch {
+  ruleId: string;
+  ruleName: string | null;
+  sessionName: string;
+  windowIndex: number;
+  paneIndex: number;
+  line: string;
+  matchedAt: string;
+  action: WatchAction;
+  error?: string;
+}
+
+export interface ListWatchesMessage extends WsMessage {
+  type: 'list-watches';
+}
+
+export interface SaveWatchMessage extends WsMessage, WatchRule {
+  type: 'save-watch';
+}
+
+export interface DeleteWatchMessage extends WsMessage {
+  type: 'delete-watch';
+  id: string;
+}
+
+export interface GetWatchHistoryMessage extends WsMessage {
+  type: 'get-watch-history';
+}
+
+export interface ClearWatchHistoryMessage extends WsMessage {
+  type: 'clear-watch-history';
+}
+
+export interface WatchesListMessage extends WsMessage {
+  type: 'watches-list';
+  rules: WatchRule[];
+}
+
+export interface WatchSavedMessage extends WsMessage {
+  type: 'watch-saved';
+  success: boolean;
+  rule?: WatchRule;
+  error?: string;
+}
+
+export interface WatchDeletedMessage extends WsMessage {
+  type: 'watch-deleted';
+  id: string;
+  success: boolean;
+  error?: string;
+}
+
+export interface WatchHistoryMessage extends WsMessage {
+  type: 'watch-history';
+  // Newest first
+  matches: WatchMatch[];
+}
+
+export interface WatchTriggeredMessage extends WsMessage {
+  type: 'watch-triggered';
+  match: WatchMatch;
+}
+
+// Automation scripts. Steps run in order against one pane; expect waits for
+// output since the previous match and can branch to a labelled step.
+// automation-status is sent to the starting client on every step.
+export interface ExpectCase {
+  pattern: string;
+  goto?: string;
+}
+
+export type AutomationAction =
+  | { action: 'send'; text: string; enter?: boolean; secret?: boolean }
+  // tmux key name, e.g. 'Enter', 'C-c'
+  | { action: 'key'; key: string }
+  | {
+      action: 'expect';
+      pattern?: string;
+      cases?: ExpectCase[];
+      timeoutSeconds?: number;
+      // Label to continue at on timeout; the run fails without one
+      onTimeout?: string;
+    }
+  | { action: 'sleep'; ms: number }
+  | { action: 'goto'; to: string }
+  | { action: 'fail'; message?: string }
+  | { action: 'done' };
+
+export type AutomationStep = AutomationAction & { label?: string };
+
+export interface AutomationScript extends PaneTarget {
+  name?: string;
+  steps: AutomationStep[];
+  // Default for expect steps; 30 if unset
+  timeoutSeconds?: number;
+}
+
+export type AutomationRunState = 'running' | 'succeeded' | 'failed' | 'stopped';
+
+export interface AutomationRunStatus {
+  runId: string;
+  name: string | null;
+  sessionName: string;
+  state: AutomationRunState;
+  step: number;
+  stepCount: number;
+  label: string | null;
+  detail: string | null;
+  startedAt: string;
+  finishedAt: string | null;
+  error?: string;
+}
+
+export interface RunAutomationMessage extends WsMessage, AutomationScript {
+  type: 'run-automation';
+  // Run id; generated if missing
+  id?: string;
+}
+
+export interface StopAutomationMessage extends WsMessage {
+  type: 'stop-automation';
+  runId: string;
+}
+
+export interface ListAutomationsMessage extends WsMessage {
+  type: 'list-automations';
+}
+
+export interface AutomationStatusMessage extends WsMessage, AutomationRunStatus {
+  type: 'automation-status';
+}
+
+export interface AutomationRunsMessage extends WsMessage {
+  type: 'automation-runs';
+  runs: AutomationRunStatus[];
+}
+
+// One-off scheduled input. Pending input survives restarts; input missed by
+// more than 10 minutes (e.g. while the server was down) isn't sent.
+// scheduled-input-sent is broadcast when input comes due.
+export interface ScheduledInput extends PaneTarget {
+  id: string;
+  name: string | null;
+  text: string;
+  enter: boolean;
+  runAt: string;
+  createdAt: string;
+}
+
+export interface ScheduleInputMessage extends WsMessage, PaneTarget {
+  type: 'schedule-input';
+  name?: string;
+  text?: string;
+  // Press Enter after the text; defaults to true
+  enter?: boolean;
+  // Exactly one of runAt (ISO 8601) or delaySeconds
+  runAt?: string;
+  delaySeconds?: number;
+}
+
+export interface ListScheduledInputsMessage extends WsMessage {
+  type: 'list-scheduled-inputs';
+}
+
+export interface CancelScheduledInputMessage extends WsMessage {
+  type: 'cancel-scheduled-input';
+  id: string;
+}
+
+export interface InputScheduledMessage extends WsMessage {
+  type: 'input-scheduled';
+  success: boolean;
+  input?: ScheduledInput;
+  error?: string;
+}
+
+export interface ScheduledInputsListMessage extends WsMessage {
+  type: 'scheduled-inputs-list';
+  // Soonest first
+  inputs: ScheduledInput[];
+}
+
+export interface ScheduledInputCancelledMessage extends WsMessage {
+  type: 'scheduled-input-cancelled';
+  id: string;
+  success: boolean;
+  error?: string;
+}
+
+export interface ScheduledInputSentMessage extends WsMessage {
+  type: 'scheduled-input-sent';
+  input: ScheduledInput;
+  success: boolean;
+  error?: string;
+}
+
+// Pane output logs, written via tmux pipe-pane to
+// ~/.webmux/logs/<session>/pane<id>-<started>.log and rotated by size.
+// Active logs resume when the server restarts if the pane is still there.
+export interface PaneLogOptions {
+  // Write plain text without escape sequences
+  stripAnsi?: boolean;
+  // Rotate past this size; defaults to 10 MB
+  maxBytes?: number;
+  // Rotated files kept as <file>.1 (newest) to <file>.<n>; defaults to 5
+  keepFiles?: number;
+}
+
+export interface PaneLog {
+  id: string;
+  paneId: string;
+  sessionName: string;
+  windowIndex: number;
+  paneIndex: number;
+  file: string;
+  stripAnsi: boolean;
+  maxBytes: number;
+  keepFiles: number;
+  startedAt: string;
+}
+
+export interface PaneLogFile {
+  sessionName: string;
+  name: string;
+  size: number;
+  modified: string;
+  active: boolean;
+}
+
+export interface StartPaneLogMessage extends WsMessage, PaneTarget, PaneLogOptions {
+  type: 'start-pane-log';
+}
+
+export interface StopPaneLogMessage extends WsMessage, PaneTarget {
+  type: 'stop-pane-log';
+}
+
+export interface ListPaneLogsMessage extends WsMessage {
+  type: 'list-pane-logs';
+}
+
+export interface TailPaneLogMessage extends WsMessage {
+  type: 'tail-pane-log';
+  sessionName: string;
+  file: string;
+  // Defaults to 100
+  lines?: number;
+}
+
+export interface PaneLogStartedMessage extends WsMessage {
+  type: 'pane-log-started';
+  success: boolean;
+  log?: PaneLog;
+  error?: string;
 }
 
-export interface ResizeMessage extends WsMessage {
-  type: 'resize';
-  cols: number;
-  rows: number;
+export interface PaneLogStoppedMessage extends WsMessage {
+  type: 'pane-log-stopped';
+  success: boolean;
+  log?: PaneLog;
+  error?: string;
 }
 
-export interface ListWindowsMessage extends WsMessage {
-  type: 'list-windows';
+export interface PaneLogsListMessage extends WsMessage {
+  type: 'pane-logs-list';
+  active: PaneLog[];
+  files: PaneLogFile[];
+}
+
+export interface PaneLogTailMessage extends WsMessage {
+  type: 'pane-log-tail';
   sessionName: string;
+  file: string;
+  content?: string;
+  error?: string;
 }
 
-export interface SelectWindowMessage extends WsMessage {
-  type: 'select-window';
+// Shell integration. For opted-in sessions the server reads OSC 133/633
+// prompt and command markers (and OSC 7 for the cwd) from each pane and
+// records the commands; shell-command is broadcast as each starts and ends.
+export interface CommandBlock {
+  id: string;
+  paneId: string;
   sessionName: string;
   windowIndex: number;
+  paneIndex: number;
+  command: string;
+  cwd: string | null;
+  startedAt: string;
+  // Null while running
+  finishedAt: string | null;
+  // Null while running or if the shell didn't report it
+  exitCode: number | null;
 }
 
-// Server response types
-export interface OutputMessage extends WsMessage {
-  type: 'output';
-  data: string;
+export interface GetShellIntegrationMessage extends WsMessage {
+  type: 'get-shell-integration';
 }
 
-export interface AttachedMessage extends WsMessage {
-  type: 'attached';
+export interface SetShellIntegrationMessage extends WsMessage {
+  type: 'set-shell-integration';
   sessionName: string;
+  enabled: boolean;
 }
 
-export interface DisconnectedMessage extends WsMessage {
-  type: 'disconnected';
+export interface GetCommandHistoryMessage extends WsMessage {
+  type: 'get-command-history';
+  sessionName: string;
+  windowIndex?: number;
+  paneIndex?: number;
+  // Defaults to 100
+  limit?: number;
 }
 
-export interface WindowsListMessage extends WsMessage {
-  type: 'windows-list';
-  sessionName: string;
-  windows: TmuxWindow[];
+export interface ShellIntegrationMessage extends WsMessage {
+  type: 'shell-integration';
+  sessions: string[];
 }
 
-// TMUX types
-export interface TmuxSession {
-  name: string;
-  windows: number;
-  created: string;
-  attached: boolean;
-  dimensions?: string;
+export interface CommandHistoryMessage extends WsMessage {
+  type: 'command-history';
+  // Newest first
+  commands: CommandBlock[];
 }
 
-export interface TmuxWindow {
-  index: number;
-  name: string;
-  active: boolean;
-  panes: number;
+export interface ShellCommandMessage extends WsMessage {
+  type: 'shell-command';
+  command: CommandBlock;
 }
 
-// API response types
-export interface ApiResponse<T = unknown> {
+// Process inspector: the pane's process tree from /proc, with the
+// environment of the foreground process.
+export interface ListeningSocket {
+  protocol: 'tcp' | 'tcp6' | 'udp' | 'udp6';
+  address: string;
+  port: number;
+}
+
+export interface ProcessNode {
+  pid: number;
+  ppid: number;
+  name: string;
+  cmdline: string[];
+  // e.g. 'R', 'S', 'T', 'Z'
+  state: string;
+  // In the terminal's foreground process group
+  foreground: boolean;
+  cpuPercent: number;
+  cpuSeconds: number;
+  memoryKb: number | null;
+  cwd: string | null;
+  listening: ListeningSocket[];
+  children: ProcessNode[];
+}
+
+export interface PaneInspection {
+  panePid: number;
+  foregroundPid: number | null;
+  tree: ProcessNode;
+  // null when it can't be read, e.g. for another user's process
+  environment: Record<string, string> | null;
+}
+
+export interface InspectPaneMessage extends WsMessage, PaneTarget {
+  type: 'inspect-pane';
+  // Mask secret-looking environment values; defaults to true
+  maskSecrets?: boolean;
+}
+
+export interface PaneProcessesMessage extends WsMessage, PaneTarget {
+  type: 'pane-processes';
   success: boolean;
-  data?: T;
+  inspection?: PaneInspection;
   error?: string;
 }
 
-// Terminal types
-export interface TerminalSize {
-  cols: number;
-  rows: number;
+// Pane process control. 'foreground' signals the pane terminal's foreground
+// process group; 'tree' signals the pane process and all its descendants.
+// Dead panes (kept by remain-on-exit) can be restarted with respawn-pane.
+export type PaneSignal = 'INT' | 'TERM' | 'KILL' | 'STOP' | 'CONT';
+export type SignalScope = 'foreground' | 'tree';
+export type RemainOnExit = 'off' | 'on' | 'failed';
+
+export interface PaneState {
+  windowIndex: number;
+  paneIndex: number;
+  paneId: string;
+  pid: number;
+  currentCommand: string;
+  dead: boolean;
+  deadStatus: number | null;
+  deadSignal: number | null;
+  remainOnExit: RemainOnExit;
 }
 
-// System stats
-export interface SystemStats {
-  hostname: string;
-  platform: string;
-  arch: string;
-  uptime: number;
-  cpu: {
-    model: string;
-    cores: number;
-    usage: number;
-    loadAvg: number[];
-  };
-  memory: {
-    total: number;
-    used: number;
-    free: number;
-    percent: string;
-  };
+export interface SignalPaneMessage extends WsMessage, PaneTarget {
+  type: 'signal-pane';
+  signal: PaneSignal;
+  scope?: SignalScope;
 }
 
-// Additional WebSocket messages
-export interface SessionsListMessage extends WsMessage {
-  type: 'sessions-list';
-  sessions: TmuxSession[];
+export interface PaneSignalledMessage extends WsMessage, PaneTarget {
+  type: 'pane-signalled';
+  signal: PaneSignal;
+  success: boolean;
+  pids: number[];
+  error?: string;
 }
 
-export interface StatsMessage extends WsMessage {
-  type: 'stats';
-  stats: {
-    cpu: {
-      cores: number;
-      model: string;
-      usage: number;
-      loadAvg: number[];
-    };
-    memory: {
-      total: number;
-      used: number;
-      free: number;
-      percent: string;
-    };
-    uptime: number;
-    hostname: string;
-    platform: string;
-    arch: string;
-  };
+export interface RespawnPaneMessage extends WsMessage, PaneTarget {
+  type: 'respawn-pane';
+  // Replaces the command the pane was started with
+  command?: string;
 }
 
-export interface ErrorMessage extends WsMessage {
-  type: 'error';
-  message: string;
+export interface PaneRespawnedMessage extends WsMessage, PaneTarget {
+  type: 'pane-respawned';
+  success: boolean;
+  error?: string;
 }
 
-export interface WindowSelectedMessage extends WsMessage {
-  type: 'window-selected';
-  sessionName: string;
-  windowIndex: number;
+export interface SetRemainOnExitMessage extends WsMessage, PaneTarget {
+  type: 'set-remain-on-exit';
+  mode: RemainOnExit;
 }
 
-export interface SessionCreatedMessage extends WsMessage {
-  type: 'session-created';
+export interface RemainOnExitSetMessage extends WsMessage, PaneTarget {
+  type: 'remain-on-exit-set';
+  mode: RemainOnExit;
   success: boolean;
-  sessionName?: string;
   error?: string;
 }
 
-export interface SessionKilledMessage extends WsMessage {
-  type: 'session-killed';
-  success: boolean;
-  error?: string;
+export interface ListPaneStatesMessage extends WsMessage {
+  type: 'list-pane-states';
+  sessionName: string;
+  windowIndex?: number;
 }
 
-export interface SessionRenamedMessage extends WsMessage {
-  type: 'session-renamed';
-  success: boolean;
+export interface PaneStatesMessage extends WsMessage {
+  type: 'pane-states';
+  sessionName: string;
+  panes: PaneState[];
   error?: string;
 }
 
-export interface WindowCreatedMessage extends WsMessage {
-  type: 'window-created';
-  success: boolean;
-  error?: string;
+// Multi-client resize policy. 'fixed' pins the session to a size; the others
+// pick among attached clients. session-size is broadcast whenever the size
+// may have changed, so clients can letterbox.
+export type ResizePolicy =
+  | { mode: 'largest' }
+  | { mode: 'smallest' }
+  | { mode: 'latest' }
+  | { mode: 'fixed'; cols: number; rows: number };
+
+export interface GetResizePolicyMessage extends WsMessage {
+  type: 'get-resize-policy';
+  sessionName: string;
 }
 
-export interface WindowKilledMessage extends WsMessage {
-  type: 'window-killed';
-  success: boolean;
-  error?: string;
+export interface SetResizePolicyMessage extends WsMessage {
+  type: 'set-resize-policy';
+  sessionName: string;
+  // null restores tmux's default behaviour
+  policy: ResizePolicy | null;
 }
 
-export interface WindowRenamedMessage extends WsMessage {
-  type: 'window-renamed';
-  success: boolean;
+export interface ResizePolicyMessage extends WsMessage {
+  type: 'resize-policy';
+  sessionName: string;
+  policy: ResizePolicy | null;
   error?: string;
 }
 
+export interface SessionSizeMessage extends WsMessage {
+  type: 'session-size';
+  sessionName: string;
+  cols: number;
+  rows: number;
+}
+
+// Session overview thumbnails. While subscribed, the server pushes previews
+// of each window's active pane whenever a window shows new activity.
+export interface SubscribeThumbnailsMessage extends WsMessage {
+  type: 'subscribe-thumbnails';
+  // Every session when omitted
+  sessions?: string[];
+  intervalMs?: number;
+  cols?: number;
+  rows?: number;
+}
+
+export interface UnsubscribeThumbnailsMessage extends WsMessage {
+  type: 'unsubscribe-thumbnails';
+}
+
+export interface WindowKey {
+  sessionName: string;
+  windowIndex: number;
+}
+
+export interface WindowThumbnail extends WindowKey {
+  windowName: string;
+  paneWidth: number;
+  paneHeight: number;
+  lines: string[];
+}
+
+export interface ThumbnailsMessage extends WsMessage {
+  type: 'thumbnails';
+  thumbnails: WindowThumbnail[];
+  removed: WindowKey[];
+}
+
 // Audio streaming messages
 export type AudioAction = 'start' | 'stop';
 
@@ -455,6 +1463,222 @@ export interface ChatLogErrorMessage extends WsMessage {
   error: string;
 }
 
+// Session template types
+export type TemplateFormat = 'yaml' | 'toml';
+
+export type TemplatePane = string | {
+  command?: string;
+  root?: string;
+};
+
+export interface TemplateWindow {
+  name?: string;
+  root?: string;
+  layout?: string;
+  command?: string;
+  env?: Record<string, string>;
+  panes?: TemplatePane[];
+}
+
+export interface SessionTemplate {
+  name: string;
+  description?: string;
+  sessionName?: string;
+  root?: string;
+  env?: Record<string, string>;
+  variables?: Record<string, string>;
+  windows: TemplateWindow[];
+}
+
+// Session template client messages
+export interface ListSessionTemplatesMessage extends WsMessage {
+  type: 'list-session-templates';
+}
+
+export interface GetSessionTemplateMessage extends WsMessage {
+  type: 'get-session-template';
+  name: string;
+}
+
+export interface SaveSessionTemplateMessage extends WsMessage {
+  type: 'save-session-template';
+  content: string;
+  format: TemplateFormat;
+  originalName?: string;
+}
+
+export interface DeleteSessionTemplateMessage extends WsMessage {
+  type: 'delete-session-template';
+  name: string;
+}
+
+export interface InstantiateSessionTemplateMessage extends WsMessage {
+  type: 'instantiate-session-template';
+  name: string;
+  sessionName?: string;
+  variables?: Record<string, string>;
+}
+
+// Session template server responses
+export interface SessionTemplatesListMessage extends WsMessage {
+  type: 'session-templates-list';
+  templates: SessionTemplate[];
+}
+
+export interface SessionTemplateContentMessage extends WsMessage {
+  type: 'session-template-content';
+  name: string;
+  content: string;
+  format?: TemplateFormat;
+  error?: string;
+}
+
+export interface SessionTemplateSavedMessage extends WsMessage {
+  type: 'session-template-saved';
+  success: boolean;
+  template?: SessionTemplate;
+  error?: string;
+}
+
+export interface SessionTemplateDeletedMessage extends WsMessage {
+  type: 'session-template-deleted';
+  name: string;
+  success: boolean;
+  error?: string;
+}
+
+export interface SessionTemplateInstantiatedMessage extends WsMessage {
+  type: 'session-template-instantiated';
+  success: boolean;
+  sessionName?: string;
+  error?: string;
+}
+
+// Session snapshot types
+export type SnapshotTrigger = 'manual' | 'scheduled';
+
+export interface SnapshotSummary {
+  id: string;
+  createdAt: string;
+  label?: string;
+  trigger: SnapshotTrigger;
+  sessions: string[];
+  windowCount: number;
+  paneCount: number;
+  hasScrollback: boolean;
+}
+
+export interface SnapshotSettings {
+  intervalMinutes?: number | null;
+  includeScrollback: boolean;
+  keep: number;
+}
+
+export interface RestoreOptions {
+  sessions?: string[];
+  restoreCommands?: boolean;
+  restoreScrollback?: boolean;
+}
+
+export interface SnapshotWindowDiff {
+  index: number;
+  name: string;
+  changes: string[];
+}
+
+export interface SnapshotSessionDiff {
+  name: string;
+  addedWindows: string[];
+  removedWindows: string[];
+  changedWindows: SnapshotWindowDiff[];
+}
+
+export interface SnapshotDiff {
+  addedSessions: string[];
+  removedSessions: string[];
+  changedSessions: SnapshotSessionDiff[];
+}
+
+// Session snapshot client messages
+export interface CreateSnapshotMessage extends WsMessage {
+  type: 'create-snapshot';
+  label?: string;
+  includeScrollback?: boolean;
+}
+
+export interface ListSnapshotsMessage extends WsMessage {
+  type: 'list-snapshots';
+}
+
+export interface DiffSnapshotsMessage extends WsMessage {
+  type: 'diff-snapshots';
+  from: string;
+  to?: string;
+}
+
+export interface RestoreSnapshotMessage extends WsMessage {
+  type: 'restore-snapshot';
+  id: string;
+  options?: RestoreOptions;
+}
+
+export interface DeleteSnapshotMessage extends WsMessage {
+  type: 'delete-snapshot';
+  id: string;
+}
+
+export interface GetSnapshotSettingsMessage extends WsMessage {
+  type: 'get-snapshot-settings';
+}
+
+export interface UpdateSnapshotSettingsMessage extends WsMessage {
+  type: 'update-snapshot-settings';
+  settings: SnapshotSettings;
+}
+
+// Session snapshot server responses
+export interface SnapshotCreatedMessage extends WsMessage {
+  type: 'snapshot-created';
+  success: boolean;
+  snapshot?: SnapshotSummary;
+  error?: string;
+}
+
+export interface SnapshotsListMessage extends WsMessage {
+  type: 'snapshots-list';
+  snapshots: SnapshotSummary[];
+}
+
+export interface SnapshotDiffMessage extends WsMessage {
+  type: 'snapshot-diff';
+  from: string;
+  to?: string;
+  diff: SnapshotDiff;
+}
+
+export interface SnapshotRestoredMessage extends WsMessage {
+  type: 'snapshot-restored';
+  id: string;
+  success: boolean;
+  result?: {
+    restored: string[];
+    skipped: string[];
+  };
+  error?: string;
+}
+
+export interface SnapshotDeletedMessage extends WsMessage {
+  type: 'snapshot-deleted';
+  id: string;
+  success: boolean;
+  error?: string;
+}
+
+export interface SnapshotSettingsMessage extends WsMessage {
+  type: 'snapshot-settings';
+  settings: SnapshotSettings;
+}
+
 // Union type for all server messages
 export type ServerMessage = 
   | SessionsListMessage
@@ -469,6 +1693,60 @@ export type ServerMessage =
   | WindowCreatedMessage
   | WindowKilledMessage
   | WindowRenamedMessage
+  | WindowMovedMessage
+  | WindowsSwappedMessage
+  | WindowLinkedMessage
+  | WindowUnlinkedMessage
+  | WindowsRenumberedMessage
+  | InputBroadcastMessage
+  | PasteProgressMessage
+  | PastedMessage
+  | BuffersListMessage
+  | BufferContentMessage
+  | BufferSetMessage
+  | BufferDeletedMessage
+  | ClipboardMessage
+  | SynchronizePanesSetMessage
+  | ScrollbackMessage
+  | ScrollbackSearchResultsMessage
+  | RecordingStartedMessage
+  | RecordingStoppedMessage
+  | RecordingsListMessage
+  | RecordingContentMessage
+  | RecordingDeletedMessage
+  | PlaybackStatusMessage
+  | PlaybackStoppedMessage
+  | ScreenSnapshotMessage
+  | PaneExportedMessage
+  | StreamModeChangedMessage
+  | ThumbnailsMessage
+  | AlertSettingsMessage
+  | WindowAlertMessage
+  | WatchesListMessage
+  | WatchSavedMessage
+  | WatchDeletedMessage
+  | WatchHistoryMessage
+  | WatchTriggeredMessage
+  | AutomationStatusMessage
+  | AutomationRunsMessage
+  | InputScheduledMessage
+  | ScheduledInputsListMessage
+  | ScheduledInputCancelledMessage
+  | ScheduledInputSentMessage
+  | PaneLogStartedMessage
+  | PaneLogStoppedMessage
+  | PaneLogsListMessage
+  | PaneLogTailMessage
+  | ShellIntegrationMessage
+  | CommandHistoryMessage
+  | ShellCommandMessage
+  | PaneProcessesMessage
+  | PaneSignalledMessage
+  | PaneRespawnedMessage
+  | RemainOnExitSetMessage
+  | PaneStatesMessage
+  | ResizePolicyMessage
+  | SessionSizeMessage
   | StatsMessage
   | ErrorMessage
   | CronJobsListMessage
@@ -484,4 +1762,15 @@ export type ServerMessage =
   | DotfileTemplatesMessage
   | ChatHistoryMessage
   | ChatEventMessage
-  | ChatLogErrorMessage;
\ No newline at end of file
+  | ChatLogErrorMessage
+  | SessionTemplatesListMessage
+  | SessionTemplateContentMessage
+  | SessionTemplateSavedMessage
+  | SessionTemplateDeletedMessage
+  | SessionTemplateInstantiatedMessage
+  | SnapshotCreatedMessage
+  | SnapshotsListMessage
+  | SnapshotDiffMessage
+  | SnapshotRestoredMessage
+  | SnapshotDeletedMessage
+  | SnapshotSettingsMessage;
\ No newline at end of file